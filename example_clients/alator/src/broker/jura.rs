@@ -0,0 +1,778 @@
+use futures::executor;
+use itertools::Itertools;
+use std::{collections::HashMap, mem};
+
+use log::info;
+use rotala::exchange::jura_v1::{Fill, JuraQuote, Order, OrderStatus, Universe};
+use rotala::http::jura::jurav1_client::JuraClient;
+
+use crate::strategy::staticweight::StaticWeightBroker;
+
+use super::{
+    BrokerBuildError, BrokerCost, BrokerEvent, BrokerOperations, BrokerOrder, BrokerOrderType,
+    BrokerState, BrokerStates, BrokerTrade, CashOperations, Clock, DateTime, Portfolio,
+    PortfolioHoldings, Quote, SendOrder, Update, PENDING_TOLERANCE,
+};
+
+type BacktestId = u64;
+type JuraBrokerEvent = BrokerEvent<JuraBrokerOrder>;
+
+/// Order sent to [JuraBroker] by strategies. Jura identifies assets by id and takes sizes and
+/// prices as strings, so orders are converted when they are sent to the exchange: sizes are
+/// rounded down to the size decimals of the asset and prices are rounded to the nearest tick.
+///
+/// Market orders are sent with the current quote as the limit price, so they execute on the next
+/// tick if the price hasn't moved by more than the slippage tolerated by the exchange.
+#[derive(Clone, Debug)]
+pub struct JuraBrokerOrder {
+    pub symbol: String,
+    pub shares: f64,
+    pub price: Option<f64>,
+    pub order_type: BrokerOrderType,
+}
+
+impl JuraBrokerOrder {
+    fn new(
+        symbol: impl Into<String>,
+        shares: f64,
+        price: Option<f64>,
+        order_type: BrokerOrderType,
+    ) -> Self {
+        Self {
+            symbol: symbol.into(),
+            shares,
+            price,
+            order_type,
+        }
+    }
+
+    pub fn market_buy(symbol: impl Into<String>, shares: f64) -> Self {
+        Self::new(symbol, shares, None, BrokerOrderType::MarketBuy)
+    }
+
+    pub fn market_sell(symbol: impl Into<String>, shares: f64) -> Self {
+        Self::new(symbol, shares, None, BrokerOrderType::MarketSell)
+    }
+
+    pub fn limit_buy(symbol: impl Into<String>, shares: f64, price: f64) -> Self {
+        Self::new(symbol, shares, Some(price), BrokerOrderType::LimitBuy)
+    }
+
+    pub fn limit_sell(symbol: impl Into<String>, shares: f64, price: f64) -> Self {
+        Self::new(symbol, shares, Some(price), BrokerOrderType::LimitSell)
+    }
+
+    pub fn stop_buy(symbol: impl Into<String>, shares: f64, price: f64) -> Self {
+        Self::new(symbol, shares, Some(price), BrokerOrderType::StopBuy)
+    }
+
+    pub fn stop_sell(symbol: impl Into<String>, shares: f64, price: f64) -> Self {
+        Self::new(symbol, shares, Some(price), BrokerOrderType::StopSell)
+    }
+
+    pub fn is_buy(&self) -> bool {
+        matches!(
+            self.order_type,
+            BrokerOrderType::MarketBuy | BrokerOrderType::LimitBuy | BrokerOrderType::StopBuy
+        )
+    }
+}
+
+impl BrokerOrder for JuraBrokerOrder {
+    fn get_order_type<T: Into<BrokerOrderType>>(&self) -> BrokerOrderType {
+        self.order_type.clone()
+    }
+    fn get_shares(&self) -> f64 {
+        self.shares
+    }
+    fn get_symbol(&self) -> String {
+        self.symbol.clone()
+    }
+    fn market_buy(symbol: String, shares: f64) -> Self {
+        JuraBrokerOrder::market_buy(symbol, shares)
+    }
+    fn market_sell(symbol: String, shares: f64) -> Self {
+        JuraBrokerOrder::market_sell(symbol, shares)
+    }
+}
+
+// Number of decimal places needed to represent a multiple of step
+fn decimals(step: f64) -> usize {
+    let mut decimals = 0;
+    while decimals < 12 && (step * 10_f64.powi(decimals as i32)).fract().abs() > 1e-9 {
+        decimals += 1;
+    }
+    decimals
+}
+
+/// Implementation of broker that uses the [Jura](rotala::exchange::jura_v1::JuraV1) exchange.
+///
+/// Jura is a derivatives exchange but this broker holds long positions only, in the same way as
+/// [UistBroker](crate::broker::uist::UistBroker), and tracks cash as if positions were paid for in
+/// full. Sell orders are sent as reduce-only so they can't open a short position. The exchange
+/// account should have a balance of at least the cash deposited with the broker. Funding paid or
+/// received on positions is settled against cash.
+#[derive(Debug)]
+pub struct JuraBroker<C: JuraClient> {
+    cash: f64,
+    holdings: PortfolioHoldings,
+    //Kept distinct from holdings because some perf calculations may need to distinguish between
+    //trades that we know are booked vs ones that we think should get booked
+    pending_orders: PortfolioHoldings,
+    //Used to mark last trade seen by broker when reconciling completed trades with exchange
+    last_seen_trade: usize,
+    latest_quotes: HashMap<String, JuraQuote>,
+    log: JuraBrokerLog,
+    trade_costs: Vec<BrokerCost>,
+    broker_state: BrokerState,
+    universe: Universe,
+    http_client: C,
+    backtest_id: BacktestId,
+}
+
+impl<C: JuraClient> StaticWeightBroker<JuraQuote, JuraBrokerOrder> for JuraBroker<C> {}
+
+impl<C: JuraClient> Quote<JuraQuote> for JuraBroker<C> {
+    fn get_quote(&self, symbol: &str) -> Option<JuraQuote> {
+        self.latest_quotes.get(symbol).cloned()
+    }
+
+    fn get_quotes(&self) -> Option<Vec<JuraQuote>> {
+        if self.latest_quotes.is_empty() {
+            return None;
+        }
+
+        let mut tmp = Vec::new();
+        for quote in self.latest_quotes.values() {
+            tmp.push(quote.clone());
+        }
+        Some(tmp)
+    }
+}
+
+impl<C: JuraClient> Portfolio<JuraQuote> for JuraBroker<C> {
+    fn get_trade_costs(&self) -> Vec<BrokerCost> {
+        self.trade_costs.clone()
+    }
+
+    fn get_holdings(&self) -> PortfolioHoldings {
+        self.holdings.clone()
+    }
+
+    fn get_cash_balance(&self) -> f64 {
+        self.cash
+    }
+
+    fn update_cash_balance(&mut self, cash: f64) {
+        self.cash = cash;
+    }
+
+    fn get_position_cost(&self, symbol: &str) -> Option<f64> {
+        self.log.cost_basis(symbol)
+    }
+
+    fn update_holdings(&mut self, symbol: &str, change: f64) {
+        info!(
+            "BROKER: Incrementing holdings in {:?} by {:?}",
+            symbol, change
+        );
+        if (change).eq(&0.0) {
+            self.holdings.remove(symbol);
+        } else {
+            self.holdings.insert(symbol.to_string(), change);
+        }
+    }
+
+    fn get_pending_orders(&self) -> PortfolioHoldings {
+        self.pending_orders.clone()
+    }
+}
+
+impl<C: JuraClient> BrokerStates for JuraBroker<C> {
+    fn get_broker_state(&self) -> BrokerState {
+        self.broker_state.clone()
+    }
+
+    fn update_broker_state(&mut self, state: BrokerState) {
+        self.broker_state = state;
+    }
+}
+
+impl<C: JuraClient> CashOperations<JuraQuote> for JuraBroker<C> {}
+
+impl<C: JuraClient> BrokerOperations<JuraBrokerOrder, JuraQuote> for JuraBroker<C> {}
+
+impl<C: JuraClient> SendOrder<JuraBrokerOrder> for JuraBroker<C> {
+    fn send_order(&mut self, order: JuraBrokerOrder) -> JuraBrokerEvent {
+        //This is an estimate of the cost based on the current price, can still end with negative
+        //balance when we reconcile with actuals, may also reject valid orders at the margin
+        match self.get_broker_state() {
+            BrokerState::Failed => {
+                info!(
+                    "BROKER: Unable to send {:?} order for {:?} shares of {:?} to exchange as broker in Failed state",
+                    order.order_type,
+                    order.shares,
+                    order.symbol
+                );
+                JuraBrokerEvent::OrderInvalid(order)
+            }
+            BrokerState::Ready => {
+                info!(
+                    "BROKER: Attempting to send {:?} order for {:?} shares of {:?} to the exchange",
+                    order.order_type, order.shares, order.symbol
+                );
+
+                let Some(quote) = self.get_quote(&order.symbol) else {
+                    info!(
+                        "BROKER: Unable to send {:?} order for {:?} shares of {:?} to exchange without quote",
+                        order.order_type, order.shares, order.symbol
+                    );
+                    return JuraBrokerEvent::OrderInvalid(order);
+                };
+                let price = if order.is_buy() { quote.ask } else { quote.bid };
+
+                let has_cash = match order.order_type {
+                    BrokerOrderType::MarketBuy | BrokerOrderType::MarketSell => self
+                        .client_has_sufficient_cash::<BrokerOrderType>(&order, &price)
+                        .is_ok(),
+                    _ => true,
+                };
+                if !has_cash
+                    || self
+                        .client_has_sufficient_holdings_for_sale::<BrokerOrderType>(&order)
+                        .is_err()
+                    || self.client_is_issuing_nonsense_order(&order).is_err()
+                {
+                    info!(
+                        "BROKER: Unable to send {:?} order for {:?} shares of {:?} to exchange",
+                        order.order_type, order.shares, order.symbol
+                    );
+                    return JuraBrokerEvent::OrderInvalid(order);
+                }
+
+                let Some(exchange_order) = self.to_exchange_order(&order) else {
+                    info!(
+                        "BROKER: Unable to convert {:?} order for {:?} shares of {:?} into exchange order",
+                        order.order_type, order.shares, order.symbol
+                    );
+                    return JuraBrokerEvent::OrderInvalid(order);
+                };
+                let shares = exchange_order.get_sz().parse::<f64>().unwrap_or_default();
+
+                let res = executor::block_on(
+                    self.http_client
+                        .insert_order(exchange_order, self.backtest_id),
+                );
+                if res.is_err() {
+                    info!(
+                        "BROKER: Exchange failed to accept {:?} order for {:?} shares of {:?}",
+                        order.order_type, order.shares, order.symbol
+                    );
+                    return JuraBrokerEvent::OrderFailure(order);
+                }
+
+                //From the point of view of strategy, an order pending is the same as an order
+                //executed. The exchange can still reject the order when it enters the book, this
+                //is reconciled on the next tick.
+                let order_effect = if order.is_buy() { shares } else { -shares };
+                self.update_pending(&order.symbol, order_effect);
+                info!(
+                    "BROKER: Successfully sent {:?} order for {:?} shares of {:?} to exchange",
+                    order.order_type, order.shares, order.symbol
+                );
+                JuraBrokerEvent::OrderSentToExchange(order)
+            }
+        }
+    }
+
+    fn send_orders(&mut self, orders: &[JuraBrokerOrder]) -> Vec<JuraBrokerEvent> {
+        let mut res = Vec::new();
+        for o in orders {
+            let trade = self.send_order(o.clone());
+            res.push(trade);
+        }
+        res
+    }
+}
+
+impl<C: JuraClient> Update for JuraBroker<C> {
+    /// Called on every tick of clock to ensure that state is synchronized with other components.
+    ///
+    /// * Calls `check` on exchange
+    /// * Updates last seen prices for exchange tick
+    /// * Removes orders rejected or cancelled by the exchange from pending orders
+    /// * Reconciles internal state against fills and funding on current tick
+    /// * Rebalances cash, which can trigger new trades if broker is in invalid state
+    async fn check(&mut self) {
+        if let Ok(tick_response) = self.http_client.tick(self.backtest_id).await {
+            if let Ok(quotes_response) = self.http_client.fetch_quotes(self.backtest_id).await {
+                //Update prices, these prices are not tradable
+                for (symbol, quote) in &quotes_response.quotes {
+                    self.latest_quotes.insert(symbol.clone(), quote.clone());
+                }
+
+                for (order, status) in tick_response
+                    .inserted_orders
+                    .iter()
+                    .zip(&tick_response.order_statuses)
+                {
+                    if let OrderStatus::Error(reason) = status {
+                        info!("BROKER: Order rejected by exchange with {:?}", reason);
+                        if let Some(meta) = self.universe.get(order.get_asset()) {
+                            let symbol = meta.name.clone();
+                            let shares = order.get_sz().parse::<f64>().unwrap_or_default();
+                            let order_effect = if order.is_buy() { -shares } else { shares };
+                            self.update_pending(&symbol, order_effect);
+                        }
+                    }
+                }
+
+                //Orders that rested in the book but were cancelled by the exchange, such as market
+                //orders that missed, will never fill
+                for canceled in &tick_response.canceled_orders {
+                    info!(
+                        "BROKER: Order cancelled by exchange with {:?}",
+                        canceled.status
+                    );
+                    let shares = canceled.order.sz.parse::<f64>().unwrap_or_default();
+                    let order_effect = if canceled.order.side == "B" {
+                        -shares
+                    } else {
+                        shares
+                    };
+                    self.update_pending(&canceled.order.coin, order_effect);
+                }
+
+                for payment in &tick_response.user_funding {
+                    let usdc = payment.usdc.parse::<f64>().unwrap_or_default();
+                    if usdc < 0.0 {
+                        self.debit_force(&-usdc);
+                    } else {
+                        self.credit(&usdc);
+                    }
+                }
+
+                for fill in tick_response.executed_trades {
+                    //Jura sides follow Hyperliquid, B is buy and A is sell
+                    let is_buy = fill.side == "B";
+                    let quantity = fill.get_quantity();
+                    //Force debit so we can end up with negative cash here
+                    if is_buy {
+                        self.debit_force(&fill.get_value());
+                    } else {
+                        self.credit(&fill.get_value());
+                    }
+                    self.log.record(fill.clone());
+
+                    let curr_position = self.get_position_qty(&fill.coin).unwrap_or(0.0);
+                    let updated = if is_buy {
+                        curr_position + quantity
+                    } else {
+                        curr_position - quantity
+                    };
+                    self.update_holdings(&fill.coin, updated);
+
+                    //Liquidations are not created by orders so were never pending
+                    if fill.liquidation.is_none() {
+                        let order_effect = if is_buy { -quantity } else { quantity };
+                        self.update_pending(&fill.coin, order_effect);
+                    }
+
+                    self.last_seen_trade += 1;
+                }
+            }
+        }
+        //Previous step can cause negative cash balance so we have to rebalance here, this
+        //is not instant so will never balance properly if the series is very volatile
+        self.rebalance_cash();
+    }
+}
+
+impl<C: JuraClient> JuraBroker<C> {
+    pub fn cost_basis(&self, symbol: &str) -> Option<f64> {
+        self.log.cost_basis(symbol)
+    }
+
+    pub fn trades_between(&self, start: &i64, stop: &i64) -> Vec<Fill> {
+        self.log.trades_between(start, stop)
+    }
+
+    fn update_pending(&mut self, symbol: &str, change: f64) {
+        let updated = self.pending_orders.get(symbol).unwrap_or(&0.0) + change;
+        if updated.abs() < PENDING_TOLERANCE {
+            self.pending_orders.remove(symbol);
+        } else {
+            self.pending_orders.insert(symbol.to_string(), updated);
+        }
+    }
+
+    /// Converts an order into a Jura order using the trading rules of the asset. Returns None if
+    /// the symbol isn't in the universe, or if there is no quote for a market order or no price
+    /// for other orders.
+    pub fn to_exchange_order(&self, order: &JuraBrokerOrder) -> Option<Order> {
+        let asset = self.universe.get_asset(&order.symbol)?;
+        let meta = self.universe.get(asset)?;
+
+        //Sizes are rounded down so that sells never exceed holdings
+        let step = 10_f64.powi(meta.sz_decimals as i32);
+        let shares = (order.shares * step + 1e-9).floor() / step;
+        let sz = format!("{:.*}", meta.sz_decimals as usize, shares);
+
+        let px_decimals = decimals(meta.tick_size);
+        let to_px = |price: f64| {
+            let ticks = (price / meta.tick_size).round();
+            format!("{:.*}", px_decimals, ticks * meta.tick_size)
+        };
+
+        let exchange_order = match order.order_type {
+            BrokerOrderType::MarketBuy => {
+                Order::market_buy(asset, &sz, &to_px(self.get_quote(&order.symbol)?.ask))
+            }
+            BrokerOrderType::MarketSell => {
+                Order::market_sell(asset, sz, to_px(self.get_quote(&order.symbol)?.bid))
+            }
+            BrokerOrderType::LimitBuy => Order::limit_buy(asset, sz, to_px(order.price?)),
+            BrokerOrderType::LimitSell => Order::limit_sell(asset, sz, to_px(order.price?)),
+            BrokerOrderType::StopBuy => Order::stop_buy(asset, sz, to_px(order.price?)),
+            BrokerOrderType::StopSell => Order::stop_sell(asset, sz, to_px(order.price?)),
+        };
+        //Broker only holds long positions
+        Some(exchange_order.with_reduce_only(!order.is_buy()))
+    }
+}
+
+impl<C: JuraClient> Clock for JuraBroker<C> {
+    fn now(&mut self) -> i64 {
+        let res = executor::block_on(self.http_client.now(self.backtest_id));
+        res.unwrap().now
+    }
+
+    fn has_next(&mut self) -> bool {
+        let res = executor::block_on(self.http_client.now(self.backtest_id));
+        res.unwrap().has_next
+    }
+}
+
+pub struct JuraBrokerBuilder<C: JuraClient> {
+    trade_costs: Vec<BrokerCost>,
+    client: Option<C>,
+    backtest_id: Option<BacktestId>,
+}
+
+impl<C: JuraClient> JuraBrokerBuilder<C> {
+    /// Fetches the first quotes and the asset rules from the exchange, returns an error if either
+    /// request fails.
+    pub async fn build(&mut self) -> Result<JuraBroker<C>, BrokerBuildError> {
+        if self.client.is_none() {
+            panic!("Cannot build broker without client");
+        }
+
+        let mut client = mem::take(&mut self.client).unwrap();
+        let backtest_id = mem::take(&mut self.backtest_id).unwrap();
+
+        //If we don't have quotes on first tick, we shouldn't error but we should expect every
+        //`DataSource` to provide a first tick
+        let mut first_quotes = HashMap::new();
+        let quote_response = client
+            .fetch_quotes(backtest_id)
+            .await
+            .map_err(|err| BrokerBuildError(err.to_string()))?;
+        for (symbol, quote) in &quote_response.quotes {
+            first_quotes.insert(symbol.clone(), quote.clone());
+        }
+
+        let meta = client
+            .meta(backtest_id)
+            .await
+            .map_err(|err| BrokerBuildError(err.to_string()))?;
+
+        Ok(JuraBroker {
+            holdings: PortfolioHoldings::new(),
+            pending_orders: PortfolioHoldings::new(),
+            cash: 0.0,
+            log: JuraBrokerLog::new(),
+            last_seen_trade: 0,
+            trade_costs: self.trade_costs.clone(),
+            latest_quotes: first_quotes,
+            broker_state: BrokerState::Ready,
+            universe: Universe::new(meta.universe),
+            http_client: client,
+            backtest_id,
+        })
+    }
+
+    pub fn with_client(&mut self, client: C, backtest_id: BacktestId) -> &mut Self {
+        self.client = Some(client);
+        self.backtest_id = Some(backtest_id);
+        self
+    }
+
+    pub fn with_trade_costs(&mut self, trade_costs: Vec<BrokerCost>) -> &mut Self {
+        self.trade_costs = trade_costs;
+        self
+    }
+
+    pub fn new() -> Self {
+        JuraBrokerBuilder {
+            trade_costs: Vec::new(),
+            client: None,
+            backtest_id: None,
+        }
+    }
+}
+
+impl<C: JuraClient> Default for JuraBrokerBuilder<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum JuraRecordedEvent {
+    TradeCompleted(Fill),
+}
+
+impl From<Fill> for JuraRecordedEvent {
+    fn from(value: Fill) -> Self {
+        JuraRecordedEvent::TradeCompleted(value)
+    }
+}
+
+//Records events generated by brokers. Used for internal calculations but is public for tax
+//calculations.
+#[doc(hidden)]
+#[derive(Clone, Debug)]
+pub struct JuraBrokerLog {
+    log: Vec<JuraRecordedEvent>,
+}
+
+impl JuraBrokerLog {
+    pub fn record<E: Into<JuraRecordedEvent>>(&mut self, event: E) {
+        let brokerevent: JuraRecordedEvent = event.into();
+        self.log.push(brokerevent);
+    }
+
+    pub fn trades(&self) -> Vec<Fill> {
+        let mut trades = Vec::new();
+        for event in &self.log {
+            let JuraRecordedEvent::TradeCompleted(trade) = event;
+            trades.push(trade.clone());
+        }
+        trades
+    }
+
+    pub fn trades_between(&self, start: &i64, stop: &i64) -> Vec<Fill> {
+        let trades = self.trades();
+        trades
+            .iter()
+            .filter(|v| v.time >= *DateTime::from(*start) && v.time <= *DateTime::from(*stop))
+            .cloned()
+            .collect_vec()
+    }
+
+    pub fn cost_basis(&self, symbol: &str) -> Option<f64> {
+        let mut cum_qty = 0.0;
+        let mut cum_val = f64::default();
+        for event in &self.log {
+            let JuraRecordedEvent::TradeCompleted(trade) = event;
+            if trade.coin.eq(symbol) {
+                if trade.side == "B" {
+                    cum_qty += trade.get_quantity();
+                    cum_val += trade.get_value();
+                } else {
+                    cum_qty -= trade.get_quantity();
+                    cum_val -= trade.get_value();
+                }
+                //reset the value if we are back to zero
+                if (cum_qty).eq(&0.0) {
+                    cum_val = f64::default();
+                }
+            }
+        }
+        if (cum_qty).eq(&0.0) {
+            return None;
+        }
+        Some(cum_val / cum_qty)
+    }
+}
+
+impl JuraBrokerLog {
+    pub fn new() -> Self {
+        JuraBrokerLog { log: Vec::new() }
+    }
+}
+
+impl Default for JuraBrokerLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::broker::{
+        BrokerCashEvent, BrokerCost, CashOperations, Clock, Portfolio, SendOrder, Update,
+    };
+    use rotala::http::jura::jurav1_client::{JuraClient, TestClient};
+    use rotala::input::penelope::Penelope;
+
+    use super::{JuraBroker, JuraBrokerBuilder, JuraBrokerEvent, JuraBrokerOrder};
+
+    async fn setup() -> JuraBroker<TestClient> {
+        let mut source = Penelope::new();
+
+        source.add_quote(100.00, 101.00, 100, "ABC");
+        source.add_quote(10.00, 11.00, 100, "BCD");
+
+        source.add_quote(104.00, 105.00, 101, "ABC");
+        source.add_quote(14.00, 15.00, 101, "BCD");
+
+        source.add_quote(95.00, 96.00, 102, "ABC");
+        source.add_quote(10.00, 11.00, 102, "BCD");
+
+        source.add_quote(95.00, 96.00, 103, "ABC");
+        source.add_quote(10.00, 11.00, 103, "BCD");
+
+        source.add_quote(95.00, 96.00, 104, "ABC");
+        source.add_quote(10.00, 11.00, 104, "BCD");
+
+        let mut client = TestClient::single("Random", source);
+        let resp = client.init("Random".to_string()).await.unwrap();
+
+        JuraBrokerBuilder::new()
+            .with_trade_costs(vec![BrokerCost::PctOfValue(0.01)])
+            .with_client(client, resp.backtest_id)
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cash_deposit_withdraw() {
+        let mut brkr = setup().await;
+        brkr.deposit_cash(&100.0);
+
+        brkr.check().await;
+
+        assert!(matches!(
+            brkr.withdraw_cash(&50.0),
+            BrokerCashEvent::WithdrawSuccess(..)
+        ));
+        assert!(matches!(
+            brkr.withdraw_cash(&51.0),
+            BrokerCashEvent::WithdrawFailure(..)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_that_buy_order_reduces_cash_and_increases_holdings() {
+        let mut brkr = setup().await;
+        brkr.deposit_cash(&100_000.0);
+
+        let res = brkr.send_order(JuraBrokerOrder::market_buy("ABC", 495.0));
+        assert!(matches!(res, JuraBrokerEvent::OrderSentToExchange(..)));
+        assert_eq!(brkr.get_pending_orders().get("ABC"), Some(&495.0));
+
+        brkr.check().await;
+        brkr.check().await;
+
+        assert_eq!(brkr.get_cash_balance(), 100_000.0 - 495.0 * 105.0);
+        assert_eq!(brkr.get_position_qty("ABC"), Some(495.0));
+        assert!(brkr.get_pending_orders().is_empty());
+        assert_eq!(brkr.cost_basis("ABC"), Some(105.0));
+    }
+
+    #[tokio::test]
+    async fn test_that_market_sell_increases_cash_and_decreases_holdings() {
+        let mut brkr = setup().await;
+        brkr.deposit_cash(&100_000.0);
+        brkr.send_order(JuraBrokerOrder::market_buy("ABC", 495.0));
+        brkr.check().await;
+        brkr.check().await;
+        let cash = brkr.get_cash_balance();
+
+        let res = brkr.send_order(JuraBrokerOrder::market_sell("ABC", 295.0));
+        assert!(matches!(res, JuraBrokerEvent::OrderSentToExchange(..)));
+        brkr.check().await;
+        brkr.check().await;
+
+        assert_eq!(brkr.get_position_qty("ABC"), Some(200.0));
+        assert!(brkr.get_cash_balance() > cash);
+    }
+
+    #[tokio::test]
+    async fn test_that_order_rejected_by_exchange_is_removed_from_pending() {
+        let mut brkr = setup().await;
+        brkr.deposit_cash(&100_000.0);
+
+        //Value is below the minimum notional of the exchange
+        let res = brkr.send_order(JuraBrokerOrder::market_buy("BCD", 0.5));
+        assert!(matches!(res, JuraBrokerEvent::OrderSentToExchange(..)));
+        assert!(!brkr.get_pending_orders().is_empty());
+
+        brkr.check().await;
+        assert!(brkr.get_pending_orders().is_empty());
+        assert_eq!(brkr.get_cash_balance(), 100_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_that_market_order_that_misses_is_removed_from_pending() {
+        let mut source = Penelope::new();
+        source.add_quote(100.00, 101.00, 100, "ABC");
+        //Price moves beyond the slippage tolerated by the exchange before the order executes
+        source.add_quote(120.00, 121.00, 101, "ABC");
+        source.add_quote(120.00, 121.00, 102, "ABC");
+        source.add_quote(120.00, 121.00, 103, "ABC");
+
+        let mut client = TestClient::single("Random", source);
+        let resp = client.init("Random".to_string()).await.unwrap();
+        let mut brkr = JuraBrokerBuilder::new()
+            .with_client(client, resp.backtest_id)
+            .build()
+            .await
+            .unwrap();
+        brkr.deposit_cash(&100_000.0);
+
+        let res = brkr.send_order(JuraBrokerOrder::market_buy("ABC", 100.0));
+        assert!(matches!(res, JuraBrokerEvent::OrderSentToExchange(..)));
+        brkr.check().await;
+        brkr.check().await;
+        assert_eq!(brkr.get_pending_orders().get("ABC"), Some(&100.0));
+
+        brkr.check().await;
+        assert!(brkr.get_pending_orders().is_empty());
+        assert_eq!(brkr.get_position_qty("ABC"), None);
+        assert_eq!(brkr.get_cash_balance(), 100_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_that_orders_are_rounded_to_asset_rules() {
+        let brkr = setup().await;
+
+        let order = brkr
+            .to_exchange_order(&JuraBrokerOrder::limit_buy("ABC", 1.123456789, 100.129))
+            .unwrap();
+        assert_eq!(order.get_asset(), 0);
+        assert_eq!(order.get_sz(), "1.12345");
+        assert_eq!(order.get_limit_px(), "100.13");
+
+        let order = brkr
+            .to_exchange_order(&JuraBrokerOrder::market_sell("BCD", 10.0))
+            .unwrap();
+        assert_eq!(order.get_asset(), 1);
+        assert_eq!(order.get_limit_px(), "10.00");
+
+        assert!(brkr
+            .to_exchange_order(&JuraBrokerOrder::market_buy("XYZ", 10.0))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_that_clock_follows_exchange() {
+        let mut brkr = setup().await;
+        assert_eq!(brkr.now(), 100);
+        brkr.check().await;
+        assert_eq!(brkr.now(), 101);
+        assert!(brkr.has_next());
+    }
+}
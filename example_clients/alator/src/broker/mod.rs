@@ -45,6 +45,16 @@
 //!
 //! Should use [UistBrokerBuilder](crate::broker::uist::UistBrokerBuilder) to create. Can create
 //! with optional [BrokerCost].
+//!
+//! ### Jura
+//!
+//! Broker using the [Jura](rotala::exchange::jura_v1::JuraV1) exchange through any
+//! [JuraClient](rotala::http::jura::jurav1_client::JuraClient). Orders are sent with
+//! [JuraBrokerOrder](crate::broker::jura::JuraBrokerOrder) and converted into Jura orders using the
+//! asset rules fetched from the exchange on creation.
+//!
+//! Should use [JuraBrokerBuilder](crate::broker::jura::JuraBrokerBuilder) to create. Can create
+//! with optional [BrokerCost].
 
 use std::{
     collections::HashMap,
@@ -54,11 +64,13 @@ use std::{
 };
 
 use log::info;
+use rotala::exchange::jura_v1::{Fill as JuraFill, JuraQuote};
 use rotala::exchange::uist_v1::{
     Order as UistOrder, OrderType as UistOrderType, Trade as UistTrade, UistQuote,
 };
 use time::{format_description, Date, Month, OffsetDateTime, Weekday};
 
+pub mod jura;
 pub mod uist;
 
 /// Once the broker moves into Failed state then all operations that mutate state are rejected.
@@ -128,6 +140,15 @@ impl BrokerTrade for UistTrade {
     }
 }
 
+impl BrokerTrade for JuraFill {
+    fn get_quantity(&self) -> f64 {
+        self.sz.parse().unwrap_or_default()
+    }
+    fn get_value(&self) -> f64 {
+        self.px.parse::<f64>().unwrap_or_default() * self.get_quantity()
+    }
+}
+
 pub trait BrokerQuote {
     fn get_bid(&self) -> f64;
     fn get_ask(&self) -> f64;
@@ -143,6 +164,16 @@ impl BrokerQuote for UistQuote {
     }
 }
 
+impl BrokerQuote for JuraQuote {
+    fn get_bid(&self) -> f64 {
+        self.bid
+    }
+
+    fn get_ask(&self) -> f64 {
+        self.ask
+    }
+}
+
 /// Implicit in this trait is that the underlying exchange supports at least as many order types
 /// as [BrokerOrderType].
 ///
@@ -192,6 +223,21 @@ pub enum BrokerCashEvent {
     OperationFailure(f64),
 }
 
+//Fills are summed as floats so pending orders that have completed may not net to exactly zero
+pub(crate) const PENDING_TOLERANCE: f64 = 1e-9;
+
+/// Broker could not be created because the exchange returned an error during setup.
+#[derive(Clone, Debug)]
+pub struct BrokerBuildError(pub String);
+
+impl Error for BrokerBuildError {}
+
+impl Display for BrokerBuildError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "Broker could not be created: {}", self.0)
+    }
+}
+
 /// Broker has attempted to execute an order which cannot be completed due to insufficient cash.
 #[derive(Clone, Debug)]
 pub struct InsufficientCashError;
@@ -565,7 +611,7 @@ pub trait BrokerOperations<O: BrokerOrder, Q: BrokerQuote>:
         //the portfolio but exchange doesn't execute any trades. This can happen if we are missing
         //prices at the start of the series
         if self.get_cash_balance() < 0.0 {
-            let shortfall = self.get_cash_balance() * -1.0;
+            let shortfall = -self.get_cash_balance();
             //When we raise cash, we try to raise a small amount more to stop continuous
             //rebalancing, this amount is arbitrary atm
             let plus_buffer = shortfall + 1000.0;
@@ -809,7 +855,7 @@ impl DateTime {
     }
 
     pub fn from_date_string(val: &str, date_fmt: &str) -> Self {
-        let format = format_description::parse(date_fmt).unwrap();
+        let format = format_description::parse_borrowed::<1>(date_fmt).unwrap();
         let parsed_date = Date::parse(val, &format).unwrap();
         let parsed_time = parsed_date.with_time(time::macros::time!(09:00));
         Self::from(parsed_time.assume_utc().unix_timestamp())
@@ -8,7 +8,9 @@ use std::{
 };
 
 use log::info;
-use rotala::exchange::uist_v1::{Order, OrderType, Trade, TradeType, UistQuote, UistV1};
+use rotala::exchange::uist_v1::{
+    Order, OrderId, OrderState, OrderType, Trade, TradeType, UistQuote, UistV1,
+};
 use rotala::http::uist::uistv1_client::Client;
 use rotala::http::uist::uistv1_client::{BacktestId, UistClient};
 
@@ -16,7 +18,7 @@ use crate::{broker::BrokerOrder, strategy::staticweight::StaticWeightBroker};
 
 use super::{
     BrokerCost, BrokerEvent, BrokerOperations, BrokerState, BrokerStates, CashOperations, Clock,
-    DateTime, Portfolio, PortfolioHoldings, Quote, SendOrder, Update,
+    DateTime, Portfolio, PortfolioHoldings, Quote, SendOrder, Update, PENDING_TOLERANCE,
 };
 
 type UistBrokerEvent = BrokerEvent<Order>;
@@ -29,6 +31,9 @@ pub struct UistBroker<C: UistClient> {
     //Kept distinct from holdings because some perf calculations may need to distinguish between
     //trades that we know are booked vs ones that we think should get booked
     pending_orders: PortfolioHoldings,
+    //Orders accepted by the exchange that have not completed, with the shares left to fill. Used
+    //to remove orders that won't fill from pending orders.
+    open_orders: HashMap<OrderId, Order>,
     //Used to mark last trade seen by broker when reconciling completed trades with exchange
     last_seen_trade: usize,
     latest_quotes: HashMap<String, UistQuote>,
@@ -171,8 +176,24 @@ impl<C: UistClient> SendOrder<Order> for UistBroker<C> {
                     return UistBrokerEvent::OrderInvalid(order.clone());
                 }
 
-                self.http_client
-                    .insert_order(order.clone(), self.backtest_id);
+                let res = executor::block_on(
+                    self.http_client
+                        .insert_order(order.clone(), self.backtest_id),
+                );
+                match res {
+                    Ok(resp) => {
+                        self.open_orders.insert(resp.order_id, order.clone());
+                    }
+                    Err(_err) => {
+                        info!(
+                            "BROKER: Exchange failed to accept {:?} order for {:?} shares of {:?}",
+                            order.get_order_type(),
+                            order.get_shares(),
+                            order.get_symbol()
+                        );
+                        return UistBrokerEvent::OrderFailure(order);
+                    }
+                }
                 //From the point of view of strategy, an order pending is the same as an order
                 //executed. If the order is executed, then it is executed. If the order isn't
                 //executed then the strategy must wait but all the strategy's work has been
@@ -222,14 +243,14 @@ impl<C: UistClient> Update for UistBroker<C> {
     /// * Calls `check` on exchange
     /// * Updates last seen prices for exchange tick
     /// * Reconciles internal state against trades completed on current tick
+    /// * Removes orders rejected, cancelled or expired by the exchange from pending orders
     /// * Rebalances cash, which can trigger new trades if broker is in invalid state
     async fn check(&mut self) {
         if let Ok(tick_response) = self.http_client.tick(self.backtest_id).await {
             if let Ok(quotes_response) = self.http_client.fetch_quotes(self.backtest_id).await {
                 //Update prices, these prices are not tradable
                 for (symbol, quote) in &quotes_response.quotes {
-                    self.latest_quotes
-                        .insert(symbol.clone(), quote.clone().into());
+                    self.latest_quotes.insert(symbol.clone(), quote.clone());
                 }
 
                 for trade in tick_response.executed_trades {
@@ -248,22 +269,49 @@ impl<C: UistClient> Update for UistBroker<C> {
                     };
                     self.update_holdings(&trade.symbol, updated);
 
-                    //Because the order has completed, we should be able to unwrap pending_orders safetly
-                    //If this fails then there must be an application bug and panic is required.
-                    let pending = self.pending_orders.get(&trade.symbol).unwrap_or(&0.0);
-
-                    let updated_pending = match trade.typ {
-                        TradeType::Buy => *pending - trade.quantity,
-                        TradeType::Sell => *pending + trade.quantity,
+                    let pending_effect = match trade.typ {
+                        TradeType::Buy => -trade.quantity,
+                        TradeType::Sell => trade.quantity,
                     };
-                    if updated_pending == 0.0 {
-                        self.pending_orders.remove(&trade.symbol);
-                    } else {
-                        self.pending_orders.insert(trade.symbol, updated_pending);
+                    self.update_pending(&trade.symbol, pending_effect);
+
+                    if let Some(order_id) = trade.order_id {
+                        if let Some(order) = self.open_orders.get_mut(&order_id) {
+                            order.shares -= trade.quantity;
+                        }
                     }
 
                     self.last_seen_trade += 1;
                 }
+
+                for update in &tick_response.order_updates {
+                    match &update.state {
+                        OrderState::Filled => {
+                            self.open_orders.remove(&update.order_id);
+                        }
+                        OrderState::Rejected { .. }
+                        | OrderState::Cancelled
+                        | OrderState::Expired => {
+                            //Whatever has not filled will never fill
+                            if let Some(order) = self.open_orders.remove(&update.order_id) {
+                                info!(
+                                    "BROKER: Order {:?} ended by exchange with {:?}",
+                                    update.order_id, update.state
+                                );
+                                let pending_effect = match order.get_order_type() {
+                                    OrderType::MarketBuy
+                                    | OrderType::LimitBuy
+                                    | OrderType::StopBuy => -order.get_shares(),
+                                    OrderType::MarketSell
+                                    | OrderType::LimitSell
+                                    | OrderType::StopSell => order.get_shares(),
+                                };
+                                self.update_pending(order.get_symbol(), pending_effect);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
             }
         }
         //Previous step can cause negative cash balance so we have to rebalance here, this
@@ -273,6 +321,15 @@ impl<C: UistClient> Update for UistBroker<C> {
 }
 
 impl<C: UistClient> UistBroker<C> {
+    fn update_pending(&mut self, symbol: &str, change: f64) {
+        let updated = self.pending_orders.get(symbol).unwrap_or(&0.0) + change;
+        if updated.abs() < PENDING_TOLERANCE {
+            self.pending_orders.remove(symbol);
+        } else {
+            self.pending_orders.insert(symbol.to_string(), updated);
+        }
+    }
+
     pub fn cost_basis(&self, symbol: &str) -> Option<f64> {
         self.log.cost_basis(symbol)
     }
@@ -314,7 +371,7 @@ impl<C: UistClient> UistBrokerBuilder<C> {
         let mut first_quotes = HashMap::new();
         let quote_response = client.fetch_quotes(backtest_id).await.unwrap();
         for (symbol, quote) in &quote_response.quotes {
-            first_quotes.insert(symbol.clone(), quote.clone().into());
+            first_quotes.insert(symbol.clone(), quote.clone());
         }
 
         let holdings = PortfolioHoldings::new();
@@ -325,6 +382,7 @@ impl<C: UistClient> UistBrokerBuilder<C> {
             //Intialised as invalid so errors throw if client tries to run before init
             holdings,
             pending_orders,
+            open_orders: HashMap::new(),
             cash: 0.0,
             log,
             last_seen_trade: 0,
@@ -454,7 +512,7 @@ mod tests {
     use crate::broker::{
         BrokerCashEvent, BrokerCost, BrokerOperations, CashOperations, Portfolio, SendOrder, Update,
     };
-    use rotala::exchange::uist_v1::{Order, OrderType, Trade, TradeType, UistV1};
+    use rotala::exchange::uist_v1::{Order, OrderType, TimeInForce, Trade, TradeType, UistV1};
     use rotala::http::uist::uistv1_client::{Client, TestClient, UistClient};
     use rotala::input::penelope::Penelope;
 
@@ -540,6 +598,37 @@ mod tests {
         assert_eq!(qty, 495.00);
     }
 
+    #[tokio::test]
+    async fn test_that_expired_order_is_removed_from_pending() {
+        let mut source = Penelope::new();
+        source.add_quote(100.00, 101.00, 100, "ABC");
+        source.add_quote(10.00, 11.00, 100, "BCD");
+        //No quote for ABC when the order executes so it expires without filling
+        source.add_quote(10.00, 11.00, 101, "BCD");
+        source.add_quote(100.00, 101.00, 102, "ABC");
+        source.add_quote(10.00, 11.00, 102, "BCD");
+
+        let mut client = TestClient::single("Random", source);
+        let resp = client.init("Random".to_string()).await.unwrap();
+        let mut brkr = UistBrokerBuilder::new()
+            .with_client(client, resp.backtest_id)
+            .build()
+            .await;
+        brkr.deposit_cash(&100_000.0);
+
+        let order = Order::market_buy("ABC", 100.0).with_time_in_force(TimeInForce::Ioc);
+        let res = brkr.send_order(order);
+        assert!(matches!(res, UistBrokerEvent::OrderSentToExchange(..)));
+        assert_eq!(brkr.get_pending_orders().get("ABC"), Some(&100.0));
+
+        brkr.check().await;
+        brkr.check().await;
+
+        assert!(brkr.get_pending_orders().is_empty());
+        assert_eq!(brkr.get_position_qty("ABC"), None);
+        assert_eq!(brkr.get_cash_balance(), 100_000.0);
+    }
+
     #[tokio::test]
     async fn test_that_buy_order_larger_than_cash_fails_with_error_returned_without_panic() {
         let mut brkr = setup().await;
@@ -854,10 +943,7 @@ mod tests {
 
         println!("{:?}", orders);
         let first = orders.first().unwrap();
-        assert!(matches!(
-            first.get_order_type(),
-            OrderType::MarketBuy { .. }
-        ));
+        assert!(matches!(first.get_order_type(), OrderType::MarketBuy));
     }
 
     #[tokio::test]
@@ -894,10 +980,7 @@ mod tests {
 
         println!("{:?}", orders1);
         let first = orders1.first().unwrap();
-        assert!(matches!(
-            first.get_order_type(),
-            OrderType::MarketSell { .. }
-        ));
+        assert!(matches!(first.get_order_type(), OrderType::MarketSell));
     }
 
     #[tokio::test]
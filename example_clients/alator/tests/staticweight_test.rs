@@ -1,9 +1,11 @@
 use std::collections::HashMap;
 
+use alator::broker::jura::JuraBrokerBuilder;
 use alator::broker::uist::UistBrokerBuilder;
 use alator::broker::BrokerCost;
 
 use alator::strategy::staticweight::{PortfolioAllocation, StaticWeightStrategyBuilder};
+use rotala::http::jura::jurav1_client::{JuraClient, TestClient as JuraTestClient};
 use rotala::http::uist::uistv1_client::{TestClient, UistClient};
 use rotala::input::penelope::Penelope;
 
@@ -38,3 +40,45 @@ async fn staticweight_integration_test() {
 
     let _perf = strat.perf(alator::perf::Frequency::Daily);
 }
+
+#[tokio::test]
+async fn staticweight_jura_integration_test() {
+    let initial_cash = 100_000.0;
+    let length_in_days: i64 = 1000;
+
+    let mut weights: PortfolioAllocation = HashMap::new();
+    weights.insert("ABC".to_string(), 0.5);
+    weights.insert("BCD".to_string(), 0.5);
+
+    //ABC doubles halfway through so the final value depends on the holdings bought
+    let mut source = Penelope::new();
+    for date in 100..length_in_days + 100 {
+        let price = if date < 600 { 100.0 } else { 200.0 };
+        source.add_quote(price, price, date, "ABC");
+        source.add_quote(50.0, 50.0, date, "BCD");
+    }
+    let mut client = JuraTestClient::single("Random", source);
+    let resp = client.init("Random".to_string()).await.unwrap();
+
+    let brkr = JuraBrokerBuilder::new()
+        .with_client(client, resp.backtest_id)
+        .with_trade_costs(vec![BrokerCost::PctOfValue(0.01)])
+        .build()
+        .await
+        .unwrap();
+
+    let mut strat = StaticWeightStrategyBuilder::new()
+        .with_brkr(brkr)
+        .with_weights(weights)
+        .default();
+
+    strat.init(&initial_cash);
+    strat.run().await;
+
+    let _perf = strat.perf(alator::perf::Frequency::Daily);
+    //Half of the portfolio, at least, was held in ABC when it doubled
+    let history = strat.get_history();
+    assert_eq!(history.len(), length_in_days as usize);
+    let last = history.last().unwrap().portfolio_value;
+    assert!(last > initial_cash * 1.4 && last < initial_cash * 2.0);
+}
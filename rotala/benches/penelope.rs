@@ -0,0 +1,136 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use rotala::exchange::uist_v1::UistQuoteByDate;
+use rotala::input::penelope::{Penelope, PenelopeQuote, PenelopeQuoteByDate};
+use rotala::input::{collect_quotes, convert_quotes};
+
+const LENGTH: i64 = 10_000;
+const SYMBOLS: [&str; 10] = [
+    "ABC", "BCD", "CDE", "DEF", "EFG", "FGH", "GHI", "HIJ", "IJK", "JKL",
+];
+
+// Copy of the layout used before Penelope moved to columnar storage, kept here so that the two can
+// be compared.
+struct HashMapPenelope {
+    inner: HashMap<i64, PenelopeQuoteByDate>,
+    dates: Vec<i64>,
+}
+
+impl HashMapPenelope {
+    fn new() -> Self {
+        Self {
+            inner: HashMap::new(),
+            dates: Vec::new(),
+        }
+    }
+
+    fn get_quotes(&self, date: &i64) -> Option<&PenelopeQuoteByDate> {
+        self.inner.get(date)
+    }
+
+    fn quotes_between(&self, start: &i64, end: &i64) -> Vec<PenelopeQuote> {
+        let mut res = Vec::new();
+        for date in &self.dates {
+            if date >= start && date <= end {
+                if let Some(quotes) = self.inner.get(date) {
+                    res.extend(quotes.values().cloned());
+                }
+            }
+        }
+        res
+    }
+
+    fn add_quote(&mut self, bid: f64, ask: f64, date: i64, symbol: impl Into<String> + Clone) {
+        if let Entry::Vacant(entry) = self.inner.entry(date) {
+            entry.insert(HashMap::new());
+            self.dates.push(date);
+        }
+
+        let quote = PenelopeQuote {
+            bid,
+            ask,
+            date,
+            symbol: symbol.clone().into(),
+            volume: None,
+        };
+
+        self.inner
+            .get_mut(&date)
+            .unwrap()
+            .insert(symbol.into(), quote);
+    }
+}
+
+fn build_columnar() -> Penelope {
+    let mut source = Penelope::new();
+    for date in 100..LENGTH + 100 {
+        for symbol in SYMBOLS {
+            source.add_quote(100.0, 101.0, date, symbol);
+        }
+    }
+    source
+}
+
+fn build_hashmap() -> HashMapPenelope {
+    let mut source = HashMapPenelope::new();
+    for date in 100..LENGTH + 100 {
+        for symbol in SYMBOLS {
+            source.add_quote(100.0, 101.0, date, symbol);
+        }
+    }
+    source
+}
+
+fn benchmarks(c: &mut Criterion) {
+    c.bench_function("penelope columnar build", |b| b.iter(build_columnar));
+    c.bench_function("penelope hashmap build", |b| b.iter(build_hashmap));
+
+    let columnar = build_columnar();
+    let hashmap = build_hashmap();
+
+    c.bench_function("penelope columnar get_quotes", |b| {
+        b.iter(|| {
+            for date in 100..LENGTH + 100 {
+                black_box(columnar.get_quotes(&date));
+            }
+        })
+    });
+    c.bench_function("penelope hashmap get_quotes", |b| {
+        b.iter(|| {
+            for date in 100..LENGTH + 100 {
+                black_box(hashmap.get_quotes(&date));
+            }
+        })
+    });
+
+    // Exchanges convert quotes into their own type on every tick
+    c.bench_function("penelope columnar get_quotes into exchange quotes", |b| {
+        b.iter(|| {
+            for date in 100..LENGTH + 100 {
+                let quotes: UistQuoteByDate =
+                    collect_quotes(columnar.get_quotes(&date).unwrap().iter());
+                black_box(quotes);
+            }
+        })
+    });
+    c.bench_function("penelope hashmap get_quotes into exchange quotes", |b| {
+        b.iter(|| {
+            for date in 100..LENGTH + 100 {
+                let quotes: UistQuoteByDate = convert_quotes(hashmap.get_quotes(&date).unwrap());
+                black_box(quotes);
+            }
+        })
+    });
+
+    c.bench_function("penelope columnar quotes_between", |b| {
+        b.iter(|| black_box(columnar.quotes_between(&5_000, &5_100)))
+    });
+    c.bench_function("penelope hashmap quotes_between", |b| {
+        b.iter(|| black_box(hashmap.quotes_between(&5_000, &5_100)))
+    });
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
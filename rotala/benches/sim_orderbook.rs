@@ -19,9 +19,9 @@ fn uist_core_loop_test() {
     uist.insert_order(Order::market_buy("ABC", 100.0));
     uist.insert_order(Order::market_buy("ABC", 100.0));
 
-    uist.tick(source.get_quotes_unchecked(&100));
-    uist.tick(source.get_quotes_unchecked(&101));
-    uist.tick(source.get_quotes_unchecked(&102));
+    uist.tick(&source.get_quotes_unchecked(&100));
+    uist.tick(&source.get_quotes_unchecked(&101));
+    uist.tick(&source.get_quotes_unchecked(&102));
 }
 
 fn benchmarks(c: &mut Criterion) {
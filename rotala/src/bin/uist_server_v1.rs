@@ -1,38 +1,45 @@
 use std::env;
+use std::process;
 use std::sync::Mutex;
 
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware::Logger, web, App, HttpServer};
 use rotala::{
-    http::uist::{
-        uistv1_server::{delete_order, fetch_quotes, info, init, insert_order, tick},
-        AppState,
+    http::{
+        config::{ConfigError, ServerConfig},
+        uist::{uistv1_server::configure, AppState},
     },
     input::penelope::Penelope,
 };
 
+fn exit_with(err: ConfigError) -> ! {
+    eprintln!("{err}");
+    process::exit(1);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-
-    let address: String = args[1].clone();
-    let port: u16 = args[2].parse().unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config = ServerConfig::from_args(&args).unwrap_or_else(|err| exit_with(err));
+    config.init_logger();
 
-    let source = Penelope::random(3000, vec!["ABC", "BCD"]);
-    let app_state = AppState::single("RANDOM", source);
+    let mut datasets = config.load_datasets().unwrap_or_else(|err| exit_with(err));
+    if datasets.is_empty() {
+        datasets.insert(
+            "RANDOM".to_string(),
+            Penelope::random(3000, vec!["ABC", "BCD"]),
+        );
+    }
+    let app_state = AppState::create(&mut datasets).with_exchange(config.uist_exchange());
 
     let uist_state = web::Data::new(Mutex::new(app_state));
 
     HttpServer::new(move || {
         App::new()
+            .wrap(Logger::default())
             .app_data(uist_state.clone())
-            .service(info)
-            .service(init)
-            .service(fetch_quotes)
-            .service(tick)
-            .service(insert_order)
-            .service(delete_order)
+            .configure(configure)
     })
-    .bind((address, port))?
+    .bind((config.address.clone(), config.port))?
     .run()
     .await
 }
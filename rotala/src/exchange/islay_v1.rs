@@ -0,0 +1,739 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Exchange, Tick, TickOutput};
+use crate::input::lobster::{LobsterLevel, LobsterQuote, LobsterTrade};
+use crate::input::{convert_quotes, Input, Side};
+
+// Prices are compared within this tolerance so that prices parsed from different sources match
+const PRICE_TOLERANCE: f64 = 1e-9;
+
+fn same_price(a: f64, b: f64) -> bool {
+    (a - b).abs() < PRICE_TOLERANCE
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Level {
+    pub price: f64,
+    pub size: f64,
+}
+
+impl From<LobsterLevel> for Level {
+    fn from(value: LobsterLevel) -> Self {
+        Self {
+            price: value.price,
+            size: value.size,
+        }
+    }
+}
+
+/// Execution in the replayed book. Prints at a level consume the queue ahead of our own orders at
+/// that level before they can fill them.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Print {
+    pub price: f64,
+    pub size: f64,
+    pub aggressor: Side,
+}
+
+impl From<LobsterTrade> for Print {
+    fn from(value: LobsterTrade) -> Self {
+        Self {
+            price: value.price,
+            size: value.size,
+            aggressor: value.aggressor,
+        }
+    }
+}
+
+/// Depth of the book at `date` and the trades printed since the last quote. Levels are sorted from
+/// the best price outwards.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IslayQuote {
+    pub symbol: String,
+    pub date: i64,
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+    #[serde(default)]
+    pub prints: Vec<Print>,
+}
+
+impl IslayQuote {
+    // Levels that orders on `side` join when they rest
+    fn levels(&self, side: Side) -> &[Level] {
+        match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        }
+    }
+
+    // Levels that orders on `side` trade against
+    fn opposite(&self, side: Side) -> &[Level] {
+        match side {
+            Side::Buy => &self.asks,
+            Side::Sell => &self.bids,
+        }
+    }
+
+    // Returns None if `price` is outside the displayed depth, as the size there is unknown
+    fn size_at(&self, side: Side, price: f64) -> Option<f64> {
+        let levels = self.levels(side);
+        let (best, worst) = (levels.first()?.price, levels.last()?.price);
+        let inside = match side {
+            Side::Buy => price <= best + PRICE_TOLERANCE && price >= worst - PRICE_TOLERANCE,
+            Side::Sell => price >= best - PRICE_TOLERANCE && price <= worst + PRICE_TOLERANCE,
+        };
+        if !inside {
+            return None;
+        }
+        Some(
+            levels
+                .iter()
+                .find(|level| same_price(level.price, price))
+                .map(|level| level.size)
+                .unwrap_or(0.0),
+        )
+    }
+}
+
+impl From<LobsterQuote> for IslayQuote {
+    fn from(value: LobsterQuote) -> Self {
+        Self {
+            symbol: value.symbol,
+            date: value.date,
+            bids: value.bids.into_iter().map(Level::from).collect(),
+            asks: value.asks.into_iter().map(Level::from).collect(),
+            prints: value.trades.into_iter().map(Print::from).collect(),
+        }
+    }
+}
+
+pub type IslayQuoteByDate = HashMap<String, IslayQuote>;
+
+pub type OrderId = u64;
+
+/// Lifecycle of an order. Orders are inserted on the next tick, when they are either `Accepted`
+/// into the book or `Rejected`. Only transitions are reported by [IslayV1::tick].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum OrderState {
+    Accepted,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected { reason: String },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OrderUpdate {
+    pub order_id: OrderId,
+    pub state: OrderState,
+}
+
+impl OrderUpdate {
+    fn new(order_id: OrderId, state: OrderState) -> Self {
+        Self { order_id, state }
+    }
+}
+
+/// Whether a fill took liquidity from the book or was a resting order that was traded against.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Fill {
+    pub order_id: OrderId,
+    pub symbol: String,
+    pub side: Side,
+    pub price: f64,
+    pub shares: f64,
+    pub date: i64,
+    pub liquidity: Liquidity,
+}
+
+/// Orders without a price are market orders.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Order {
+    pub order_id: Option<OrderId>,
+    pub symbol: String,
+    pub side: Side,
+    pub shares: f64,
+    pub price: Option<f64>,
+}
+
+impl Order {
+    fn new(side: Side, symbol: impl Into<String>, shares: f64, price: Option<f64>) -> Self {
+        Self {
+            order_id: None,
+            symbol: symbol.into(),
+            side,
+            shares,
+            price,
+        }
+    }
+
+    pub fn market_buy(symbol: impl Into<String>, shares: f64) -> Self {
+        Order::new(Side::Buy, symbol, shares, None)
+    }
+
+    pub fn market_sell(symbol: impl Into<String>, shares: f64) -> Self {
+        Order::new(Side::Sell, symbol, shares, None)
+    }
+
+    pub fn limit_buy(symbol: impl Into<String>, shares: f64, price: f64) -> Self {
+        Order::new(Side::Buy, symbol, shares, Some(price))
+    }
+
+    pub fn limit_sell(symbol: impl Into<String>, shares: f64, price: f64) -> Self {
+        Order::new(Side::Sell, symbol, shares, Some(price))
+    }
+
+    // Whether this order can trade at `price`
+    fn crosses(&self, price: f64) -> bool {
+        match (self.side, self.price) {
+            (_, None) => true,
+            (Side::Buy, Some(limit)) => price <= limit + PRICE_TOLERANCE,
+            (Side::Sell, Some(limit)) => price >= limit - PRICE_TOLERANCE,
+        }
+    }
+}
+
+// Order in the book with the number of shares that have to trade at its price before it fills
+#[derive(Clone, Debug)]
+struct RestingOrder {
+    order: Order,
+    queue_ahead: f64,
+}
+
+/// Exchange that executes orders against L2 depth.
+///
+/// Orders inserted before a tick enter the book on that tick and can execute from the next tick.
+/// Orders that cross the spread walk the book, filling against each level in turn until the order
+/// is complete or the next level is through the limit price. Liquidity taken by one order is not
+/// available to later orders on the same tick, but the book is historical so it is back to full
+/// size on the next tick. The remainder of a limit order rests in the book. Market orders are
+/// immediate or cancel, so any remainder is cancelled.
+///
+/// Limit orders that rest join the back of the queue at their price, behind the displayed size at
+/// that level and any earlier orders at the same price. Prints at or through the price of a
+/// resting order first trade against the queue ahead of it, and the order fills only with the
+/// volume printed after the queue is cleared. If the displayed size at its price falls below the
+/// queue ahead, the queue shrinks to match as cancellations are assumed to come from ahead of the
+/// order.
+#[derive(Clone, Debug, Default)]
+pub struct IslayV1 {
+    book: Vec<RestingOrder>,
+    //This is cleared on every tick
+    order_buffer: Vec<Order>,
+    //Updates that happen between ticks, i.e. cancellations, are reported on the next tick
+    order_updates: Vec<OrderUpdate>,
+    last_inserted: u64,
+    symbols: Option<HashSet<String>>,
+}
+
+impl IslayV1 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the symbols that can be traded, orders for any other symbol are rejected.
+    pub fn with_symbols(mut self, symbols: Vec<String>) -> Self {
+        self.symbols = Some(symbols.into_iter().collect());
+        self
+    }
+
+    /// Returns the id of the order immediately, any id set on the order is overwritten. Orders are
+    /// only inserted into the book when tick is called.
+    pub fn insert_order(&mut self, mut order: Order) -> OrderId {
+        let order_id = self.last_inserted;
+        self.last_inserted += 1;
+        order.order_id = Some(order_id);
+        self.order_buffer.push(order);
+        order_id
+    }
+
+    /// Returns true if the order was waiting for the next tick or was in the book, the
+    /// cancellation is reported on the next tick.
+    pub fn delete_order(&mut self, order_id: OrderId) -> bool {
+        let before = self.order_buffer.len() + self.book.len();
+        self.order_buffer
+            .retain(|order| order.order_id != Some(order_id));
+        self.book
+            .retain(|resting| resting.order.order_id != Some(order_id));
+        if self.order_buffer.len() + self.book.len() < before {
+            self.order_updates
+                .push(OrderUpdate::new(order_id, OrderState::Cancelled));
+            return true;
+        }
+        false
+    }
+
+    fn validate_order(&self, order: &Order) -> Result<(), String> {
+        if order.shares <= 0.0 || !order.shares.is_finite() {
+            return Err(format!("invalid shares {}", order.shares));
+        }
+        if let Some(price) = order.price {
+            if price <= 0.0 || !price.is_finite() {
+                return Err(format!("invalid price {price}"));
+            }
+        }
+        if let Some(symbols) = &self.symbols {
+            if !symbols.contains(&order.symbol) {
+                return Err(format!("unknown symbol {}", order.symbol));
+            }
+        }
+        Ok(())
+    }
+
+    // Shares ahead of a new order: the displayed size at its price and our own orders already
+    // resting at that price
+    fn queue_ahead(&self, order: &Order, quotes: &IslayQuoteByDate) -> f64 {
+        let Some(price) = order.price else {
+            return 0.0;
+        };
+        let displayed = quotes
+            .get(&order.symbol)
+            .and_then(|quote| quote.size_at(order.side, price))
+            .unwrap_or(0.0);
+        let own: f64 = self
+            .book
+            .iter()
+            .filter(|resting| {
+                resting.order.symbol == order.symbol
+                    && resting.order.side == order.side
+                    && resting
+                        .order
+                        .price
+                        .is_some_and(|other| same_price(other, price))
+            })
+            .map(|resting| resting.order.shares)
+            .sum();
+        displayed + own
+    }
+
+    /// Quotes can be of any type that converts into [IslayQuote].
+    pub fn tick<Q: Clone + Into<IslayQuote>>(
+        &mut self,
+        quotes: &HashMap<String, Q>,
+    ) -> TickOutput<Self> {
+        self.execute_tick(&convert_quotes(quotes))
+    }
+
+    fn execute_tick(&mut self, quotes: &IslayQuoteByDate) -> TickOutput<Self> {
+        let mut order_updates = std::mem::take(&mut self.order_updates);
+
+        //To eliminate lookahead bias, new orders are only inserted after the orders already in the
+        //book have executed
+        let (fills, mut execution_updates) = self.execute_orders(quotes);
+        order_updates.append(&mut execution_updates);
+
+        let inserted_orders = std::mem::take(&mut self.order_buffer);
+        for order in inserted_orders.iter() {
+            let order_id = order.order_id.unwrap();
+            match self.validate_order(order) {
+                Ok(()) => {
+                    let queue_ahead = self.queue_ahead(order, quotes);
+                    self.book.push(RestingOrder {
+                        order: order.clone(),
+                        queue_ahead,
+                    });
+                    order_updates.push(OrderUpdate::new(order_id, OrderState::Accepted));
+                }
+                Err(reason) => {
+                    order_updates.push(OrderUpdate::new(order_id, OrderState::Rejected { reason }));
+                }
+            }
+        }
+        TickOutput {
+            fills,
+            inserted_orders,
+            events: order_updates,
+        }
+    }
+
+    fn execute_orders(&mut self, quotes: &IslayQuoteByDate) -> (Vec<Fill>, Vec<OrderUpdate>) {
+        let mut fills = Vec::new();
+        let mut order_updates = Vec::new();
+        // Liquidity taken from each level of the book on this tick
+        let mut taken: HashMap<(&str, Side, usize), f64> = HashMap::new();
+        // Shares of our own orders resting at each price, in the queue ahead of later orders.
+        // Prices are matched with same_price so this can't be keyed by price.
+        let mut own_ahead: Vec<(&str, Side, f64, f64)> = Vec::new();
+
+        for resting in self.book.iter_mut() {
+            let order = &resting.order;
+            let Some(quote) = quotes.get(&order.symbol) else {
+                continue;
+            };
+            let order_id = order.order_id.unwrap();
+            let fill = |price: f64, shares: f64, liquidity: Liquidity| Fill {
+                order_id,
+                symbol: order.symbol.clone(),
+                side: order.side,
+                price,
+                shares,
+                date: quote.date,
+                liquidity,
+            };
+
+            let mut remaining = order.shares;
+            for (pos, level) in quote.opposite(order.side).iter().enumerate() {
+                if remaining <= 0.0 || !order.crosses(level.price) {
+                    break;
+                }
+                let used = taken
+                    .entry((quote.symbol.as_str(), order.side, pos))
+                    .or_default();
+                let shares = remaining.min((level.size - *used).max(0.0));
+                if shares <= 0.0 {
+                    continue;
+                }
+                *used += shares;
+                remaining -= shares;
+                fills.push(fill(level.price, shares, Liquidity::Taker));
+            }
+
+            if remaining < order.shares {
+                // Remainder is now the best price at its level
+                resting.queue_ahead = 0.0;
+            } else if let Some(limit) = order.price {
+                let mut shares = 0.0;
+                for print in quote
+                    .prints
+                    .iter()
+                    .filter(|print| print.aggressor != order.side)
+                {
+                    // Prints through the limit traded at our price first, so they clear the
+                    // queue ahead in the same way as prints at the limit
+                    if order.crosses(print.price) {
+                        let excess = print.size - resting.queue_ahead;
+                        resting.queue_ahead = (resting.queue_ahead - print.size).max(0.0);
+                        if excess > 0.0 {
+                            shares = remaining.min(shares + excess);
+                        }
+                    }
+                }
+                if shares > 0.0 {
+                    remaining -= shares;
+                    fills.push(fill(limit, shares, Liquidity::Maker));
+                }
+                if let Some(displayed) = quote.size_at(order.side, limit) {
+                    let own: f64 = own_ahead
+                        .iter()
+                        .filter(|(symbol, side, price, _)| {
+                            *symbol == quote.symbol
+                                && *side == order.side
+                                && same_price(*price, limit)
+                        })
+                        .map(|(_, _, _, shares)| shares)
+                        .sum();
+                    resting.queue_ahead = resting.queue_ahead.min(displayed + own);
+                }
+            }
+            if let Some(limit) = order.price {
+                own_ahead.push((&quote.symbol, order.side, limit, remaining.max(0.0)));
+            }
+
+            if remaining < order.shares {
+                let state = if remaining <= 0.0 {
+                    OrderState::Filled
+                } else {
+                    OrderState::PartiallyFilled
+                };
+                order_updates.push(OrderUpdate::new(order_id, state));
+            }
+            // Market orders are immediate or cancel, anything that can't fill against the book on
+            // the first tick is cancelled rather than resting without a price
+            if order.price.is_none() && remaining > 0.0 {
+                order_updates.push(OrderUpdate::new(order_id, OrderState::Cancelled));
+                remaining = 0.0;
+            }
+            resting.order.shares = remaining;
+        }
+        self.book.retain(|resting| resting.order.shares > 0.0);
+        (fills, order_updates)
+    }
+}
+
+impl Exchange for IslayV1 {
+    type Quote = IslayQuote;
+    type Order = Order;
+    type OrderId = OrderId;
+    type Fill = Fill;
+    type InsertResponse = OrderId;
+    type DeleteResponse = bool;
+    type TickEvents = Vec<OrderUpdate>;
+
+    fn for_dataset<I: Input>(&self, dataset: &I) -> Self {
+        self.clone().with_symbols(dataset.get_symbols().to_vec())
+    }
+
+    fn insert_order(&mut self, order: Order) -> OrderId {
+        IslayV1::insert_order(self, order)
+    }
+
+    fn delete_order(&mut self, order_id: OrderId) -> bool {
+        IslayV1::delete_order(self, order_id)
+    }
+}
+
+impl<I: Input> Tick<I> for IslayV1 {
+    fn tick(&mut self, dataset: &I, date: i64) -> TickOutput<Self>
+    where
+        I::Quote: Into<IslayQuote>,
+    {
+        match self.quotes(dataset, date) {
+            Some(quotes) => self.execute_tick(&quotes),
+            None => TickOutput::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IslayV1, Liquidity, Order, OrderState, Side};
+    use crate::exchange::TickOutput;
+    use crate::input::lobster::{Lobster, LobsterLevel, LobsterQuote, LobsterTrade};
+
+    fn level(price: f64, size: f64) -> LobsterLevel {
+        LobsterLevel { price, size }
+    }
+
+    fn trade(price: f64, size: f64, aggressor: Side) -> LobsterTrade {
+        LobsterTrade {
+            price,
+            size,
+            aggressor,
+        }
+    }
+
+    fn add_quote(source: &mut Lobster, date: i64, bid_size: f64, trades: Vec<LobsterTrade>) {
+        source.add_quote(LobsterQuote {
+            symbol: "ABC".to_string(),
+            date,
+            bids: vec![level(99.0, bid_size), level(98.0, 200.0)],
+            asks: vec![
+                level(100.0, 100.0),
+                level(101.0, 200.0),
+                level(102.0, 300.0),
+            ],
+            trades,
+        });
+    }
+
+    fn setup() -> (Lobster, IslayV1) {
+        let mut source = Lobster::new();
+        add_quote(&mut source, 100, 300.0, Vec::new());
+        add_quote(
+            &mut source,
+            101,
+            300.0,
+            vec![trade(99.0, 250.0, Side::Sell)],
+        );
+        add_quote(
+            &mut source,
+            102,
+            300.0,
+            vec![trade(99.0, 100.0, Side::Sell)],
+        );
+        add_quote(&mut source, 103, 300.0, vec![trade(98.0, 60.0, Side::Sell)]);
+        (source, IslayV1::new())
+    }
+
+    #[test]
+    fn test_that_market_order_walks_the_book() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::market_buy("ABC", 250.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        let TickOutput {
+            fills,
+            events: updates,
+            ..
+        } = exchange.tick(source.get_quotes(&101).unwrap());
+
+        assert_eq!(fills.len(), 2);
+        assert_eq!((fills[0].price, fills[0].shares), (100.0, 100.0));
+        assert_eq!((fills[1].price, fills[1].shares), (101.0, 150.0));
+        assert!(fills.iter().all(|fill| fill.liquidity == Liquidity::Taker));
+        assert_eq!(updates[0].state, OrderState::Filled);
+    }
+
+    #[test]
+    fn test_that_liquidity_taken_is_shared_between_orders_on_same_tick() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::limit_buy("ABC", 80.0, 100.0));
+        exchange.insert_order(Order::limit_buy("ABC", 80.0, 100.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        let TickOutput {
+            fills,
+            events: updates,
+            ..
+        } = exchange.tick(source.get_quotes(&101).unwrap());
+
+        //Only 100 shares are offered at the limit so the second order rests with the remainder
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[1].shares, 20.0);
+        assert_eq!(updates[1].state, OrderState::PartiallyFilled);
+    }
+
+    #[test]
+    fn test_that_resting_limit_fills_once_volume_exceeds_queue_ahead() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 99.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+
+        //250 trade at 99 but 300 are ahead in the queue
+        let fills = exchange.tick(source.get_quotes(&101).unwrap()).fills;
+        assert!(fills.is_empty());
+
+        //Another 100 trade, the first 50 clear the queue and the rest fill the order
+        let TickOutput {
+            fills,
+            events: updates,
+            ..
+        } = exchange.tick(source.get_quotes(&102).unwrap());
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].shares, 50.0);
+        assert_eq!(fills[0].price, 99.0);
+        assert_eq!(fills[0].side, Side::Buy);
+        assert_eq!(fills[0].liquidity, Liquidity::Maker);
+        assert_eq!(updates[0].state, OrderState::PartiallyFilled);
+
+        //Trade through the price fills the remainder
+        let TickOutput {
+            fills,
+            events: updates,
+            ..
+        } = exchange.tick(source.get_quotes(&103).unwrap());
+        assert_eq!(fills[0].shares, 50.0);
+        assert_eq!(updates[0].state, OrderState::Filled);
+    }
+
+    #[test]
+    fn test_that_trade_through_price_fills_only_volume_after_queue_ahead() {
+        let mut source = Lobster::new();
+        add_quote(&mut source, 100, 300.0, Vec::new());
+        add_quote(
+            &mut source,
+            101,
+            300.0,
+            vec![trade(98.0, 320.0, Side::Sell)],
+        );
+        let mut exchange = IslayV1::new();
+
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 99.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        let TickOutput {
+            fills,
+            events: updates,
+            ..
+        } = exchange.tick(source.get_quotes(&101).unwrap());
+
+        //300 ahead in the queue trade first, so only 20 of the print fill the order
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].shares, 20.0);
+        assert_eq!(fills[0].price, 99.0);
+        assert_eq!(updates[0].state, OrderState::PartiallyFilled);
+    }
+
+    #[test]
+    fn test_that_market_order_remainder_is_cancelled() {
+        let (source, mut exchange) = setup();
+
+        let order_id = exchange.insert_order(Order::market_buy("ABC", 700.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        let TickOutput {
+            fills,
+            events: updates,
+            ..
+        } = exchange.tick(source.get_quotes(&101).unwrap());
+
+        //Only 600 are offered so the rest of the order is cancelled
+        let filled: f64 = fills.iter().map(|fill| fill.shares).sum();
+        assert_eq!(filled, 600.0);
+        assert_eq!(updates[0].state, OrderState::PartiallyFilled);
+        assert_eq!(updates[1].order_id, order_id);
+        assert_eq!(updates[1].state, OrderState::Cancelled);
+
+        let fills = exchange.tick(source.get_quotes(&102).unwrap()).fills;
+        assert!(fills.is_empty());
+        assert!(!exchange.delete_order(order_id));
+    }
+
+    #[test]
+    fn test_that_queue_ahead_shrinks_when_displayed_size_falls() {
+        let mut source = Lobster::new();
+        add_quote(&mut source, 100, 300.0, Vec::new());
+        //Displayed size falls to 20 without any trades, so cancellations came from ahead
+        add_quote(&mut source, 101, 20.0, Vec::new());
+        add_quote(&mut source, 102, 20.0, vec![trade(99.0, 70.0, Side::Sell)]);
+        let mut exchange = IslayV1::new();
+
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 99.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        exchange.tick(source.get_quotes(&101).unwrap());
+        let fills = exchange.tick(source.get_quotes(&102).unwrap()).fills;
+
+        assert_eq!(fills[0].shares, 50.0);
+    }
+
+    #[test]
+    fn test_that_own_orders_queue_behind_each_other() {
+        let (source, mut exchange) = setup();
+
+        let first = exchange.insert_order(Order::limit_buy("ABC", 100.0, 99.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 99.0));
+        exchange.tick(source.get_quotes(&101).unwrap());
+
+        //Second order joins behind the displayed size and the first order
+        let fills = exchange.tick(source.get_quotes(&102).unwrap()).fills;
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].order_id, first);
+    }
+
+    #[test]
+    fn test_that_own_orders_within_price_tolerance_queue_behind_each_other() {
+        let mut source = Lobster::new();
+        add_quote(&mut source, 100, 300.0, Vec::new());
+        add_quote(&mut source, 101, 20.0, Vec::new());
+        add_quote(&mut source, 102, 20.0, vec![trade(99.0, 70.0, Side::Sell)]);
+        let mut exchange = IslayV1::new();
+
+        //Prices are equal within tolerance but have different representations
+        let first = exchange.insert_order(Order::limit_buy("ABC", 100.0, 99.0));
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 99.0 + 1e-12));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        exchange.tick(source.get_quotes(&101).unwrap());
+
+        //Second order stays behind the displayed size and the first order
+        let fills = exchange.tick(source.get_quotes(&102).unwrap()).fills;
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].order_id, first);
+        assert_eq!(fills[0].shares, 50.0);
+    }
+
+    #[test]
+    fn test_that_deleted_order_never_executes() {
+        let (source, mut exchange) = setup();
+
+        let order_id = exchange.insert_order(Order::market_buy("ABC", 100.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        assert!(exchange.delete_order(order_id));
+        let TickOutput {
+            fills,
+            events: updates,
+            ..
+        } = exchange.tick(source.get_quotes(&101).unwrap());
+
+        assert!(fills.is_empty());
+        assert_eq!(updates[0].state, OrderState::Cancelled);
+        assert!(!exchange.delete_order(order_id));
+    }
+}
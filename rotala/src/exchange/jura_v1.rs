@@ -1,17 +1,28 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-use crate::input::penelope::{PenelopeQuote, PenelopeQuoteByDate};
+use super::{Exchange, Tick, TickOutput};
+use crate::input::penelope::PenelopeQuote;
+use crate::input::{convert_quotes, Funding, FundingRateByDate, Input};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct JuraQuote {
-    bid: f64,
-    ask: f64,
-    date: i64,
-    symbol: String,
+    pub bid: f64,
+    pub ask: f64,
+    pub date: i64,
+    pub symbol: String,
+    /// Size available at the bid, reported in the L2 book.
+    #[serde(default)]
+    pub bid_size: Option<f64>,
+    /// Size available at the ask, reported in the L2 book.
+    #[serde(default)]
+    pub ask_size: Option<f64>,
 }
 
+/// Penelope has a single volume for each quote, which is taken as the size available on both
+/// sides.
 impl From<PenelopeQuote> for JuraQuote {
     fn from(value: PenelopeQuote) -> Self {
         Self {
@@ -19,10 +30,14 @@ impl From<PenelopeQuote> for JuraQuote {
             ask: value.ask,
             date: value.date,
             symbol: value.symbol,
+            bid_size: value.volume,
+            ask_size: value.volume,
         }
     }
 }
 
+pub type JuraQuoteByDate = HashMap<String, JuraQuote>;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Side {
     Ask,
@@ -38,36 +53,584 @@ impl From<Side> for String {
     }
 }
 
-/// HL is a future exchanges so assumes some of the functions of a broker, this means that
-/// functions that report on the client's overall position won't be implemented at this stage.
-/// * closed_pnl, unimplemented because the exchange does not keep track of client pnl
-/// * dir, unimplemented as this appears to track the overall position in a coin, will always
-/// be set to false
-/// * crossed, this is unclear and may relate to margin or the execution of previous trades, this
-/// will always be set to false
-/// * hash, will always be an empty string, as HL is on-chain a transaction hash is produced but
-/// won't be in a test env, always set to false
-/// * start_position, unimplemented as this relates to overall position which is untracked, will
-/// always be set to false
+/// HL is a future exchanges so assumes some of the functions of a broker. The exchange tracks the
+/// client's [Position] in each asset so fills report the same fields as HL userFills:
+/// * closed_pnl, pnl realised by the part of the fill that reduces the position, calculated
+///   against the average entry price
+/// * dir, the effect on the position: "Open Long", "Open Short", "Close Long", "Close Short",
+///   "Long > Short" or "Short > Long"
+/// * crossed, true if the order took liquidity, see [OrderBook] for when an order is a taker
+/// * start_position, signed size of the position before the fill
+/// * hash, as HL is on-chain a transaction hash is produced but won't be in a test env, always
+///   set to false
+/// * cloid, client order id of the order if one was set
+/// * liquidation, set if the fill closed a position that breached maintenance margin
+///
+/// Fields are serialized in camelCase, as in HL, everywhere fills are returned including the
+/// `/tick` endpoint which used snake_case before positions were tracked. Snake_case names are
+/// still accepted when deserializing.
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Fill {
+    #[serde(alias = "closed_pnl")]
     pub closed_pnl: String,
     pub coin: String,
     pub crossed: bool,
-    pub dir: bool,
+    pub dir: String,
     pub hash: bool,
     pub oid: u64,
     pub px: String,
     pub side: String,
-    pub start_position: bool,
+    #[serde(alias = "start_position")]
+    pub start_position: String,
     pub sz: String,
     pub time: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub liquidation: Option<Liquidation>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Liquidation {
+    pub mark_px: String,
+    pub method: String,
+}
+
+/// Funding paid or received on a position, follows the delta of a Hyperliquid userFunding entry.
+/// Usdc is negative when the client pays funding.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FundingPayment {
+    pub time: i64,
+    pub coin: String,
+    pub usdc: String,
+    pub szi: String,
+    pub funding_rate: String,
+}
+
+/// Position in a single asset. Size is signed, negative for shorts, and the entry price is the
+/// average price paid for the current position. Margin is the collateral allocated to an
+/// isolated position and is always zero for cross positions.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Position {
+    pub szi: f64,
+    pub entry_px: f64,
+    #[serde(default)]
+    pub margin: f64,
+}
+
+// Sizes are summed as floats so a position that has been closed may not be exactly zero
+const FLAT_TOLERANCE: f64 = 1e-9;
+
+impl Position {
+    fn is_flat(szi: f64) -> bool {
+        szi.abs() < FLAT_TOLERANCE
+    }
+
+    /// Updates the position with a trade, returning the direction of the trade and the closed
+    /// pnl.
+    fn apply(&mut self, is_buy: bool, sz: f64, px: f64) -> (String, f64) {
+        let signed_sz = if is_buy { sz } else { -sz };
+        let is_long = self.szi > 0.0;
+
+        // Opening or adding to a position
+        if Self::is_flat(self.szi) || is_long == is_buy {
+            let new_szi = self.szi + signed_sz;
+            self.entry_px = (self.szi.abs() * self.entry_px + sz * px) / new_szi.abs();
+            self.szi = new_szi;
+            let dir = if is_buy { "Open Long" } else { "Open Short" };
+            return (dir.to_string(), 0.0);
+        }
+
+        let closed_sz = sz.min(self.szi.abs());
+        let closed_pnl = if is_long {
+            closed_sz * (px - self.entry_px)
+        } else {
+            closed_sz * (self.entry_px - px)
+        };
+
+        let mut new_szi = self.szi + signed_sz;
+        let dir = if !Self::is_flat(new_szi) && sz > self.szi.abs() {
+            // Position flips, the new position is entered at the trade price
+            self.entry_px = px;
+            if is_long {
+                "Long > Short"
+            } else {
+                "Short > Long"
+            }
+        } else {
+            if Self::is_flat(new_szi) {
+                new_szi = 0.0;
+                self.entry_px = 0.0;
+            }
+            if is_long {
+                "Close Long"
+            } else {
+                "Close Short"
+            }
+        };
+        self.szi = new_szi;
+        (dir.to_string(), closed_pnl)
+    }
+}
+
+/// Balance that a new exchange starts with, this can be changed with [JuraV1::with_balance].
+pub const DEFAULT_BALANCE: f64 = 100_000.0;
+
+/// Oid of the first liquidation fill. Liquidations count up from here so they never share an oid
+/// with a client order.
+pub const LIQUIDATION_OID_START: OrderId = 1 << 63;
+/// Leverage used for an asset until [JuraV1::update_leverage] is called, or the maximum leverage
+/// of the asset if that is lower.
+pub const DEFAULT_LEVERAGE: u32 = 20;
+/// Trading rules used by [AssetMeta::new].
+pub const DEFAULT_SZ_DECIMALS: u32 = 5;
+pub const DEFAULT_TICK_SIZE: f64 = 0.01;
+pub const DEFAULT_MAX_LEVERAGE: u32 = 50;
+pub const DEFAULT_MIN_NOTIONAL: f64 = 10.0;
+
+/// Trading rules for an asset, follows an entry in the universe of the Hyperliquid meta response.
+/// Hyperliquid doesn't return tick size or minimum notional but they are needed to validate
+/// orders. Maintenance margin is half of the initial margin at max_leverage.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetMeta {
+    /// Coin name, quotes for the asset are looked up by this symbol
+    pub name: String,
+    pub sz_decimals: u32,
+    pub tick_size: f64,
+    pub max_leverage: u32,
+    pub min_notional: f64,
+}
+
+impl AssetMeta {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            sz_decimals: DEFAULT_SZ_DECIMALS,
+            tick_size: DEFAULT_TICK_SIZE,
+            max_leverage: DEFAULT_MAX_LEVERAGE,
+            min_notional: DEFAULT_MIN_NOTIONAL,
+        }
+    }
+
+    // Division of floats is inexact so values within a small tolerance of a step are accepted
+    fn is_multiple_of(value: f64, step: f64) -> bool {
+        let steps = value / step;
+        (steps - steps.round()).abs() < 1e-6
+    }
+
+    fn parse_px(px: &str) -> Option<f64> {
+        px.parse::<f64>()
+            .ok()
+            .filter(|px| px.is_finite() && *px > 0.0)
+    }
+
+    /// Parses the size and limit price of an order, returns the error that Hyperliquid returns if
+    /// either can't be parsed or has more precision than the asset allows.
+    fn parse_order(&self, order: &Order) -> Result<(f64, f64), String> {
+        let asset = order.asset;
+        let sz = Self::parse_px(&order.sz)
+            .filter(|sz| Self::is_multiple_of(*sz, 10_f64.powi(-(self.sz_decimals as i32))))
+            .ok_or_else(|| format!("Order has invalid size. asset={asset}"))?;
+        let px = Self::parse_px(&order.limit_px)
+            .ok_or_else(|| format!("Order has invalid price. asset={asset}"))?;
+        if !Self::is_multiple_of(px, self.tick_size) {
+            return Err(format!(
+                "Price must be divisible by tick size. asset={asset}"
+            ));
+        }
+
+        if let OrderType::Trigger(trigger) = &order.order_type {
+            if !(trigger.trigger_px.is_finite() && trigger.trigger_px > 0.0) {
+                return Err(format!("Order has invalid trigger price. asset={asset}"));
+            }
+            if !Self::is_multiple_of(trigger.trigger_px, self.tick_size) {
+                return Err(format!(
+                    "Trigger price must be divisible by tick size. asset={asset}"
+                ));
+            }
+        }
+
+        // Reduce-only orders can close positions smaller than the minimum
+        if !order.reduce_only && sz * px < self.min_notional {
+            return Err(format!(
+                "Order must have minimum value of ${}. asset={asset}",
+                self.min_notional
+            ));
+        }
+        Ok((sz, px))
+    }
+}
+
+/// Assets that can be traded on the exchange, the id of an asset is its index in the universe.
+/// Orders for assets outside the universe are rejected. An empty universe trades every asset with
+/// the default rules, named by id, so asset 0 is quoted as symbol "0".
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Universe {
+    assets: Vec<AssetMeta>,
+}
+
+impl Universe {
+    pub fn new(assets: Vec<AssetMeta>) -> Self {
+        Self { assets }
+    }
+
+    /// Creates a universe with the default trading rules for every symbol, assets are numbered in
+    /// the order given.
+    pub fn from_symbols(symbols: &[String]) -> Self {
+        Self::new(symbols.iter().map(AssetMeta::new).collect())
+    }
+
+    pub fn get(&self, asset: u64) -> Option<&AssetMeta> {
+        self.assets.get(asset as usize)
+    }
+
+    pub fn get_assets(&self) -> &[AssetMeta] {
+        &self.assets
+    }
+
+    /// Returns the id of the asset with the given coin name.
+    pub fn get_asset(&self, coin: &str) -> Option<u64> {
+        self.assets
+            .iter()
+            .position(|meta| meta.name == coin)
+            .map(|asset| asset as u64)
+    }
+
+    // Trading rules for an asset, every asset has default rules if the universe is empty
+    fn meta(&self, asset: u64) -> Option<AssetMeta> {
+        if self.assets.is_empty() {
+            return Some(AssetMeta::new(asset.to_string()));
+        }
+        self.get(asset).cloned()
+    }
+
+    // Assets outside the universe are named by id
+    fn coin(&self, asset: u64) -> String {
+        self.get(asset)
+            .map_or_else(|| asset.to_string(), |meta| meta.name.clone())
+    }
+
+    fn max_leverage(&self, asset: u64) -> u32 {
+        self.get(asset)
+            .map_or(DEFAULT_MAX_LEVERAGE, |meta| meta.max_leverage)
+    }
+}
+
+/// Leverage setting for an asset. Cross positions share the balance of the account as margin,
+/// isolated positions have margin allocated from the balance when they are opened.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Leverage {
+    Cross { value: u32 },
+    Isolated { value: u32 },
+}
+
+impl Leverage {
+    pub fn value(&self) -> u32 {
+        match self {
+            Leverage::Cross { value } | Leverage::Isolated { value } => *value,
+        }
+    }
+
+    pub fn is_cross(&self) -> bool {
+        matches!(self, Leverage::Cross { .. })
+    }
+}
+
+impl Default for Leverage {
+    fn default() -> Self {
+        Leverage::Cross {
+            value: DEFAULT_LEVERAGE,
+        }
+    }
+}
+
+/// Collateral and positions held by the client. Positions are valued at the mark price, which is
+/// the mid of the last quote seen for the asset.
+#[derive(Clone, Debug)]
+struct Account {
+    // Collateral plus realised pnl, excluding margin allocated to isolated positions
+    balance: f64,
+    positions: HashMap<u64, Position>,
+    leverage: HashMap<u64, Leverage>,
+    marks: HashMap<u64, f64>,
+    universe: Universe,
+}
+
+impl Account {
+    fn new(balance: f64) -> Self {
+        Self {
+            balance,
+            positions: HashMap::new(),
+            leverage: HashMap::new(),
+            marks: HashMap::new(),
+            universe: Universe::default(),
+        }
+    }
+
+    fn get_leverage(&self, asset: u64) -> Leverage {
+        self.leverage
+            .get(&asset)
+            .copied()
+            .unwrap_or(Leverage::Cross {
+                value: DEFAULT_LEVERAGE.min(self.universe.max_leverage(asset)),
+            })
+    }
+
+    fn update_marks(&mut self, quotes: &JuraQuoteByDate) {
+        if self.universe.assets.is_empty() {
+            for (symbol, quote) in quotes {
+                if let Ok(asset) = symbol.parse::<u64>() {
+                    self.marks.insert(asset, (quote.bid + quote.ask) / 2.0);
+                }
+            }
+            return;
+        }
+        for (asset, meta) in self.universe.assets.iter().enumerate() {
+            if let Some(quote) = quotes.get(&meta.name) {
+                self.marks
+                    .insert(asset as u64, (quote.bid + quote.ask) / 2.0);
+            }
+        }
+    }
+
+    // Positions without a quote are marked at entry
+    fn mark(&self, asset: u64, position: &Position) -> f64 {
+        *self.marks.get(&asset).unwrap_or(&position.entry_px)
+    }
+
+    fn unrealized_pnl(&self, asset: u64, position: &Position) -> f64 {
+        position.szi * (self.mark(asset, position) - position.entry_px)
+    }
+
+    fn notional(&self, asset: u64, position: &Position) -> f64 {
+        position.szi.abs() * self.mark(asset, position)
+    }
+
+    fn maintenance_margin(&self, asset: u64, position: &Position) -> f64 {
+        self.notional(asset, position) / (2.0 * self.universe.max_leverage(asset) as f64)
+    }
+
+    fn cross_positions(&self) -> impl Iterator<Item = (&u64, &Position)> {
+        self.positions
+            .iter()
+            .filter(|(asset, _)| self.get_leverage(**asset).is_cross())
+    }
+
+    /// Balance plus unrealised pnl of cross positions.
+    fn cross_equity(&self) -> f64 {
+        self.balance
+            + self
+                .cross_positions()
+                .map(|(asset, position)| self.unrealized_pnl(*asset, position))
+                .sum::<f64>()
+    }
+
+    fn cross_margin_used(&self) -> f64 {
+        self.cross_positions()
+            .map(|(asset, position)| {
+                self.notional(*asset, position) / self.get_leverage(*asset).value() as f64
+            })
+            .sum()
+    }
+
+    fn cross_maintenance_margin(&self) -> f64 {
+        self.cross_positions()
+            .map(|(asset, position)| self.maintenance_margin(*asset, position))
+            .sum()
+    }
+
+    /// Margin that can be used to open new positions, cross or isolated.
+    fn available_margin(&self) -> f64 {
+        self.cross_equity() - self.cross_margin_used()
+    }
+
+    fn account_value(&self) -> f64 {
+        self.cross_equity()
+            + self
+                .positions
+                .iter()
+                .filter(|(asset, _)| !self.get_leverage(**asset).is_cross())
+                .map(|(asset, position)| position.margin + self.unrealized_pnl(*asset, position))
+                .sum::<f64>()
+    }
+
+    // Number of shares that an order can trade without increasing or flipping the position
+    fn reducible_shares(&self, asset: u64, is_buy: bool) -> f64 {
+        let szi = self
+            .positions
+            .get(&asset)
+            .map(|position| position.szi)
+            .unwrap_or_default();
+        if is_buy {
+            (-szi).max(0.0)
+        } else {
+            szi.max(0.0)
+        }
+    }
+
+    // Margin reserved by orders in the book is not available to this order
+    fn has_margin_for(&self, asset: u64, is_buy: bool, sz: f64, px: f64, reserved: f64) -> bool {
+        let increase = (sz - self.reducible_shares(asset, is_buy)).max(0.0);
+        if increase <= 0.0 {
+            return true;
+        }
+        let required = increase * px / self.get_leverage(asset).value() as f64;
+        required <= self.available_margin() - reserved
+    }
+
+    /// Updates the position and balance with a trade, returns the start position, the direction
+    /// of the trade and the closed pnl.
+    fn apply_fill(&mut self, asset: u64, is_buy: bool, sz: f64, px: f64) -> (f64, String, f64) {
+        let leverage = self.get_leverage(asset);
+        let closed_sz = sz.min(self.reducible_shares(asset, is_buy));
+        let opened_sz = sz - closed_sz;
+
+        let position = self.positions.entry(asset).or_default();
+        let start_position = position.szi;
+        let (dir, closed_pnl) = position.apply(is_buy, sz, px);
+
+        if leverage.is_cross() {
+            self.balance += closed_pnl;
+        } else {
+            let released = if closed_sz > 0.0 {
+                position.margin * closed_sz / start_position.abs()
+            } else {
+                0.0
+            };
+            let allocated = opened_sz * px / leverage.value() as f64;
+            position.margin += allocated - released;
+            self.balance += released + closed_pnl - allocated;
+        }
+        (start_position, dir, closed_pnl)
+    }
+
+    // Funding on isolated positions is paid from, or into, the margin of the position
+    fn apply_funding(&mut self, asset: u64, usdc: f64) {
+        if self.get_leverage(asset).is_cross() {
+            self.balance += usdc;
+        } else if let Some(position) = self.positions.get_mut(&asset) {
+            position.margin += usdc;
+        }
+    }
+
+    fn update_leverage(&mut self, asset: u64, is_cross: bool, value: u32) -> Result<(), String> {
+        let Some(meta) = self.universe.meta(asset) else {
+            return Err(format!("Invalid asset. asset={asset}"));
+        };
+        if value == 0 || value > meta.max_leverage {
+            return Err(format!("Invalid leverage value. asset={asset}"));
+        }
+
+        let current = self.get_leverage(asset);
+        let has_position = self
+            .positions
+            .get(&asset)
+            .is_some_and(|position| position.szi != 0.0);
+        if has_position {
+            if current.is_cross() != is_cross {
+                return Err(format!(
+                    "Cannot switch leverage type with open position. asset={asset}"
+                ));
+            }
+            if !is_cross && current.value() != value {
+                return Err(format!(
+                    "Cannot change leverage of open isolated position. asset={asset}"
+                ));
+            }
+        }
+
+        let leverage = if is_cross {
+            Leverage::Cross { value }
+        } else {
+            Leverage::Isolated { value }
+        };
+        self.leverage.insert(asset, leverage);
+        if self.available_margin() < 0.0 {
+            self.leverage.insert(asset, current);
+            return Err(format!(
+                "Insufficient margin to change leverage. asset={asset}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns assets with positions that have breached maintenance margin. Isolated positions
+    /// are checked individually, if the cross account breaches then every cross position is
+    /// returned.
+    fn breached_positions(&self) -> Vec<u64> {
+        let mut breached: Vec<u64> = self
+            .positions
+            .iter()
+            .filter(|(asset, position)| {
+                position.szi != 0.0
+                    && !self.get_leverage(**asset).is_cross()
+                    && position.margin + self.unrealized_pnl(**asset, position)
+                        < self.maintenance_margin(**asset, position)
+            })
+            .map(|(asset, _)| *asset)
+            .collect();
+
+        if self.cross_equity() < self.cross_maintenance_margin() {
+            breached.extend(
+                self.cross_positions()
+                    .filter(|(_, position)| position.szi != 0.0)
+                    .map(|(asset, _)| *asset),
+            );
+        }
+        breached.sort();
+        breached
+    }
 }
 
 pub type OrderId = u64;
 
+/// Status of an order sent to the exchange, returned for every order inserted into the book on
+/// tick. Follows the statuses returned by the Hyperliquid order endpoint, orders that are
+/// rejected are never given an [OrderId].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderStatus {
+    Resting {
+        oid: OrderId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cloid: Option<String>,
+    },
+    Error(String),
+}
+
+/// Reason that the exchange cancelled a resting order without a request from the client, follows
+/// the order statuses returned by the Hyperliquid orderStatus endpoint.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum CancelReason {
+    /// Reduce-only order with no position left to reduce.
+    #[serde(rename = "reduceOnlyCanceled")]
+    ReduceOnly,
+    /// Order that would increase a position without enough margin available when it executes.
+    #[serde(rename = "marginCanceled")]
+    Margin,
+    /// Ioc order, including market orders, that could not match when it executed.
+    #[serde(rename = "iocCancelRejected")]
+    Ioc,
+}
+
+// Displays the serde name, which is the status Hyperliquid reports
+impl fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(status)) => f.write_str(&status),
+            _ => Err(fmt::Error),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
-enum TimeInForce {
+pub enum TimeInForce {
     Alo,
     Ioc,
     Gtc,
@@ -113,7 +676,7 @@ pub struct Order {
     limit_px: String,
     sz: String,
     reduce_only: bool,
-    //This is client order id, need to check whether test impl should reasonably use this.
+    // Client order id, must be unique amongst the client's resting orders
     cloid: Option<String>,
     order_type: OrderType,
 }
@@ -187,15 +750,96 @@ impl Order {
         }
     }
 
+    pub fn post_only_buy(
+        asset: impl Into<u64>,
+        sz: impl Into<String>,
+        price: impl Into<String>,
+    ) -> Self {
+        Self {
+            asset: asset.into(),
+            is_buy: true,
+            limit_px: price.into(),
+            sz: sz.into(),
+            reduce_only: false,
+            cloid: None,
+            order_type: OrderType::Limit(LimitOrder {
+                tif: TimeInForce::Alo,
+            }),
+        }
+    }
+
+    pub fn post_only_sell(
+        asset: impl Into<u64>,
+        sz: impl Into<String>,
+        price: impl Into<String>,
+    ) -> Self {
+        Self {
+            asset: asset.into(),
+            is_buy: false,
+            limit_px: price.into(),
+            sz: sz.into(),
+            reduce_only: false,
+            cloid: None,
+            order_type: OrderType::Limit(LimitOrder {
+                tif: TimeInForce::Alo,
+            }),
+        }
+    }
+
+    pub fn get_asset(&self) -> u64 {
+        self.asset
+    }
+
+    pub fn is_buy(&self) -> bool {
+        self.is_buy
+    }
+
+    pub fn get_sz(&self) -> &str {
+        &self.sz
+    }
+
+    pub fn get_limit_px(&self) -> &str {
+        &self.limit_px
+    }
+
+    /// Reduce-only orders can only reduce the position in the asset. They are rejected if there
+    /// is no position to reduce when they arrive and are clipped to the size of the position when
+    /// they execute.
+    pub fn with_reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = reduce_only;
+        self
+    }
+
+    /// Client order id that can be used to identify the order instead of the oid assigned by the
+    /// exchange, see [JuraV1::delete_order_by_cloid].
+    pub fn with_cloid(mut self, cloid: impl Into<String>) -> Self {
+        self.cloid = Some(cloid.into());
+        self
+    }
+
+    pub fn get_cloid(&self) -> Option<&String> {
+        self.cloid.as_ref()
+    }
+
+    fn is_post_only(&self) -> bool {
+        matches!(
+            self.order_type,
+            OrderType::Limit(LimitOrder {
+                tif: TimeInForce::Alo
+            })
+        )
+    }
+
     pub fn stop_buy(
         asset: impl Into<u64>,
         sz: impl Into<String>,
         price: impl Into<String>,
     ) -> Self {
         // It is possible for a stop to use a different trigger price, we guard against this with
-        // the default order type because it is unexpected behaviour in most applications.
+        // the default order type because it is unexpected behaviour in most applications. Prices
+        // that can't be parsed are rejected when the order arrives at the exchange.
         let copy = price.into();
-        let to_f64 = copy.parse::<f64>().unwrap();
+        let to_f64 = copy.parse::<f64>().unwrap_or(f64::NAN);
         Self {
             asset: asset.into(),
             is_buy: true,
@@ -217,9 +861,10 @@ impl Order {
         price: impl Into<String>,
     ) -> Self {
         // It is possible for a stop to use a different trigger price, we guard against this with
-        // the default order type because it is unexpected behaviour in most applications.
+        // the default order type because it is unexpected behaviour in most applications. Prices
+        // that can't be parsed are rejected when the order arrives at the exchange.
         let copy = price.into();
-        let to_f64 = copy.parse::<f64>().unwrap();
+        let to_f64 = copy.parse::<f64>().unwrap_or(f64::NAN);
         Self {
             asset: asset.into(),
             is_buy: false,
@@ -241,9 +886,10 @@ impl Order {
         price: impl Into<String>,
     ) -> Self {
         // It is possible for a stop to use a different trigger price, we guard against this with
-        // the default order type because it is unexpected behaviour in most applications.
+        // the default order type because it is unexpected behaviour in most applications. Prices
+        // that can't be parsed are rejected when the order arrives at the exchange.
         let copy = price.into();
-        let to_f64 = copy.parse::<f64>().unwrap();
+        let to_f64 = copy.parse::<f64>().unwrap_or(f64::NAN);
         Self {
             asset: asset.into(),
             is_buy: true,
@@ -265,9 +911,10 @@ impl Order {
         price: impl Into<String>,
     ) -> Self {
         // It is possible for a stop to use a different trigger price, we guard against this with
-        // the default order type because it is unexpected behaviour in most applications.
+        // the default order type because it is unexpected behaviour in most applications. Prices
+        // that can't be parsed are rejected when the order arrives at the exchange.
         let copy = price.into();
-        let to_f64 = copy.parse::<f64>().unwrap();
+        let to_f64 = copy.parse::<f64>().unwrap_or(f64::NAN);
         Self {
             asset: asset.into(),
             is_buy: false,
@@ -284,27 +931,224 @@ impl Order {
     }
 }
 
+/// Order in the wire format used by the Hyperliquid exchange endpoint, fields are abbreviated:
+/// asset, is_buy, price, size, reduce_only, order type and cloid.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OrderWire {
+    pub a: u64,
+    pub b: bool,
+    pub p: String,
+    pub s: String,
+    pub r: bool,
+    pub t: OrderTypeWire,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub c: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderTypeWire {
+    Limit {
+        tif: TimeInForce,
+    },
+    Trigger {
+        #[serde(rename = "isMarket")]
+        is_market: bool,
+        #[serde(rename = "triggerPx")]
+        trigger_px: String,
+        tpsl: String,
+    },
+}
+
+impl TryFrom<OrderWire> for Order {
+    type Error = String;
+
+    fn try_from(value: OrderWire) -> Result<Self, Self::Error> {
+        let order_type = match value.t {
+            OrderTypeWire::Limit { tif } => OrderType::Limit(LimitOrder { tif }),
+            OrderTypeWire::Trigger {
+                is_market,
+                trigger_px,
+                tpsl,
+            } => {
+                let tpsl = match tpsl.as_str() {
+                    "tp" => TriggerType::Tp,
+                    "sl" => TriggerType::Sl,
+                    _ => return Err(format!("Invalid tpsl {tpsl}")),
+                };
+                let trigger_px = trigger_px
+                    .parse::<f64>()
+                    .map_err(|_| format!("Invalid trigger price {trigger_px}"))?;
+                OrderType::Trigger(TriggerOrder {
+                    trigger_px,
+                    is_market,
+                    tpsl,
+                })
+            }
+        };
+        Ok(Self {
+            asset: value.a,
+            is_buy: value.b,
+            limit_px: value.p,
+            sz: value.s,
+            reduce_only: value.r,
+            cloid: value.c,
+            order_type,
+        })
+    }
+}
+
+/// Resting order, follows the Hyperliquid openOrders response.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenOrder {
+    pub coin: String,
+    pub limit_px: String,
+    pub oid: OrderId,
+    pub side: String,
+    pub sz: String,
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloid: Option<String>,
+}
+
+/// Order cancelled by the exchange on tick, follows the Hyperliquid orderStatus response.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CanceledOrder {
+    pub order: OpenOrder,
+    pub status: CancelReason,
+    pub status_timestamp: i64,
+}
+
+/// Order returned by the Hyperliquid orderStatus endpoint. Status is `open` for resting orders,
+/// or the [CancelReason] for orders that the exchange cancelled.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderInfo {
+    pub order: OpenOrder,
+    pub status: String,
+    pub status_timestamp: i64,
+}
+
+impl From<CanceledOrder> for OrderInfo {
+    fn from(value: CanceledOrder) -> Self {
+        Self {
+            order: value.order,
+            status: value.status.to_string(),
+            status_timestamp: value.status_timestamp,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MarginSummary {
+    pub account_value: String,
+    pub total_ntl_pos: String,
+    pub total_raw_usd: String,
+    pub total_margin_used: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionData {
+    pub coin: String,
+    pub szi: String,
+    pub entry_px: String,
+    pub position_value: String,
+    pub unrealized_pnl: String,
+    pub return_on_equity: String,
+    pub leverage: Leverage,
+    pub liquidation_px: Option<String>,
+    pub margin_used: String,
+    pub max_leverage: u32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct AssetPosition {
+    pub position: PositionData,
+    #[serde(rename = "type")]
+    pub typ: String,
+}
+
+/// State of the client's account, follows the Hyperliquid clearinghouseState response.
+/// Liquidation price is not calculated and is always null.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClearinghouseState {
+    pub asset_positions: Vec<AssetPosition>,
+    pub cross_margin_summary: MarginSummary,
+    pub margin_summary: MarginSummary,
+    pub cross_maintenance_margin_used: String,
+    pub withdrawable: String,
+    pub time: i64,
+}
+
 #[derive(Clone, Debug)]
 struct InnerOrder {
     pub order_id: OrderId,
     pub order: Order,
     pub attempted_execution: bool,
+    // Date on which the order was accepted by the exchange
+    pub timestamp: i64,
+    // Orders placed between ticks have an oid but can't execute until they enter the book on tick
+    pub pending: bool,
+    // Size and limit price are parsed when the order is validated
+    pub shares: f64,
+    pub price: f64,
 }
 
 impl InnerOrder {
-    pub fn get_shares(&self) -> f64 {
-        // We unwrap immediately because we can't continue if the client is passing incorrectly
-        // sized orders
-        str::parse::<f64>(&self.order.sz).unwrap()
+    fn to_open_order(&self, universe: &Universe) -> OpenOrder {
+        OpenOrder {
+            coin: universe.coin(self.order.asset),
+            limit_px: self.order.limit_px.clone(),
+            oid: self.order_id,
+            side: if self.order.is_buy {
+                Side::Bid.into()
+            } else {
+                Side::Ask.into()
+            },
+            sz: self.order.sz.clone(),
+            timestamp: self.timestamp,
+            cloid: self.order.cloid.clone(),
+        }
+    }
+
+    fn cancel(&self, universe: &Universe, status: CancelReason, now: i64) -> CanceledOrder {
+        CanceledOrder {
+            order: self.to_open_order(universe),
+            status,
+            status_timestamp: now,
+        }
     }
 }
 
+/// Returned on every tick along with fills and inserted orders.
+#[derive(Clone, Debug, Default)]
+pub struct JuraTickEvents {
+    /// Order ids of triggered orders.
+    pub triggered: Vec<OrderId>,
+    /// Status of each inserted order, in the same order as the inserted orders.
+    pub statuses: Vec<OrderStatus>,
+    /// Always empty when returned from [tick](JuraV1::tick), funding is settled separately with
+    /// [settle_funding](JuraV1::settle_funding).
+    pub funding: Vec<FundingPayment>,
+    /// Resting orders that the exchange cancelled on this tick.
+    pub canceled: Vec<CanceledOrder>,
+}
+
 #[derive(Clone, Debug)]
 pub struct JuraV1 {
     orderbook: OrderBook,
     trade_log: Vec<Fill>,
+    funding_log: Vec<FundingPayment>,
+    cancel_log: Vec<CanceledOrder>,
     //This is cleared on every tick
     order_buffer: Vec<Order>,
+    //Date of the last tick that settled funding, used to accrue rates on dates without quotes
+    funded_until: Option<i64>,
 }
 
 impl JuraV1 {
@@ -312,8 +1156,94 @@ impl JuraV1 {
         Self {
             orderbook: OrderBook::default(),
             trade_log: Vec::new(),
+            funding_log: Vec::new(),
+            cancel_log: Vec::new(),
             order_buffer: Vec::new(),
+            funded_until: None,
+        }
+    }
+
+    /// Sets the maximum slippage, as a fraction of price, tolerated by market orders. Defaults to
+    /// 10% which is the default in production.
+    pub fn with_slippage(mut self, slippage: f64) -> Self {
+        self.orderbook.slippage = slippage;
+        self
+    }
+
+    /// Sets the collateral that the client starts with, defaults to [DEFAULT_BALANCE].
+    pub fn with_balance(mut self, balance: f64) -> Self {
+        self.orderbook.account.balance = balance;
+        self
+    }
+
+    /// Sets the assets that can be traded. Defaults to an empty [Universe], which trades every
+    /// asset with the default rules.
+    pub fn with_universe(mut self, universe: Universe) -> Self {
+        self.orderbook.account.universe = universe;
+        self
+    }
+
+    pub fn get_universe(&self) -> &Universe {
+        &self.orderbook.account.universe
+    }
+
+    // Orders are checked against the state of the exchange at the time they arrive at the book,
+    // this is the quote on this tick because orders are inserted after execution. Returns the
+    // parsed size and limit price.
+    // An order replacing a resting order can reuse the cloid of the order it replaces
+    fn validate_order(
+        &self,
+        order: &Order,
+        quotes: &JuraQuoteByDate,
+        replacing: Option<OrderId>,
+    ) -> Result<(f64, f64), String> {
+        let meta = self
+            .orderbook
+            .account
+            .universe
+            .meta(order.asset)
+            .ok_or_else(|| format!("Invalid asset. asset={}", order.asset))?;
+        let (shares, price) = meta.parse_order(order)?;
+
+        if let Some(cloid) = &order.cloid {
+            let oid = self.orderbook.cloids.get(cloid).copied();
+            if oid.is_some() && oid != replacing {
+                return Err(format!(
+                    "Order has duplicate cloid. asset={} cloid={}",
+                    order.asset, cloid
+                ));
+            }
+        }
+
+        if let Some(quote) = quotes.get(&meta.name) {
+            if order.is_post_only() && OrderBook::crosses(order.is_buy, price, quote) {
+                return Err(format!(
+                    "Post only order would have immediately matched, bbo was {}@{}. asset={}",
+                    quote.bid, quote.ask, order.asset
+                ));
+            }
+        }
+
+        // Trigger orders are only checked once they have triggered
+        if matches!(order.order_type, OrderType::Limit(..)) {
+            if order.reduce_only && self.orderbook.reducible_shares(order) <= 0.0 {
+                return Err(format!(
+                    "Reduce only order would increase position. asset={}",
+                    order.asset
+                ));
+            }
+
+            if !self
+                .orderbook
+                .has_margin_for(order, shares, price, replacing.as_slice())
+            {
+                return Err(format!(
+                    "Insufficient margin to place order. asset={}",
+                    order.asset
+                ));
+            }
         }
+        Ok((shares, price))
     }
 
     fn sort_order_buffer(&mut self) {
@@ -334,27 +1264,343 @@ impl JuraV1 {
         self.order_buffer.push(order);
     }
 
-    pub fn delete_order(&mut self, asset: u64, order_id: u64) {
-        self.orderbook.delete_order(asset, order_id);
+    /// Returns false if there is no resting order with that oid for the asset.
+    pub fn delete_order(&mut self, asset: u64, order_id: u64) -> bool {
+        self.orderbook.delete_order(asset, order_id)
+    }
+
+    /// Validates an order against the current quotes and the state of the account and assigns an
+    /// oid immediately, as the Hyperliquid exchange endpoint does. To avoid lookahead bias the
+    /// order enters the book on the next call to [tick](JuraV1::tick), the same as
+    /// [insert_order](JuraV1::insert_order). Orders placed this way are not returned from tick.
+    pub fn place_order<Q: Clone + Into<JuraQuote>>(
+        &mut self,
+        order: Order,
+        quotes: &HashMap<String, Q>,
+    ) -> OrderStatus {
+        let quotes: JuraQuoteByDate = convert_quotes(quotes);
+        match self.validate_order(&order, &quotes, None) {
+            Ok((shares, price)) => self.place_validated_order(order, shares, price, &quotes),
+            Err(reason) => OrderStatus::Error(reason),
+        }
+    }
+
+    fn place_validated_order(
+        &mut self,
+        order: Order,
+        shares: f64,
+        price: f64,
+        quotes: &JuraQuoteByDate,
+    ) -> OrderStatus {
+        let date = quotes
+            .values()
+            .next()
+            .map(|quote| quote.date)
+            .unwrap_or(self.orderbook.now);
+        let cloid = order.cloid.clone();
+        let oid = self
+            .orderbook
+            .insert_pending_order(order, shares, price, date);
+        OrderStatus::Resting { oid, cloid }
+    }
+
+    /// Cancels a resting order and places a new order in its place, the new order is given a new
+    /// oid. Returns an error status if the original order is not resting or the new order is
+    /// rejected, in which case the original order stays in the book.
+    pub fn modify_order<Q: Clone + Into<JuraQuote>>(
+        &mut self,
+        asset: u64,
+        order_id: OrderId,
+        order: Order,
+        quotes: &HashMap<String, Q>,
+    ) -> OrderStatus {
+        if !self.orderbook.contains(asset, order_id) {
+            return OrderStatus::Error("Cannot modify canceled or filled order".to_string());
+        }
+        let quotes: JuraQuoteByDate = convert_quotes(quotes);
+        match self.validate_order(&order, &quotes, Some(order_id)) {
+            Ok((shares, price)) => {
+                self.delete_order(asset, order_id);
+                self.place_validated_order(order, shares, price, &quotes)
+            }
+            Err(reason) => OrderStatus::Error(reason),
+        }
+    }
+
+    pub fn get_oid_by_cloid(&self, cloid: &str) -> Option<OrderId> {
+        self.orderbook.cloids.get(cloid).copied()
+    }
+
+    pub fn get_open_orders(&self) -> Vec<OpenOrder> {
+        self.orderbook
+            .inner
+            .iter()
+            .map(|order| order.to_open_order(&self.orderbook.account.universe))
+            .collect()
+    }
+
+    /// Returns the status of a resting order or an order cancelled by the exchange. Orders that
+    /// have filled or were cancelled by the client are not tracked.
+    pub fn get_order_status(&self, oid: OrderId) -> Option<OrderInfo> {
+        self.find_order_status(|order| order.oid == oid)
+    }
+
+    pub fn get_order_status_by_cloid(&self, cloid: &str) -> Option<OrderInfo> {
+        self.find_order_status(|order| order.cloid.as_deref() == Some(cloid))
+    }
+
+    fn find_order_status(&self, matches: impl Fn(&OpenOrder) -> bool) -> Option<OrderInfo> {
+        if let Some(order) = self.get_open_orders().into_iter().find(&matches) {
+            return Some(OrderInfo {
+                status_timestamp: order.timestamp,
+                order,
+                status: "open".to_string(),
+            });
+        }
+        self.cancel_log
+            .iter()
+            .rev()
+            .find(|canceled| matches(&canceled.order))
+            .map(|canceled| canceled.clone().into())
+    }
+
+    pub fn get_cancel_log(&self) -> &[CanceledOrder] {
+        &self.cancel_log
+    }
+
+    pub fn get_trade_log(&self) -> &[Fill] {
+        &self.trade_log
+    }
+
+    pub fn get_funding_log(&self) -> &[FundingPayment] {
+        &self.funding_log
+    }
+
+    pub fn get_clearinghouse_state(&self, time: i64) -> ClearinghouseState {
+        let account = &self.orderbook.account;
+        let mut assets: Vec<&u64> = account
+            .positions
+            .iter()
+            .filter(|(_, position)| position.szi != 0.0)
+            .map(|(asset, _)| asset)
+            .collect();
+        assets.sort();
+
+        let mut asset_positions = Vec::new();
+        let mut total_ntl_pos = 0.0;
+        let mut cross_ntl_pos = 0.0;
+        let mut total_margin_used = 0.0;
+        for asset in assets {
+            let position = &account.positions[asset];
+            let leverage = account.get_leverage(*asset);
+            let notional = account.notional(*asset, position);
+            let unrealized_pnl = account.unrealized_pnl(*asset, position);
+            let margin_used = if leverage.is_cross() {
+                cross_ntl_pos += notional;
+                notional / leverage.value() as f64
+            } else {
+                position.margin + unrealized_pnl
+            };
+            let entry_margin = position.szi.abs() * position.entry_px / leverage.value() as f64;
+            total_ntl_pos += notional;
+            total_margin_used += margin_used;
+
+            asset_positions.push(AssetPosition {
+                position: PositionData {
+                    coin: account.universe.coin(*asset),
+                    szi: position.szi.to_string(),
+                    entry_px: position.entry_px.to_string(),
+                    position_value: notional.to_string(),
+                    unrealized_pnl: unrealized_pnl.to_string(),
+                    return_on_equity: (unrealized_pnl / entry_margin).to_string(),
+                    leverage,
+                    liquidation_px: None,
+                    margin_used: margin_used.to_string(),
+                    max_leverage: account.universe.max_leverage(*asset),
+                },
+                typ: "oneWay".to_string(),
+            });
+        }
+
+        ClearinghouseState {
+            asset_positions,
+            cross_margin_summary: MarginSummary {
+                account_value: account.cross_equity().to_string(),
+                total_ntl_pos: cross_ntl_pos.to_string(),
+                total_raw_usd: account.balance.to_string(),
+                total_margin_used: account.cross_margin_used().to_string(),
+            },
+            margin_summary: MarginSummary {
+                account_value: account.account_value().to_string(),
+                total_ntl_pos: total_ntl_pos.to_string(),
+                total_raw_usd: account.balance.to_string(),
+                total_margin_used: total_margin_used.to_string(),
+            },
+            cross_maintenance_margin_used: account.cross_maintenance_margin().to_string(),
+            withdrawable: account.available_margin().max(0.0).to_string(),
+            time,
+        }
+    }
+
+    /// Deletes a resting order by client order id, returns false if there is no resting order with
+    /// that cloid for the asset.
+    pub fn delete_order_by_cloid(&mut self, asset: u64, cloid: &str) -> bool {
+        match self.orderbook.cloids.get(cloid) {
+            Some(order_id) => self.orderbook.delete_order(asset, *order_id),
+            None => false,
+        }
+    }
+
+    pub fn get_position(&self, asset: u64) -> Option<&Position> {
+        self.orderbook.account.positions.get(&asset)
+    }
+
+    pub fn get_positions(&self) -> &HashMap<u64, Position> {
+        &self.orderbook.account.positions
+    }
+
+    /// Collateral plus realised pnl, excluding margin allocated to isolated positions.
+    pub fn get_balance(&self) -> f64 {
+        self.orderbook.account.balance
+    }
+
+    /// Balance plus margin allocated to isolated positions plus unrealised pnl, positions are
+    /// valued at the last mark price.
+    pub fn get_account_value(&self) -> f64 {
+        self.orderbook.account.account_value()
+    }
+
+    /// Margin required by cross positions at the current leverage.
+    pub fn get_cross_margin_used(&self) -> f64 {
+        self.orderbook.account.cross_margin_used()
+    }
+
+    pub fn get_leverage(&self, asset: u64) -> Leverage {
+        self.orderbook.account.get_leverage(asset)
+    }
+
+    /// Changes leverage for an asset, following the Hyperliquid updateLeverage action. Leverage
+    /// type cannot be switched with an open position, and the leverage of an open isolated
+    /// position cannot be changed.
+    pub fn update_leverage(
+        &mut self,
+        asset: u64,
+        is_cross: bool,
+        leverage: u32,
+    ) -> Result<(), String> {
+        self.orderbook
+            .account
+            .update_leverage(asset, is_cross, leverage)
     }
 
-    pub fn tick(&mut self, quotes: &PenelopeQuoteByDate) -> (Vec<Fill>, Vec<Order>, Vec<u64>) {
+    /// Settles funding against open positions. Each position pays `-szi * price * rate`, where
+    /// price is the mid of the quote on the funding date, so longs pay shorts when the rate is
+    /// positive. Should be called before [tick](JuraV1::tick) with the same quotes so that
+    /// liquidations account for funding.
+    pub fn settle_funding<Q: Clone + Into<JuraQuote>>(
+        &mut self,
+        date: i64,
+        rates: &FundingRateByDate,
+        quotes: &HashMap<String, Q>,
+    ) -> Vec<FundingPayment> {
+        self.execute_funding(date, rates, &convert_quotes(quotes))
+    }
+
+    fn execute_funding(
+        &mut self,
+        date: i64,
+        rates: &FundingRateByDate,
+        quotes: &JuraQuoteByDate,
+    ) -> Vec<FundingPayment> {
+        let account = &mut self.orderbook.account;
+        let mut assets: Vec<u64> = account
+            .positions
+            .iter()
+            .filter(|(_, position)| position.szi != 0.0)
+            .map(|(asset, _)| *asset)
+            .collect();
+        assets.sort();
+
+        let mut payments = Vec::new();
+        for asset in assets {
+            let coin = account.universe.coin(asset);
+            let Some(rate) = rates.get(&coin) else {
+                continue;
+            };
+            let position = &account.positions[&asset];
+            let price = match quotes.get(&coin) {
+                Some(quote) => (quote.bid + quote.ask) / 2.0,
+                None => account.mark(asset, position),
+            };
+            let szi = position.szi;
+            let usdc = -szi * price * rate;
+            account.apply_funding(asset, usdc);
+
+            payments.push(FundingPayment {
+                time: date,
+                coin,
+                usdc: usdc.to_string(),
+                szi: szi.to_string(),
+                funding_rate: rate.to_string(),
+            });
+        }
+        self.funding_log.extend(payments.iter().cloned());
+        payments
+    }
+
+    /// Returns fills, the orders inserted on this tick and [JuraTickEvents].
+    pub fn tick<Q: Clone + Into<JuraQuote>>(
+        &mut self,
+        quotes: &HashMap<String, Q>,
+    ) -> TickOutput<Self> {
+        self.execute_tick(&convert_quotes(quotes))
+    }
+
+    fn execute_tick(&mut self, quotes: &JuraQuoteByDate) -> TickOutput<Self> {
+        if let Some(quote) = quotes.values().next() {
+            self.orderbook.now = quote.date;
+        }
+        // Positions are marked to the new quotes and liquidated before any orders execute
+        self.orderbook.account.update_marks(quotes);
+        let mut fills = match quotes.values().next() {
+            Some(quote) => self.orderbook.liquidate(quote.date),
+            None => Vec::new(),
+        };
+
         //To eliminate lookahead bias, we only insert new orders after we have executed any orders
         //that were on the stack first
-        let (fills, triggered_order_ids) = self.orderbook.execute_orders(quotes);
+        let (mut executed, triggered_order_ids, canceled_orders) =
+            self.orderbook.execute_orders(quotes);
+        fills.append(&mut executed);
         for fill in &fills {
             self.trade_log.push(fill.clone());
         }
+        self.cancel_log.extend(canceled_orders.iter().cloned());
 
         self.sort_order_buffer();
-        for order in self.order_buffer.iter_mut() {
-            self.orderbook.insert_order(order.clone());
+        let mut order_statuses = Vec::with_capacity(self.order_buffer.len());
+        for order in self.order_buffer.iter() {
+            let status = match self.validate_order(order, quotes, None) {
+                Ok((shares, price)) => OrderStatus::Resting {
+                    oid: self.orderbook.insert_order(order.clone(), shares, price),
+                    cloid: order.cloid.clone(),
+                },
+                Err(reason) => OrderStatus::Error(reason),
+            };
+            order_statuses.push(status);
+        }
+        self.orderbook.activate_pending_orders();
+
+        TickOutput {
+            fills,
+            inserted_orders: std::mem::take(&mut self.order_buffer),
+            events: JuraTickEvents {
+                triggered: triggered_order_ids,
+                statuses: order_statuses,
+                funding: Vec::new(),
+                canceled: canceled_orders,
+            },
         }
-
-        println!("{:?}", self.orderbook);
-
-        let inserted_orders = std::mem::take(&mut self.order_buffer);
-        (fills, inserted_orders, triggered_order_ids)
     }
 }
 
@@ -364,19 +1610,90 @@ impl Default for JuraV1 {
     }
 }
 
+impl Exchange for JuraV1 {
+    type Quote = JuraQuote;
+    type Order = Order;
+    /// Orders are deleted by asset and oid.
+    type OrderId = (u64, OrderId);
+    type Fill = Fill;
+    type InsertResponse = ();
+    type DeleteResponse = bool;
+    type TickEvents = JuraTickEvents;
+
+    /// If no [Universe] has been set then every symbol in the dataset can be traded with default
+    /// rules.
+    fn for_dataset<I: Input>(&self, dataset: &I) -> Self {
+        if self.get_universe().get_assets().is_empty() {
+            self.clone()
+                .with_universe(Universe::from_symbols(dataset.get_symbols()))
+        } else {
+            self.clone()
+        }
+    }
+
+    fn insert_order(&mut self, order: Order) {
+        JuraV1::insert_order(self, order)
+    }
+
+    fn delete_order(&mut self, order_id: (u64, OrderId)) -> bool {
+        JuraV1::delete_order(self, order_id.0, order_id.1)
+    }
+}
+
+/// Datasets must have funding rates, inputs without funding return None from
+/// [get_funding_rates](Funding::get_funding_rates).
+impl<I: Input + Funding> Tick<I> for JuraV1 {
+    /// Funding is settled before orders execute, see [settle_funding](JuraV1::settle_funding).
+    /// Rates on dates without quotes are summed and paid on the next tick.
+    fn tick(&mut self, dataset: &I, date: i64) -> TickOutput<Self>
+    where
+        I::Quote: Into<JuraQuote>,
+    {
+        let Some(quotes) = self.quotes(dataset, date) else {
+            return TickOutput::default();
+        };
+        let rates = match self.funded_until {
+            Some(previous) => dataset.get_funding_rates_between(&previous, &date),
+            None => dataset.get_funding_rates(&date),
+        };
+        self.funded_until = Some(date);
+        let funding = match rates {
+            Some(rates) => self.execute_funding(date, &rates, &quotes),
+            None => Vec::new(),
+        };
+        let mut output = self.execute_tick(&quotes);
+        output.events.funding = funding;
+        output
+    }
+}
+
 /// OrderBook is an implementation of the Hyperliquid API running against a local server. This allows
 /// testing of strategies using the same API/order types/etc.
 ///
-/// Hyperliquid is a derivatives exchange. In order to simplify the implementation it is assumed
-/// that everything is cash/no margin/no leverage.
+/// Hyperliquid is a derivatives exchange. The client has a balance of collateral and a [Leverage]
+/// setting for each asset. Orders that would increase a position are rejected when they arrive,
+/// and cancelled with [CancelReason::Margin] when they would execute, if the initial margin at
+/// the order's limit price is greater than the margin available. Initial margin is notional value
+/// divided by leverage. Limit orders in the book reserve their initial margin, so margin available
+/// to an order excludes that reserved by every other order.
+///
+/// On every tick positions are marked at the mid price, before any orders execute. A position
+/// is liquidated at the mark price when its equity is less than maintenance margin, half of
+/// initial margin at the max leverage of the asset. Isolated positions are checked individually
+/// and can lose at most their margin. Cross positions are checked against the balance of the
+/// account and are all liquidated together. Liquidation fills take oids counting up from
+/// [LIQUIDATION_OID_START].
 ///
 /// Hyperliquid has two order types: limit and trigger.
 ///
-/// Limit orders have various [TimeInForce] settings, we currently only support [TimeInForce::Ioc]
-/// and [TimeInForce::Gtc]. This is roughly equivalent to a market order that will execute on the
-/// next tick after entry with maximum slippage of 10%. Slippage is constant in this
-/// implementation as this is the default setting in production. If this doesn't execute on the
-/// next tick then it is cancelled.
+/// Limit orders have various [TimeInForce] settings. [TimeInForce::Ioc] is roughly equivalent to
+/// a market order that will execute on the next tick after entry with maximum slippage of 10%.
+/// Slippage is constant in this implementation as this is the default setting in production. If
+/// this doesn't execute on the next tick then it is cancelled.
+///
+/// [TimeInForce::Alo] is post-only. If the order would cross the quote when it arrives at the
+/// book then it is rejected with an [OrderStatus::Error], otherwise it rests in the book as a maker
+/// order and executes in the same way as [TimeInForce::Gtc].
 ///
 /// Trigger orders are orders that turn into Limit orders when a trigger has been hit. The
 /// trigger_px and limit_px are distinct so this works slightly differently to a normal TP/SL
@@ -392,14 +1709,24 @@ impl Default for JuraV1 {
 /// When an order is triggered, is_market is used to determine whether the [TimeInForce] is
 /// [TimeInForce::Ioc] (if true) or [TimeInForce::Gtc] (if false).
 ///
-/// After a trade executes a fill is returned to the user, the data returned is substantially
-/// different to the Hyperliquid API due to Hyperliquid performing functions like margin.
-/// The differences are documented in [Fill].
+/// After a trade executes a fill is returned to the user and the [Position] in that asset is
+/// updated. The differences from the Hyperliquid API are documented in [Fill].
+///
+/// A fill is a taker, crossed, if it came from a [TimeInForce::Ioc] order or from a
+/// [TimeInForce::Gtc] order on the first tick on which it could execute. Gtc orders that rest for
+/// at least one tick and [TimeInForce::Alo] orders are makers.
 #[derive(Clone, Debug)]
 struct OrderBook {
     inner: VecDeque<InnerOrder>,
     last_inserted: u64,
+    // Liquidations are not client orders so take oids from a separate range
+    last_liquidation: u64,
     slippage: f64,
+    account: Account,
+    // Index of resting orders by client order id
+    cloids: HashMap<String, OrderId>,
+    // Date of the last tick
+    now: i64,
 }
 
 impl Default for OrderBook {
@@ -413,10 +1740,20 @@ impl OrderBook {
         Self {
             inner: VecDeque::new(),
             last_inserted: 0,
+            last_liquidation: LIQUIDATION_OID_START,
             slippage: 0.1,
+            account: Account::new(DEFAULT_BALANCE),
+            cloids: HashMap::new(),
+            now: 0,
         }
     }
 
+    fn contains(&self, asset: u64, order_id: u64) -> bool {
+        self.inner
+            .iter()
+            .any(|order| order_id == order.order_id && asset == order.order.asset)
+    }
+
     // Hyperliquid returns an error if we try to cancel a non-existent order
     pub fn delete_order(&mut self, asset: u64, order_id: u64) -> bool {
         let mut delete_position: Option<usize> = None;
@@ -427,7 +1764,11 @@ impl OrderBook {
             }
         }
         if let Some(position) = delete_position {
-            self.inner.remove(position);
+            if let Some(order) = self.inner.remove(position) {
+                if let Some(cloid) = &order.order.cloid {
+                    self.cloids.remove(cloid);
+                }
+            }
             return true;
         }
         false
@@ -436,56 +1777,199 @@ impl OrderBook {
     // Hyperliquid immediately returns an oid to the user whether the order is resting or filled on
     // the next tick. Because we need to guard against lookahead bias, we cannot execute immediately
     // but we have to return order id here.
-    pub fn insert_order(&mut self, order: Order) -> OrderId {
+    pub fn insert_order(&mut self, order: Order, shares: f64, price: f64) -> OrderId {
         let order_id = self.last_inserted;
         // We assume that orders are received instaneously.
         // Latency can be added here when this is implemented.
+        if let Some(cloid) = &order.cloid {
+            self.cloids.insert(cloid.clone(), order_id);
+        }
         let inner_order = InnerOrder {
             order_id,
             order,
             attempted_execution: false,
+            timestamp: self.now,
+            pending: false,
+            shares,
+            price,
         };
         self.inner.push_back(inner_order);
         self.last_inserted += 1;
         order_id
     }
 
-    fn execute_buy(quote: JuraQuote, order: &InnerOrder, date: i64) -> Fill {
-        let trade_price = quote.ask;
-        Fill {
-            closed_pnl: "0.0".to_string(),
-            coin: order.order.asset.to_string(),
-            crossed: false,
-            dir: false,
-            hash: false,
-            oid: order.order_id,
-            px: trade_price.to_string(),
-            side: Side::Ask.into(),
-            start_position: false,
-            sz: order.get_shares().to_string(),
-            time: date,
+    pub fn insert_pending_order(
+        &mut self,
+        order: Order,
+        shares: f64,
+        price: f64,
+        date: i64,
+    ) -> OrderId {
+        let order_id = self.insert_order(order, shares, price);
+        if let Some(inner_order) = self.inner.back_mut() {
+            inner_order.timestamp = date;
+            inner_order.pending = true;
+        }
+        order_id
+    }
+
+    pub fn activate_pending_orders(&mut self) {
+        for order in self.inner.iter_mut() {
+            order.pending = false;
         }
     }
 
-    fn execute_sell(quote: JuraQuote, order: &InnerOrder, date: i64) -> Fill {
-        let trade_price = quote.bid;
+    // Executes `shares` of the order, which can be less than the order size if it is reduce-only
+    fn execute(
+        account: &mut Account,
+        trade_price: f64,
+        order: &InnerOrder,
+        shares: f64,
+        date: i64,
+        crossed: bool,
+    ) -> Fill {
+        let (start_position, dir, closed_pnl) =
+            account.apply_fill(order.order.asset, order.order.is_buy, shares, trade_price);
+        let side = if order.order.is_buy {
+            Side::Bid
+        } else {
+            Side::Ask
+        };
+
         Fill {
-            closed_pnl: "0.0".to_string(),
-            coin: order.order.asset.to_string(),
-            crossed: false,
-            dir: false,
+            closed_pnl: closed_pnl.to_string(),
+            coin: account.universe.coin(order.order.asset),
+            crossed,
+            dir,
             hash: false,
             oid: order.order_id,
             px: trade_price.to_string(),
-            side: Side::Bid.into(),
-            start_position: false,
-            sz: order.get_shares().to_string(),
+            side: side.into(),
+            start_position: start_position.to_string(),
+            sz: shares.to_string(),
             time: date,
+            cloid: order.order.cloid.clone(),
+            liquidation: None,
+        }
+    }
+
+    fn execute_buy(
+        account: &mut Account,
+        quote: JuraQuote,
+        order: &InnerOrder,
+        shares: f64,
+        date: i64,
+        crossed: bool,
+    ) -> Fill {
+        Self::execute(account, quote.ask, order, shares, date, crossed)
+    }
+
+    fn execute_sell(
+        account: &mut Account,
+        quote: JuraQuote,
+        order: &InnerOrder,
+        shares: f64,
+        date: i64,
+        crossed: bool,
+    ) -> Fill {
+        Self::execute(account, quote.bid, order, shares, date, crossed)
+    }
+
+    fn reducible_shares(&self, order: &Order) -> f64 {
+        self.account.reducible_shares(order.asset, order.is_buy)
+    }
+
+    // Initial margin of the limit orders in the book that would increase a position, pending
+    // orders included. Orders are taken in book order and the first orders against a position
+    // reduce it, so reserve nothing. `excluded` orders are executing or being replaced.
+    fn reserved_margin(&self, excluded: &[OrderId]) -> f64 {
+        let mut reducible: HashMap<(u64, bool), f64> = HashMap::new();
+        let mut reserved = 0.0;
+        for order in &self.inner {
+            if excluded.contains(&order.order_id)
+                || order.order.reduce_only
+                || !matches!(order.order.order_type, OrderType::Limit(..))
+            {
+                continue;
+            }
+            let (asset, is_buy) = (order.order.asset, order.order.is_buy);
+            let left = reducible
+                .entry((asset, is_buy))
+                .or_insert_with(|| self.account.reducible_shares(asset, is_buy));
+            let closed = order.shares.min(*left);
+            *left -= closed;
+            reserved += (order.shares - closed) * order.price
+                / self.account.get_leverage(asset).value() as f64;
+        }
+        reserved
+    }
+
+    fn has_margin_for(&self, order: &Order, shares: f64, price: f64, excluded: &[OrderId]) -> bool {
+        if shares <= self.reducible_shares(order) {
+            return true;
+        }
+        self.account.has_margin_for(
+            order.asset,
+            order.is_buy,
+            shares,
+            price,
+            self.reserved_margin(excluded),
+        )
+    }
+
+    // Closes positions that have breached maintenance margin at the mark price
+    pub fn liquidate(&mut self, date: i64) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        for asset in self.account.breached_positions() {
+            let position = self.account.positions[&asset].clone();
+            let mark = self.account.mark(asset, &position);
+            let equity = position.margin + self.account.unrealized_pnl(asset, &position);
+            let is_buy = position.szi < 0.0;
+            let (start_position, dir, closed_pnl) =
+                self.account
+                    .apply_fill(asset, is_buy, position.szi.abs(), mark);
+            // Losses on an isolated position are limited to the margin of the position
+            if !self.account.get_leverage(asset).is_cross() && equity < 0.0 {
+                self.account.balance -= equity;
+            }
+
+            let side = if is_buy { Side::Bid } else { Side::Ask };
+            fills.push(Fill {
+                closed_pnl: closed_pnl.to_string(),
+                coin: self.account.universe.coin(asset),
+                crossed: true,
+                dir,
+                hash: false,
+                oid: self.last_liquidation,
+                px: mark.to_string(),
+                side: side.into(),
+                start_position: start_position.to_string(),
+                sz: position.szi.abs().to_string(),
+                time: date,
+                cloid: None,
+                liquidation: Some(Liquidation {
+                    mark_px: mark.to_string(),
+                    method: "market".to_string(),
+                }),
+            });
+            self.last_liquidation += 1;
+        }
+        fills
+    }
+
+    // Whether a limit order would execute immediately against the quote. Takes the price parsed
+    // when the order was validated, orders without a valid price are rejected before this.
+    fn crosses(is_buy: bool, price: f64, quote: &JuraQuote) -> bool {
+        if is_buy {
+            price >= quote.ask
+        } else {
+            price <= quote.bid
         }
     }
 
-    fn create_trigger(order: &InnerOrder, tif: TimeInForce) -> Order {
-        Order {
+    // Triggered orders keep the size and price of the trigger order
+    fn create_trigger(order: &InnerOrder, tif: TimeInForce) -> (Order, f64, f64) {
+        let triggered = Order {
             asset: order.order.asset,
             is_buy: order.order.is_buy,
             limit_px: order.order.limit_px.clone(),
@@ -493,28 +1977,66 @@ impl OrderBook {
             reduce_only: order.order.reduce_only,
             cloid: order.order.cloid.clone(),
             order_type: OrderType::Limit(LimitOrder { tif }),
-        }
+        };
+        (triggered, order.shares, order.price)
     }
 
-    fn create_gtc_trigger(order: &InnerOrder) -> Order {
+    fn create_gtc_trigger(order: &InnerOrder) -> (Order, f64, f64) {
         Self::create_trigger(order, TimeInForce::Gtc)
     }
 
-    fn create_ioc_trigger(order: &InnerOrder) -> Order {
+    fn create_ioc_trigger(order: &InnerOrder) -> (Order, f64, f64) {
         Self::create_trigger(order, TimeInForce::Ioc)
     }
 
-    pub fn execute_orders(&mut self, quotes: &PenelopeQuoteByDate) -> (Vec<Fill>, Vec<OrderId>) {
+    pub fn execute_orders(
+        &mut self,
+        quotes: &JuraQuoteByDate,
+    ) -> (Vec<Fill>, Vec<OrderId>, Vec<CanceledOrder>) {
         let mut fills: Vec<Fill> = Vec::new();
         let mut should_delete: Vec<(u64, u64)> = Vec::new();
+        let mut canceled: Vec<CanceledOrder> = Vec::new();
         // HyperLiquid execution can trigger more orders, we don't execute these immediately.
-        let mut should_insert: Vec<Order> = Vec::new();
+        let mut should_insert: Vec<(Order, f64, f64)> = Vec::new();
 
         // We have to have a mutable reference so we can update attempted_execution
-        for order in self.inner.iter_mut() {
-            let symbol = order.order.asset.to_string();
+        for position in 0..self.inner.len() {
+            if self.inner[position].pending {
+                continue;
+            }
+            // Reduce-only orders are clipped to the current position and cancelled if there is
+            // nothing left to reduce. Trigger orders are checked when the triggered order executes.
+            // Only the execution is clipped, an order that doesn't execute keeps its size.
+            let order = &self.inner[position];
+            let mut shares = order.shares;
+            if order.order.reduce_only && matches!(order.order.order_type, OrderType::Limit(..)) {
+                let reducible = self.reducible_shares(&order.order);
+                if reducible <= 0.0 {
+                    canceled.push(order.cancel(
+                        &self.account.universe,
+                        CancelReason::ReduceOnly,
+                        self.now,
+                    ));
+                    should_delete.push((order.order.asset, order.order_id));
+                    continue;
+                }
+                shares = shares.min(reducible);
+            }
+
+            let mut executing: Vec<OrderId> = should_delete.iter().map(|(_, oid)| *oid).collect();
+            executing.push(order.order_id);
+            if matches!(order.order.order_type, OrderType::Limit(..))
+                && !self.has_margin_for(&order.order, shares, order.price, &executing)
+            {
+                canceled.push(order.cancel(&self.account.universe, CancelReason::Margin, self.now));
+                should_delete.push((order.order.asset, order.order_id));
+                continue;
+            }
+
+            let order = &mut self.inner[position];
+            let symbol = self.account.universe.coin(order.order.asset);
             if let Some(quote) = quotes.get(&symbol) {
-                let quote_copy: JuraQuote = quote.clone().into();
+                let quote_copy = quote.clone();
                 let date = quote_copy.date;
                 let result = match &order.order.order_type {
                     OrderType::Limit(limit) => {
@@ -524,53 +2046,86 @@ impl OrderBook {
                         // Market order code in Python SDK:
                         // https://github.com/hyperliquid-dex/hyperliquid-python-sdk/blob/67864cf979d3bbea2e964a99ecc0a1effb7bb911/hyperliquid/exchange.py#L209
                         match limit.tif {
-                            // Don't support Alo TimeInForce
                             TimeInForce::Ioc => {
                                 // Market orders can only be executed on the next time step
                                 if order.attempted_execution {
                                     // We have tried to execute this before, return nothing
+                                    canceled.push(order.cancel(
+                                        &self.account.universe,
+                                        CancelReason::Ioc,
+                                        self.now,
+                                    ));
                                     should_delete.push((order.order.asset, order.order_id));
                                     None
                                 } else {
                                     // For a market order, the limit price represents the maximum amount
                                     // of slippage tolerated by the client
-
-                                    // We unwrap here because if the client is sending us bad prices
-                                    // then we need to stop execution
-                                    let price = str::parse::<f64>(&order.order.limit_px).unwrap();
+                                    let price = order.price;
                                     order.attempted_execution = true;
                                     if order.order.is_buy {
                                         if price * (1.0 + self.slippage) >= quote_copy.ask {
                                             should_delete.push((order.order.asset, order.order_id));
-                                            Some(Self::execute_buy(quote_copy, order, date))
+                                            Some(Self::execute_buy(
+                                                &mut self.account,
+                                                quote_copy,
+                                                order,
+                                                shares,
+                                                date,
+                                                true,
+                                            ))
                                         } else {
                                             None
                                         }
                                     } else if price * (1.0 - self.slippage) <= quote_copy.bid {
                                         should_delete.push((order.order.asset, order.order_id));
-                                        Some(Self::execute_sell(quote_copy, order, date))
+                                        Some(Self::execute_sell(
+                                            &mut self.account,
+                                            quote_copy,
+                                            order,
+                                            shares,
+                                            date,
+                                            true,
+                                        ))
                                     } else {
                                         None
                                     }
                                 }
                             }
-                            TimeInForce::Gtc => {
-                                let price = str::parse::<f64>(&order.order.limit_px).unwrap();
+                            // Alo orders that crossed on arrival never made it into the book, so
+                            // any Alo order executing here is resting as a maker
+                            TimeInForce::Gtc | TimeInForce::Alo => {
+                                let price = order.price;
+                                let crossed = matches!(limit.tif, TimeInForce::Gtc)
+                                    && !order.attempted_execution;
+                                order.attempted_execution = true;
                                 if order.order.is_buy {
                                     if price >= quote_copy.ask {
                                         should_delete.push((order.order.asset, order.order_id));
-                                        Some(Self::execute_buy(quote_copy, order, date))
+                                        Some(Self::execute_buy(
+                                            &mut self.account,
+                                            quote_copy,
+                                            order,
+                                            shares,
+                                            date,
+                                            crossed,
+                                        ))
                                     } else {
                                         None
                                     }
                                 } else if price <= quote_copy.bid {
                                     should_delete.push((order.order.asset, order.order_id));
-                                    Some(Self::execute_sell(quote_copy, order, date))
+                                    Some(Self::execute_sell(
+                                        &mut self.account,
+                                        quote_copy,
+                                        order,
+                                        shares,
+                                        date,
+                                        crossed,
+                                    ))
                                 } else {
                                     None
                                 }
                             }
-                            _ => unimplemented!(),
                         }
                     }
                     OrderType::Trigger(trigger) => {
@@ -657,17 +2212,22 @@ impl OrderBook {
 
         let mut triggered_order_ids = Vec::new();
 
-        for order in should_insert {
-            triggered_order_ids.push(self.insert_order(order));
+        for (order, shares, price) in should_insert {
+            triggered_order_ids.push(self.insert_order(order, shares, price));
         }
-        (fills, triggered_order_ids)
+        (fills, triggered_order_ids, canceled)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{JuraV1, Order};
+    use super::{
+        AssetMeta, CancelReason, Fill, JuraTickEvents, JuraV1, Leverage, Order, OrderStatus,
+        OrderWire, Position, Universe, DEFAULT_BALANCE, LIQUIDATION_OID_START,
+    };
+    use crate::exchange::{Tick, TickOutput};
     use crate::input::penelope::Penelope;
+    use std::collections::HashMap;
 
     fn setup() -> (Penelope, JuraV1) {
         let mut source = Penelope::new();
@@ -675,18 +2235,861 @@ mod tests {
         source.add_quote(102.00, 103.00, 101, "0".to_owned());
         source.add_quote(105.00, 106.00, 102, "0".to_owned());
 
-        let exchange = JuraV1::new();
+        let exchange = JuraV1::new().with_universe(Universe::from_symbols(source.get_symbols()));
 
         (source, exchange)
     }
 
+    #[test]
+    fn test_that_market_order_outside_configured_slippage_is_not_filled() {
+        let (source, _) = setup();
+        let mut exchange = JuraV1::new()
+            .with_universe(Universe::from_symbols(source.get_symbols()))
+            .with_slippage(0.0);
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert_eq!(exchange.trade_log.len(), 0);
+    }
+
+    #[test]
+    fn test_that_exchange_without_universe_trades_assets_by_id() {
+        let (source, _) = setup();
+        let mut exchange = JuraV1::new();
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "103.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let fills = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].coin, "0");
+        assert_eq!(exchange.get_position(0).unwrap().szi, 100.0);
+    }
+
+    #[test]
+    fn test_that_market_order_that_misses_is_cancelled_with_ioc_reason() {
+        let (source, _) = setup();
+        let mut exchange = JuraV1::new()
+            .with_universe(Universe::from_symbols(source.get_symbols()))
+            .with_slippage(0.0);
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let canceled = exchange
+            .tick(&source.get_quotes_unchecked(&101))
+            .events
+            .canceled;
+        //First attempt misses but the order stays in the book until the next tick
+        assert!(canceled.is_empty());
+
+        let TickOutput {
+            fills,
+            events: JuraTickEvents { canceled, .. },
+            ..
+        } = exchange.tick(&source.get_quotes_unchecked(&102));
+        assert!(fills.is_empty());
+        assert_eq!(canceled.len(), 1);
+        assert_eq!(canceled[0].status, CancelReason::Ioc);
+        assert_eq!(canceled[0].status_timestamp, 102);
+        assert!(exchange.get_open_orders().is_empty());
+        assert_eq!(
+            exchange.get_order_status(0).unwrap().status,
+            "iocCancelRejected"
+        );
+    }
+
+    #[test]
+    fn test_that_post_only_order_that_crosses_is_rejected() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::post_only_buy(0_u64, "100.0", "102.00"));
+        exchange.insert_order(Order::post_only_sell(0_u64, "100.0", "101.00"));
+        let TickOutput {
+            inserted_orders: inserted,
+            events: JuraTickEvents { statuses, .. },
+            ..
+        } = exchange.tick(&source.get_quotes_unchecked(&100));
+
+        assert_eq!(inserted.len(), 2);
+        assert!(statuses
+            .iter()
+            .all(|status| matches!(status, OrderStatus::Error(..))));
+
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        assert_eq!(exchange.trade_log.len(), 0);
+        assert!(exchange.orderbook.inner.is_empty());
+    }
+
+    #[test]
+    fn test_that_post_only_order_without_valid_price_is_rejected() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::post_only_buy(0_u64, "100.0", "abc"));
+        let statuses = exchange
+            .tick(&source.get_quotes_unchecked(&100))
+            .events
+            .statuses;
+
+        assert!(
+            matches!(&statuses[0], OrderStatus::Error(reason) if reason.contains("invalid price"))
+        );
+        assert!(exchange.orderbook.inner.is_empty());
+    }
+
+    #[test]
+    fn test_that_post_only_order_rests_and_executes_as_maker() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::post_only_sell(0_u64, "100.0", "104.00"));
+        let statuses = exchange
+            .tick(&source.get_quotes_unchecked(&100))
+            .events
+            .statuses;
+        assert_eq!(
+            statuses,
+            vec![OrderStatus::Resting {
+                oid: 0,
+                cloid: None
+            }]
+        );
+
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        assert_eq!(exchange.trade_log.len(), 0);
+
+        exchange.tick(&source.get_quotes_unchecked(&102));
+        assert_eq!(exchange.trade_log.len(), 1);
+        assert_eq!(exchange.trade_log[0].oid, 0);
+    }
+
+    #[test]
+    fn test_that_position_tracks_average_entry_and_closed_pnl() {
+        let mut position = Position::default();
+
+        assert_eq!(
+            position.apply(true, 10.0, 100.0),
+            ("Open Long".to_string(), 0.0)
+        );
+        assert_eq!(
+            position.apply(true, 10.0, 110.0),
+            ("Open Long".to_string(), 0.0)
+        );
+        assert_eq!(position.entry_px, 105.0);
+
+        assert_eq!(
+            position.apply(false, 5.0, 115.0),
+            ("Close Long".to_string(), 50.0)
+        );
+        assert_eq!(position.szi, 15.0);
+        assert_eq!(position.entry_px, 105.0);
+
+        assert_eq!(
+            position.apply(false, 20.0, 100.0),
+            ("Long > Short".to_string(), -75.0)
+        );
+        assert_eq!(position.szi, -5.0);
+        assert_eq!(position.entry_px, 100.0);
+
+        assert_eq!(
+            position.apply(true, 5.0, 90.0),
+            ("Close Short".to_string(), 50.0)
+        );
+        assert_eq!(position, Position::default());
+    }
+
+    #[test]
+    fn test_that_position_closed_in_float_steps_is_flat() {
+        let mut position = Position::default();
+
+        position.apply(true, 0.3, 100.0);
+        position.apply(false, 0.1, 100.0);
+        position.apply(false, 0.2, 100.0);
+        assert_eq!(position, Position::default());
+
+        assert_eq!(
+            position.apply(false, 0.1, 100.0),
+            ("Open Short".to_string(), 0.0)
+        );
+        assert_eq!(position.entry_px, 100.0);
+    }
+
+    #[test]
+    fn test_that_fill_accepts_snake_case_fields() {
+        let fill: Fill = serde_json::from_str(
+            r#"{"closed_pnl":"0.0","coin":"0","crossed":true,"dir":"Open Long","hash":false,"oid":0,"px":"100.0","side":"B","start_position":"0.0","sz":"1.0","time":100}"#,
+        )
+        .unwrap();
+        assert_eq!(fill.start_position, "0.0");
+        assert!(serde_json::to_string(&fill)
+            .unwrap()
+            .contains("startPosition"));
+    }
+
+    #[test]
+    fn test_that_fills_report_position_changes() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.insert_order(Order::market_sell(0_u64, "100.0", "105.00"));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&102));
+
+        let open = &exchange.trade_log[0];
+        assert_eq!(open.dir, "Open Long");
+        assert_eq!(open.side, "B");
+        assert_eq!(open.start_position, "0");
+        assert_eq!(open.closed_pnl, "0");
+        assert!(open.crossed);
+
+        //Bought at 103, sold at 105
+        let close = &exchange.trade_log[1];
+        assert_eq!(close.dir, "Close Long");
+        assert_eq!(close.side, "A");
+        assert_eq!(close.start_position, "100");
+        assert_eq!(close.closed_pnl, "200");
+        assert_eq!(exchange.get_position(0), Some(&Position::default()));
+    }
+
+    #[test]
+    fn test_that_resting_limit_order_is_maker() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::limit_buy(0_u64, "100.0", "103.00"));
+        exchange.insert_order(Order::limit_sell(0_u64, "100.0", "104.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&102));
+
+        //Buy crosses on the first tick it can execute, sell rests for one tick
+        assert_eq!(exchange.trade_log.len(), 2);
+        assert!(exchange.trade_log[0].crossed);
+        assert!(!exchange.trade_log[1].crossed);
+    }
+
+    #[test]
+    fn test_that_reduce_only_order_without_position_is_rejected() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::market_sell(0_u64, "100.0", "101.00").with_reduce_only(true));
+        let statuses = exchange
+            .tick(&source.get_quotes_unchecked(&100))
+            .events
+            .statuses;
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert!(matches!(statuses[0], OrderStatus::Error(..)));
+        assert!(exchange.trade_log.is_empty());
+    }
+
+    #[test]
+    fn test_that_reduce_only_order_with_nothing_to_reduce_is_cancelled_with_reason() {
+        let mut source = Penelope::new();
+        source.add_quote(101.00, 102.00, 100, "0");
+        source.add_quote(102.00, 103.00, 101, "0");
+        source.add_quote(105.00, 106.00, 102, "0");
+        source.add_quote(105.00, 106.00, 103, "0");
+        source.add_quote(105.00, 106.00, 104, "0");
+        let mut exchange =
+            JuraV1::new().with_universe(Universe::from_symbols(source.get_symbols()));
+
+        exchange.insert_order(Order::market_buy(0_u64, "50.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.insert_order(Order::limit_sell(0_u64, "50.0", "200.00").with_reduce_only(true));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        //Position is closed by another order before the reduce-only order can execute
+        exchange.insert_order(Order::market_sell(0_u64, "50.0", "104.00"));
+        exchange.tick(&source.get_quotes_unchecked(&102));
+        let canceled = exchange
+            .tick(&source.get_quotes_unchecked(&103))
+            .events
+            .canceled;
+        assert!(canceled.is_empty());
+        assert_eq!(exchange.get_position(0), Some(&Position::default()));
+
+        let canceled = exchange
+            .tick(&source.get_quotes_unchecked(&104))
+            .events
+            .canceled;
+        assert_eq!(canceled.len(), 1);
+        assert_eq!(canceled[0].order.oid, 1);
+        assert_eq!(canceled[0].status, CancelReason::ReduceOnly);
+        assert_eq!(canceled[0].status_timestamp, 104);
+        assert!(exchange.get_open_orders().is_empty());
+    }
+
+    #[test]
+    fn test_that_reduce_only_order_is_clipped_to_position() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::market_buy(0_u64, "50.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.insert_order(Order::market_sell(0_u64, "100.0", "102.00").with_reduce_only(true));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&102));
+
+        assert_eq!(exchange.trade_log.len(), 2);
+        assert_eq!(exchange.trade_log[1].sz, "50");
+        assert_eq!(exchange.trade_log[1].dir, "Close Long");
+        assert_eq!(exchange.get_position(0), Some(&Position::default()));
+    }
+
+    #[test]
+    fn test_that_resting_reduce_only_order_keeps_size_until_it_executes() {
+        let mut source = Penelope::new();
+        source.add_quote(101.00, 102.00, 100, "0");
+        source.add_quote(102.00, 103.00, 101, "0");
+        source.add_quote(102.00, 103.00, 102, "0");
+        source.add_quote(121.00, 122.00, 103, "0");
+        let mut exchange =
+            JuraV1::new().with_universe(Universe::from_symbols(source.get_symbols()));
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.insert_order(Order::limit_sell(0_u64, "100.0", "120.00").with_reduce_only(true));
+        exchange.insert_order(Order::market_sell(0_u64, "50.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&102));
+
+        //Position is smaller than the resting order but the order is unchanged until it executes
+        assert_eq!(exchange.get_position(0).unwrap().szi, 50.0);
+        assert_eq!(exchange.get_open_orders()[0].sz, "100.0");
+
+        let fills = exchange.tick(&source.get_quotes_unchecked(&103)).fills;
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].sz, "50");
+        assert_eq!(exchange.get_position(0), Some(&Position::default()));
+        assert!(exchange.get_open_orders().is_empty());
+    }
+
+    #[test]
+    fn test_that_triggered_reduce_only_order_does_not_open_position() {
+        let mut source = Penelope::new();
+        source.add_quote(101.00, 102.00, 100, "0");
+        source.add_quote(102.00, 103.00, 101, "0");
+        source.add_quote(105.00, 106.00, 102, "0");
+        source.add_quote(110.00, 111.00, 103, "0");
+        source.add_quote(110.00, 111.00, 104, "0");
+        let mut exchange =
+            JuraV1::new().with_universe(Universe::from_symbols(source.get_symbols()));
+
+        //Open short with a stop loss, then close the short before the stop triggers
+        exchange.insert_order(Order::market_sell(0_u64, "100.0", "101.00"));
+        exchange.insert_order(Order::stop_buy(0_u64, "100.0", "110.00").with_reduce_only(true));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "103.00"));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&102));
+
+        let triggered = exchange
+            .tick(&source.get_quotes_unchecked(&103))
+            .events
+            .triggered;
+        assert_eq!(triggered.len(), 1);
+        exchange.tick(&source.get_quotes_unchecked(&104));
+
+        assert_eq!(exchange.trade_log.len(), 2);
+        assert_eq!(exchange.get_position(0), Some(&Position::default()));
+        assert!(exchange.orderbook.inner.is_empty());
+    }
+
+    #[test]
+    fn test_that_cloid_is_returned_on_status_and_fill() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00").with_cloid("0x1"));
+        let statuses = exchange
+            .tick(&source.get_quotes_unchecked(&100))
+            .events
+            .statuses;
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert_eq!(
+            statuses,
+            vec![OrderStatus::Resting {
+                oid: 0,
+                cloid: Some("0x1".to_string())
+            }]
+        );
+        assert_eq!(exchange.trade_log[0].cloid, Some("0x1".to_string()));
+        //Cloid can be reused once the order is no longer resting
+        assert!(exchange.orderbook.cloids.is_empty());
+    }
+
+    #[test]
+    fn test_that_duplicate_cloid_is_rejected() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::limit_buy(0_u64, "100.0", "90.00").with_cloid("0x1"));
+        exchange.insert_order(Order::limit_buy(0_u64, "100.0", "91.00").with_cloid("0x1"));
+        let statuses = exchange
+            .tick(&source.get_quotes_unchecked(&100))
+            .events
+            .statuses;
+
+        assert!(matches!(statuses[0], OrderStatus::Resting { oid: 0, .. }));
+        assert!(matches!(statuses[1], OrderStatus::Error(..)));
+        assert_eq!(exchange.orderbook.inner.len(), 1);
+    }
+
+    #[test]
+    fn test_that_order_can_be_cancelled_by_cloid() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::limit_buy(0_u64, "100.0", "103.00").with_cloid("0x1"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+
+        assert!(!exchange.delete_order_by_cloid(1, "0x1"));
+        assert!(!exchange.delete_order_by_cloid(0, "0x2"));
+        assert!(exchange.delete_order_by_cloid(0, "0x1"));
+
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        assert!(exchange.trade_log.is_empty());
+        assert!(exchange.orderbook.cloids.is_empty());
+    }
+
+    fn setup_crash() -> Penelope {
+        let mut source = Penelope::new();
+        source.add_quote(101.00, 102.00, 100, "0");
+        source.add_quote(102.00, 103.00, 101, "0");
+        source.add_quote(97.00, 98.00, 102, "0");
+        source.add_quote(89.50, 90.50, 103, "0");
+        source
+    }
+
+    #[test]
+    fn test_that_order_without_margin_is_rejected() {
+        let (source, mut exchange) = setup();
+        exchange = exchange.with_balance(1000.0);
+
+        //Default leverage is 20x, so 510 margin is required for 100 shares at 102
+        exchange.insert_order(Order::market_buy(0_u64, "300.0", "102.00"));
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        let statuses = exchange
+            .tick(&source.get_quotes_unchecked(&100))
+            .events
+            .statuses;
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert!(matches!(statuses[0], OrderStatus::Error(..)));
+        assert!(matches!(statuses[1], OrderStatus::Resting { .. }));
+        assert_eq!(exchange.trade_log.len(), 1);
+        assert_eq!(exchange.get_cross_margin_used(), 100.0 * 102.5 / 20.0);
+    }
+
+    #[test]
+    fn test_that_resting_orders_reserve_margin() {
+        let (source, mut exchange) = setup();
+        exchange = exchange.with_balance(1000.0);
+
+        //425 margin is reserved by the resting order, leaving less than the 637.5 needed
+        exchange.insert_order(Order::limit_buy(0_u64, "100.0", "85.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.insert_order(Order::limit_buy(0_u64, "150.0", "85.00"));
+        exchange.insert_order(Order::limit_buy(0_u64, "50.0", "85.00"));
+        let statuses = exchange
+            .tick(&source.get_quotes_unchecked(&101))
+            .events
+            .statuses;
+
+        assert!(matches!(statuses[0], OrderStatus::Error(..)));
+        assert!(matches!(statuses[1], OrderStatus::Resting { .. }));
+        assert_eq!(exchange.get_open_orders().len(), 2);
+    }
+
+    #[test]
+    fn test_that_resting_order_without_margin_is_cancelled_with_reason() {
+        let source = setup_crash();
+        let mut exchange = JuraV1::new()
+            .with_universe(Universe::from_symbols(source.get_symbols()))
+            .with_balance(1000.0);
+
+        //637.5 margin is required for the limit order, the long loses 400 on the crash
+        exchange.insert_order(Order::limit_buy(0_u64, "150.0", "85.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.insert_order(Order::market_buy(0_u64, "50.0", "98.00"));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        let TickOutput {
+            fills,
+            events: JuraTickEvents { canceled, .. },
+            ..
+        } = exchange.tick(&source.get_quotes_unchecked(&102));
+        assert_eq!(fills.len(), 1);
+        assert!(canceled.is_empty());
+        assert_eq!(exchange.get_order_status(0).unwrap().status, "open");
+
+        let canceled = exchange
+            .tick(&source.get_quotes_unchecked(&103))
+            .events
+            .canceled;
+        assert_eq!(canceled.len(), 1);
+        assert_eq!(canceled[0].status, CancelReason::Margin);
+        assert!(exchange.get_open_orders().is_empty());
+
+        let status = exchange.get_order_status(0).unwrap();
+        assert_eq!(status.status, "marginCanceled");
+        assert_eq!(status.status_timestamp, 103);
+        assert!(exchange.get_order_status(1).is_none());
+    }
+
+    #[test]
+    fn test_that_isolated_position_allocates_margin() {
+        let (source, mut exchange) = setup();
+
+        exchange.update_leverage(0, false, 10).unwrap();
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.insert_order(Order::market_sell(0_u64, "100.0", "105.00"));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        let position = exchange.get_position(0).unwrap();
+        assert_eq!(position.margin, 1030.0);
+        assert_eq!(exchange.get_balance(), DEFAULT_BALANCE - 1030.0);
+        //Bought at 103, marked at 102.5
+        assert_eq!(exchange.get_account_value(), DEFAULT_BALANCE - 50.0);
+
+        exchange.tick(&source.get_quotes_unchecked(&102));
+        assert_eq!(exchange.get_position(0).unwrap().margin, 0.0);
+        assert_eq!(exchange.get_balance(), DEFAULT_BALANCE + 200.0);
+    }
+
+    #[test]
+    fn test_that_cross_position_is_liquidated_at_mark() {
+        let source = setup_crash();
+        let mut exchange = JuraV1::new()
+            .with_universe(Universe::from_symbols(source.get_symbols()))
+            .with_balance(600.0);
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        assert_eq!(exchange.trade_log.len(), 1);
+
+        //Marked at 97.5, equity is 50 and maintenance margin is 97.5
+        let fills = exchange.tick(&source.get_quotes_unchecked(&102)).fills;
+        assert_eq!(fills.len(), 1);
+        let liquidation = &fills[0];
+        assert!(liquidation.liquidation.is_some());
+        assert_eq!(liquidation.dir, "Close Long");
+        assert_eq!(liquidation.px, "97.5");
+        assert_eq!(liquidation.closed_pnl, "-550");
+        assert_eq!(liquidation.oid, LIQUIDATION_OID_START);
+        assert_eq!(exchange.get_position(0), Some(&Position::default()));
+        assert_eq!(exchange.get_balance(), 50.0);
+
+        //Client oids carry on from the last client order
+        exchange.insert_order(Order::limit_buy(0_u64, "1.0", "85.00"));
+        let statuses = exchange
+            .tick(&source.get_quotes_unchecked(&103))
+            .events
+            .statuses;
+        assert!(matches!(statuses[0], OrderStatus::Resting { oid: 1, .. }));
+    }
+
+    #[test]
+    fn test_that_isolated_liquidation_loses_at_most_margin() {
+        let source = setup_crash();
+        let mut exchange = JuraV1::new()
+            .with_universe(Universe::from_symbols(source.get_symbols()))
+            .with_balance(10_000.0);
+
+        exchange.update_leverage(0, false, 10).unwrap();
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        //Equity is 480 with maintenance margin of 97.5
+        let fills = exchange.tick(&source.get_quotes_unchecked(&102)).fills;
+        assert!(fills.is_empty());
+
+        //Marked at 90, position has lost more than margin
+        let fills = exchange.tick(&source.get_quotes_unchecked(&103)).fills;
+        assert_eq!(fills.len(), 1);
+        assert_eq!(exchange.get_balance(), 10_000.0 - 1030.0);
+    }
+
+    #[test]
+    fn test_that_leverage_type_cannot_change_with_open_position() {
+        let (source, mut exchange) = setup();
+
+        assert!(exchange.update_leverage(0, true, 0).is_err());
+        assert!(exchange.update_leverage(0, true, 51).is_err());
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert!(exchange.update_leverage(0, false, 10).is_err());
+        assert!(exchange.update_leverage(0, true, 10).is_ok());
+        assert_eq!(exchange.get_leverage(0), Leverage::Cross { value: 10 });
+    }
+
+    #[test]
+    fn test_that_funding_is_paid_by_longs_when_rate_is_positive() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.insert_order(Order::market_sell(1_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        let quotes = source.get_quotes_unchecked(&102);
+        let rates = HashMap::from([("0".to_string(), 0.001), ("1".to_string(), 0.001)]);
+        let payments = exchange.settle_funding(102, &rates, &quotes);
+
+        //No position in asset 1 so only asset 0 pays, marked at 105.5
+        assert_eq!(payments.len(), 1);
+        assert_eq!(payments[0].coin, "0");
+        assert_eq!(payments[0].szi, "100");
+        assert!((payments[0].usdc.parse::<f64>().unwrap() + 10.55).abs() < 1e-9);
+        assert!((exchange.get_balance() - (DEFAULT_BALANCE - 10.55)).abs() < 1e-9);
+        assert_eq!(exchange.funding_log.len(), 1);
+    }
+
+    #[test]
+    fn test_that_short_receives_funding_into_isolated_margin() {
+        let (source, mut exchange) = setup();
+
+        exchange.update_leverage(0, false, 10).unwrap();
+        exchange.insert_order(Order::market_sell(0_u64, "100.0", "101.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        let balance = exchange.get_balance();
+
+        let quotes = source.get_quotes_unchecked(&102);
+        let rates = HashMap::from([("0".to_string(), 0.001)]);
+        exchange.settle_funding(102, &rates, &quotes);
+
+        //Sold at 102 so margin is 1020, receives 10.55
+        let position = exchange.get_position(0).unwrap();
+        assert!((position.margin - 1030.55).abs() < 1e-9);
+        assert_eq!(exchange.get_balance(), balance);
+    }
+
+    #[test]
+    fn test_that_funding_on_dates_without_quotes_is_paid_on_next_tick() {
+        let mut source = Penelope::new();
+        source.add_quote(101.00, 102.00, 100, "0");
+        source.add_quote(102.00, 103.00, 101, "0");
+        source.add_quote(105.00, 106.00, 110, "0");
+        //Neither date has quotes
+        source.add_funding_rate(0.001, 105, "0");
+        source.add_funding_rate(0.001, 108, "0");
+        let mut exchange =
+            JuraV1::new().with_universe(Universe::from_symbols(source.get_symbols()));
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "103.00"));
+        Tick::tick(&mut exchange, &source, 100);
+        Tick::tick(&mut exchange, &source, 101);
+        let funding = Tick::tick(&mut exchange, &source, 110).events.funding;
+
+        //Rates are summed and paid at the mid on 110, 105.5
+        assert_eq!(funding.len(), 1);
+        assert_eq!(funding[0].time, 110);
+        assert_eq!(funding[0].funding_rate, "0.002");
+        assert!((funding[0].usdc.parse::<f64>().unwrap() + 21.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_that_placed_order_has_oid_but_executes_after_next_tick() {
+        let (source, mut exchange) = setup();
+
+        let quotes = source.get_quotes_unchecked(&100);
+        let status = exchange.place_order(Order::market_buy(0_u64, "100.0", "102.00"), &quotes);
+        assert_eq!(
+            status,
+            OrderStatus::Resting {
+                oid: 0,
+                cloid: None
+            }
+        );
+        assert_eq!(exchange.get_open_orders().len(), 1);
+        assert_eq!(exchange.get_open_orders()[0].timestamp, 100);
+
+        //Order enters the book on this tick, so doesn't execute against these quotes
+        let TickOutput {
+            fills,
+            inserted_orders: inserted,
+            ..
+        } = exchange.tick(&quotes);
+        assert!(fills.is_empty());
+        assert!(inserted.is_empty());
+
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        assert_eq!(exchange.get_trade_log().len(), 1);
+        assert_eq!(exchange.get_trade_log()[0].px, "103");
+    }
+
+    #[test]
+    fn test_that_modify_replaces_resting_order() {
+        let (source, mut exchange) = setup();
+
+        let quotes = source.get_quotes_unchecked(&100);
+        exchange.place_order(Order::limit_buy(0_u64, "100.0", "90.00"), &quotes);
+        let status = exchange.modify_order(0, 0, Order::limit_buy(0_u64, "50.0", "91.00"), &quotes);
+        assert!(matches!(status, OrderStatus::Resting { oid: 1, .. }));
+
+        let open_orders = exchange.get_open_orders();
+        assert_eq!(open_orders.len(), 1);
+        assert_eq!(open_orders[0].limit_px, "91.00");
+
+        let status = exchange.modify_order(0, 0, Order::limit_buy(0_u64, "50.0", "91.00"), &quotes);
+        assert!(matches!(status, OrderStatus::Error(..)));
+    }
+
+    #[test]
+    fn test_that_rejected_modify_leaves_original_order_open() {
+        let (source, mut exchange) = setup();
+
+        let quotes = source.get_quotes_unchecked(&100);
+        exchange.place_order(
+            Order::limit_buy(0_u64, "100.0", "90.00").with_cloid("0x1"),
+            &quotes,
+        );
+        exchange.place_order(
+            Order::limit_buy(0_u64, "100.0", "80.00").with_cloid("0x2"),
+            &quotes,
+        );
+
+        let rejected = [
+            Order::limit_buy(0_u64, "100.0", "90.001"),
+            Order::limit_buy(0_u64, "0.1", "90.00"),
+            Order::limit_buy(0_u64, "100.0", "91.00").with_cloid("0x2"),
+            Order::post_only_buy(0_u64, "100.0", "102.00"),
+        ];
+        for order in rejected {
+            let status = exchange.modify_order(0, 0, order, &quotes);
+            assert!(matches!(status, OrderStatus::Error(..)));
+            assert_eq!(exchange.get_open_orders().len(), 2);
+            assert_eq!(exchange.get_oid_by_cloid("0x1"), Some(0));
+        }
+
+        //Replacement can keep the cloid of the order that it replaces
+        let status = exchange.modify_order(
+            0,
+            0,
+            Order::limit_buy(0_u64, "100.0", "91.00").with_cloid("0x1"),
+            &quotes,
+        );
+        assert!(matches!(status, OrderStatus::Resting { oid: 2, .. }));
+        assert_eq!(exchange.get_oid_by_cloid("0x1"), Some(2));
+        assert_eq!(exchange.get_open_orders().len(), 2);
+    }
+
+    #[test]
+    fn test_that_wire_order_converts_to_order() {
+        let wire: OrderWire = serde_json::from_str(
+            r#"{"a":0,"b":false,"p":"100.0","s":"1.5","r":true,"t":{"trigger":{"isMarket":true,"triggerPx":"100.0","tpsl":"sl"}},"c":"0x1"}"#,
+        )
+        .unwrap();
+        let order = Order::try_from(wire).unwrap();
+        assert!(order.reduce_only);
+        assert_eq!(order.get_cloid(), Some(&"0x1".to_string()));
+
+        let wire: OrderWire = serde_json::from_str(
+            r#"{"a":0,"b":true,"p":"100.0","s":"1.5","r":false,"t":{"limit":{"tif":"Alo"}}}"#,
+        )
+        .unwrap();
+        assert!(Order::try_from(wire).unwrap().is_post_only());
+    }
+
+    #[test]
+    fn test_that_clearinghouse_state_reports_positions() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        let state = exchange.get_clearinghouse_state(101);
+        assert_eq!(state.asset_positions.len(), 1);
+        let position = &state.asset_positions[0].position;
+        assert_eq!(position.szi, "100");
+        assert_eq!(position.entry_px, "103");
+        assert_eq!(position.unrealized_pnl, "-50");
+        assert_eq!(position.leverage, Leverage::Cross { value: 20 });
+        assert_eq!(
+            state.margin_summary.account_value,
+            (DEFAULT_BALANCE - 50.0).to_string()
+        );
+    }
+
+    #[test]
+    fn test_that_order_that_breaks_asset_rules_is_rejected() {
+        let (source, mut exchange) = setup();
+        let quotes = source.get_quotes_unchecked(&100);
+
+        let rejected = [
+            (
+                Order::limit_buy(0_u64, "abc", "100.00"),
+                "Order has invalid size. asset=0",
+            ),
+            (
+                Order::limit_buy(0_u64, "1.000001", "100.00"),
+                "Order has invalid size. asset=0",
+            ),
+            (
+                Order::limit_buy(0_u64, "1.0", "-100.00"),
+                "Order has invalid price. asset=0",
+            ),
+            (
+                Order::limit_buy(0_u64, "1.0", "100.001"),
+                "Price must be divisible by tick size. asset=0",
+            ),
+            (
+                Order::stop_sell(0_u64, "1.0", "abc"),
+                "Order has invalid price. asset=0",
+            ),
+            (
+                Order::limit_buy(0_u64, "0.05", "100.00"),
+                "Order must have minimum value of $10. asset=0",
+            ),
+            (
+                Order::limit_buy(1_u64, "1.0", "100.00"),
+                "Invalid asset. asset=1",
+            ),
+        ];
+        for (order, reason) in rejected {
+            assert_eq!(
+                exchange.place_order(order, &quotes),
+                OrderStatus::Error(reason.to_string())
+            );
+        }
+
+        let status = exchange.place_order(Order::limit_buy(0_u64, "0.12345", "100.00"), &quotes);
+        assert!(matches!(status, OrderStatus::Resting { .. }));
+    }
+
+    #[test]
+    fn test_that_orders_and_fills_use_coin_name_from_universe() {
+        let mut source = Penelope::new();
+        source.add_quote(101.00, 102.00, 100, "BTC");
+        source.add_quote(102.00, 103.00, 101, "BTC");
+
+        let universe = Universe::new(vec![AssetMeta {
+            max_leverage: 10,
+            ..AssetMeta::new("BTC")
+        }]);
+        let mut exchange = JuraV1::new().with_universe(universe);
+        assert_eq!(exchange.get_leverage(0), Leverage::Cross { value: 10 });
+        assert!(exchange.update_leverage(0, true, 20).is_err());
+
+        exchange.insert_order(Order::market_buy(0_u64, "1.0", "102.00"));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert_eq!(exchange.get_trade_log().len(), 1);
+        assert_eq!(exchange.get_trade_log()[0].coin, "BTC");
+        assert_eq!(exchange.get_universe().get_asset("BTC"), Some(0));
+    }
+
     #[test]
     fn test_that_buy_market_executes_incrementing_trade_log() {
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
-        exchange.tick(source.get_quotes_unchecked(&100));
-        exchange.tick(source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
 
         //TODO: no abstraction!
         assert_eq!(exchange.trade_log.len(), 1);
@@ -702,8 +3105,8 @@ mod tests {
         exchange.insert_order(Order::market_buy(0_u64, "25.0", "102.00"));
         exchange.insert_order(Order::market_buy(0_u64, "25.0", "102.00"));
 
-        exchange.tick(source.get_quotes_unchecked(&100));
-        exchange.tick(source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
         assert_eq!(exchange.trade_log.len(), 4);
     }
 
@@ -712,12 +3115,12 @@ mod tests {
         let (source, mut exchange) = setup();
         exchange.insert_order(Order::market_buy(0_u64, "25.0", "102.00"));
         exchange.insert_order(Order::market_buy(0_u64, "25.0", "102.00"));
-        exchange.tick(source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&100));
 
         exchange.insert_order(Order::market_buy(0_u64, "25.0", "102.00"));
         exchange.insert_order(Order::market_buy(0_u64, "25.0", "102.00"));
-        exchange.tick(source.get_quotes_unchecked(&101));
-        exchange.tick(source.get_quotes_unchecked(&102));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&102));
 
         assert_eq!(exchange.trade_log.len(), 4);
     }
@@ -728,8 +3131,8 @@ mod tests {
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
-        exchange.tick(source.get_quotes_unchecked(&100));
-        exchange.tick(source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
 
         assert_eq!(exchange.trade_log.len(), 1);
         let trade = exchange.trade_log.remove(0);
@@ -744,8 +3147,8 @@ mod tests {
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_sell(0_u64, "100.0", "101.00"));
-        exchange.tick(source.get_quotes_unchecked(&100));
-        exchange.tick(source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
 
         assert_eq!(exchange.trade_log.len(), 1);
         let trade = exchange.trade_log.remove(0);
@@ -759,7 +3162,7 @@ mod tests {
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_buy(99_u64, "100.0", "102.00"));
-        exchange.tick(source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&100));
 
         assert_eq!(exchange.trade_log.len(), 0);
     }
@@ -770,7 +3173,7 @@ mod tests {
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
-        exchange.tick(source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&100));
 
         assert!(exchange.order_buffer.is_empty());
     }
@@ -781,14 +3184,15 @@ mod tests {
         source.add_quote(101.00, 102.00, 100, "0".to_owned());
         source.add_quote(105.00, 106.00, 102, "0".to_owned());
 
-        let mut exchange = JuraV1::new();
+        let mut exchange =
+            JuraV1::new().with_universe(Universe::from_symbols(source.get_symbols()));
 
         exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
-        exchange.tick(source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&100));
         //Orderbook should have one order and trade log has no executed trades
         assert_eq!(exchange.trade_log.len(), 0);
 
-        exchange.tick(source.get_quotes_unchecked(&102));
+        exchange.tick(&source.get_quotes_unchecked(&102));
         //Order should execute now
         assert_eq!(exchange.trade_log.len(), 1);
     }
@@ -800,9 +3204,9 @@ mod tests {
         exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
         exchange.insert_order(Order::market_buy(0_u64, "100.0", "102.00"));
         exchange.insert_order(Order::market_sell(0_u64, "100.0", "102.00"));
-        let res = exchange.tick(source.get_quotes_unchecked(&100));
+        let res = exchange.tick(&source.get_quotes_unchecked(&100));
 
-        assert_eq!(res.1.len(), 3);
-        assert!(!(res.1.first().unwrap().is_buy));
+        assert_eq!(res.inserted_orders.len(), 3);
+        assert!(!(res.inserted_orders.first().unwrap().is_buy));
     }
 }
@@ -3,5 +3,78 @@
 //! to Orderbooks and the logic contained within the Exchange itself primarily relates to the
 //! orchestration of the backtest (for example, ticking forward or synchronizing state with clients
 //! ).
+//!
+//! Operations that every exchange supports are defined in [Exchange], and stepping through a
+//! dataset in [Tick]. An exchange that implements both can be run by
+//! [AppState](crate::http::backtest::AppState) and served with
+//! [configure](crate::http::backtest::configure), so a new exchange only has to add the endpoints
+//! that are specific to it.
+pub mod islay_v1;
 pub mod jura_v1;
 pub mod uist_v1;
+
+use std::collections::HashMap;
+
+use crate::input::{collect_quotes, Input};
+
+/// Operations shared by all exchanges: inserting and deleting orders and setting up a backtest.
+///
+/// Exchanges differ in what they report to clients so these are associated types. Anything
+/// reported on tick other than fills and the orders inserted into the book, for example changes
+/// to the state of orders, is returned in [TickEvents](Exchange::TickEvents) of [TickOutput].
+pub trait Exchange: Clone {
+    type Quote: Clone;
+    type Order;
+    type OrderId;
+    type Fill;
+    /// Returned when an order is inserted. Exchanges that only assign ids once an order reaches
+    /// the book on tick return `()`.
+    type InsertResponse;
+    /// Returned when an order is deleted.
+    type DeleteResponse;
+    type TickEvents: Default;
+
+    /// Returns a copy of this exchange set up to trade the dataset, called when a backtest is
+    /// created.
+    fn for_dataset<I: Input>(&self, dataset: &I) -> Self;
+    fn insert_order(&mut self, order: Self::Order) -> Self::InsertResponse;
+    fn delete_order(&mut self, order_id: Self::OrderId) -> Self::DeleteResponse;
+}
+
+/// Ticking forward through a dataset of type `I`, and the quotes that clients see on each tick.
+///
+/// Exchanges run against any [Input] with quotes that convert into the exchange
+/// [Quote](Exchange::Quote). Exchanges that need more from a dataset than quotes require it
+/// in their implementation of this trait, for example [JuraV1](jura_v1::JuraV1) only runs
+/// against inputs that implement [Funding](crate::input::Funding).
+pub trait Tick<I: Input>: Exchange {
+    /// Quotes seen by clients at date.
+    fn quotes(&self, dataset: &I, date: i64) -> Option<HashMap<String, Self::Quote>>
+    where
+        I::Quote: Into<Self::Quote>,
+    {
+        Some(collect_quotes(dataset.get_quotes(&date)?))
+    }
+    /// Executes orders against the dataset at date.
+    fn tick(&mut self, dataset: &I, date: i64) -> TickOutput<Self>
+    where
+        I::Quote: Into<Self::Quote>;
+}
+
+/// Returned by exchanges on every tick.
+pub struct TickOutput<E: Exchange> {
+    pub fills: Vec<E::Fill>,
+    /// Orders inserted into the book on this tick.
+    pub inserted_orders: Vec<E::Order>,
+    pub events: E::TickEvents,
+}
+
+impl<E: Exchange> Default for TickOutput<E> {
+    fn default() -> Self {
+        Self {
+            fills: Vec::new(),
+            inserted_orders: Vec::new(),
+            events: Default::default(),
+        }
+    }
+}
@@ -1,7 +1,14 @@
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+use std::sync::Arc;
 
-use crate::input::penelope::{PenelopeQuote, PenelopeQuoteByDate};
+use super::{Exchange, Tick, TickOutput};
+use crate::input::ohlcv::OhlcvBar;
+use crate::input::penelope::PenelopeQuote;
+use crate::input::tape::TapeQuote;
+use crate::input::Side;
+use crate::input::{convert_quotes, Input};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct UistQuote {
@@ -9,6 +16,15 @@ pub struct UistQuote {
     pub ask: f64,
     pub date: i64,
     pub symbol: String,
+    #[serde(default)]
+    pub volume: Option<f64>,
+    /// Range traded over the period starting at `date`, only set by bar inputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bar: Option<UistBar>,
+    /// Trades printed on `date`, only set by trade inputs. Inputs that set this always set it,
+    /// with no trades if nothing printed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trades: Option<Vec<UistPrint>>,
 }
 
 impl From<PenelopeQuote> for UistQuote {
@@ -18,19 +34,205 @@ impl From<PenelopeQuote> for UistQuote {
             ask: value.ask,
             date: value.date,
             symbol: value.symbol,
+            volume: value.volume,
+            bar: None,
+            trades: None,
         }
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UistBar {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+/// Bars quote at the close with no spread so that clients value positions at the close.
+impl From<OhlcvBar> for UistQuote {
+    fn from(value: OhlcvBar) -> Self {
+        Self {
+            bid: value.close,
+            ask: value.close,
+            date: value.date,
+            symbol: value.symbol,
+            volume: value.volume,
+            bar: Some(UistBar {
+                open: value.open,
+                high: value.high,
+                low: value.low,
+                close: value.close,
+            }),
+            trades: None,
+        }
+    }
+}
+
+/// Trade printed on a quote in `Trade` execution mode, see [ExecutionMode]. Resting limits only
+/// fill against prints from the opposite `aggressor`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct UistPrint {
+    pub price: f64,
+    pub size: f64,
+    pub aggressor: TradeType,
+}
+
+impl From<TapeQuote> for UistQuote {
+    fn from(value: TapeQuote) -> Self {
+        let trades = value
+            .trades
+            .into_iter()
+            .map(|trade| UistPrint {
+                price: trade.price,
+                size: trade.size,
+                aggressor: match trade.aggressor {
+                    Side::Buy => TradeType::Buy,
+                    Side::Sell => TradeType::Sell,
+                },
+            })
+            .collect();
+        Self {
+            bid: value.bid,
+            ask: value.ask,
+            date: value.date,
+            symbol: value.symbol,
+            volume: value.volume,
+            bar: None,
+            trades: Some(trades),
+        }
+    }
+}
+
+/// Sets how orders are matched against quotes.
+///
+/// In `Quote` mode, orders execute against the bid and ask. Limits execute when the quote crosses
+/// the limit, and stops trigger when the quote moves through the stop price.
+///
+/// In `Bar` mode, orders execute within the range of the bar. Market orders fill at the open.
+/// Limits fill when the range of the bar touches the limit, at the open if the bar opens through
+/// the limit. Stops trigger when the high (for buys) or low (for sells) reaches the stop. They fill
+/// at the stop price, or at the open if the bar gaps through the stop. Quotes without a bar execute
+/// as in `Quote` mode.
+///
+/// In `Trade` mode, limit orders that don't cross the spread fill only when a trade prints through
+/// their price, at the limit price. Only trades from the opposite aggressor count, sells for a
+/// resting buy and buys for a resting sell. Each fill is capped at the volume printed through the
+/// price, which is shared between orders on the same side. Other orders, and quotes without
+/// trades, execute as in `Quote` mode.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum ExecutionMode {
+    #[default]
+    Quote,
+    Bar,
+    Trade,
+}
+
+pub type UistQuoteByDate = HashMap<String, UistQuote>;
+
 pub type OrderId = u64;
 
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+/// Lifecycle of an order. Orders are `Pending` from insertion until the next tick, when they are
+/// either `Accepted` into the book or `Rejected`. Only transitions are reported by
+/// [UistV1::tick], `Pending` is the starting state so is never reported.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub enum OrderState {
+    Pending,
+    Accepted,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Expired,
+    Rejected { reason: String },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OrderUpdate {
+    pub order_id: OrderId,
+    pub state: OrderState,
+}
+
+impl OrderUpdate {
+    fn new(order_id: OrderId, state: OrderState) -> Self {
+        Self { order_id, state }
+    }
+}
+
+/// Models the price impact of trading against a quote. Impact is returned as a positive amount
+/// per share: buys execute at `ask + impact` and sells at `bid - impact`. Models are shared between
+/// copies of an exchange so must be immutable.
+///
+/// The model is set with [UistV1::with_slippage] rather than passed to [UistV1::new], so that
+/// `new` and [Default] still build an exchange without slippage like every other setting.
+pub trait SlippageModel: Debug + Send + Sync {
+    fn impact(&self, quote: &UistQuote, shares: f64, typ: &TradeType) -> f64;
+}
+
+/// Every trade executes at the touch, this is the default.
+#[derive(Clone, Debug, Default)]
+pub struct NoSlippage;
+
+impl SlippageModel for NoSlippage {
+    fn impact(&self, _quote: &UistQuote, _shares: f64, _typ: &TradeType) -> f64 {
+        0.0
+    }
+}
+
+/// Moves the price against the trade by a fixed number of basis points of the touch.
+#[derive(Clone, Debug)]
+pub struct FixedBpsSlippage {
+    pub bps: f64,
+}
+
+impl SlippageModel for FixedBpsSlippage {
+    fn impact(&self, quote: &UistQuote, _shares: f64, typ: &TradeType) -> f64 {
+        let touch = match typ {
+            TradeType::Buy => quote.ask,
+            TradeType::Sell => quote.bid,
+        };
+        touch * self.bps / 10_000.0
+    }
+}
+
+/// Moves the price against the trade by a fraction of the quoted spread.
+#[derive(Clone, Debug)]
+pub struct SpreadSlippage {
+    pub fraction: f64,
+}
+
+impl SlippageModel for SpreadSlippage {
+    fn impact(&self, quote: &UistQuote, _shares: f64, _typ: &TradeType) -> f64 {
+        (quote.ask - quote.bid).max(0.0) * self.fraction
+    }
+}
+
+/// Square-root impact: `coefficient * price * sqrt(shares / volume)`. Quotes without volume
+/// have no impact.
+#[derive(Clone, Debug)]
+pub struct SqrtImpactSlippage {
+    pub coefficient: f64,
+}
+
+impl SlippageModel for SqrtImpactSlippage {
+    fn impact(&self, quote: &UistQuote, shares: f64, typ: &TradeType) -> f64 {
+        let Some(volume) = quote.volume.filter(|volume| *volume > 0.0) else {
+            return 0.0;
+        };
+        let touch = match typ {
+            TradeType::Buy => quote.ask,
+            TradeType::Sell => quote.bid,
+        };
+        self.coefficient * touch * (shares / volume).sqrt()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub enum TradeType {
     Buy,
     Sell,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
 pub enum OrderType {
     MarketSell,
     MarketBuy,
@@ -47,6 +249,16 @@ pub struct Trade {
     pub quantity: f64,
     pub date: i64,
     pub typ: TradeType,
+    /// Effective price per share after slippage.
+    #[serde(default)]
+    pub price: f64,
+    /// Cost of slippage for the whole trade, relative to executing at the touch.
+    #[serde(default)]
+    pub impact: f64,
+    /// Order that created this trade, an order can be filled across more than one trade if
+    /// liquidity is limited.
+    #[serde(default)]
+    pub order_id: Option<OrderId>,
 }
 
 impl Trade {
@@ -63,10 +275,41 @@ impl Trade {
             quantity,
             date,
             typ,
+            price: if quantity == 0.0 {
+                0.0
+            } else {
+                value / quantity
+            },
+            impact: 0.0,
+            order_id: None,
         }
     }
 }
 
+const SECONDS_IN_DAY: i64 = 86_400;
+
+pub(crate) fn is_valid_participation_rate(participation_rate: f64) -> bool {
+    participation_rate > 0.0 && participation_rate <= 1.0
+}
+
+/// How long an order can rest in the book before it expires, expiry is reported by
+/// [UistV1::tick] as [OrderState::Expired].
+///
+/// Orders execute at the earliest on the tick after they are inserted. [TimeInForce::Ioc] and
+/// [TimeInForce::Fok] orders only get that one tick to execute: Ioc cancels any remainder and Fok
+/// cancels the whole order if it can't be filled in full. [TimeInForce::Day] orders expire on the
+/// first tick of a later UTC day than the tick on which they entered the book.
+/// [TimeInForce::Gtd] orders can execute on any tick up to and including the given date.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum TimeInForce {
+    #[default]
+    Gtc,
+    Ioc,
+    Fok,
+    Day,
+    Gtd(i64),
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Order {
     pub order_id: Option<OrderId>,
@@ -74,6 +317,8 @@ pub struct Order {
     pub symbol: String,
     pub shares: f64,
     pub price: Option<f64>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
 }
 
 impl Order {
@@ -92,6 +337,15 @@ impl Order {
         &self.order_type
     }
 
+    pub fn get_time_in_force(&self) -> &TimeInForce {
+        &self.time_in_force
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
     fn set_order_id(&mut self, order_id: u64) {
         self.order_id = Some(order_id);
     }
@@ -103,6 +357,7 @@ impl Order {
             symbol: symbol.into(),
             shares,
             price: None,
+            time_in_force: TimeInForce::Gtc,
         }
     }
 
@@ -113,6 +368,7 @@ impl Order {
             symbol: symbol.into(),
             shares,
             price: Some(price),
+            time_in_force: TimeInForce::Gtc,
         }
     }
 
@@ -157,6 +413,9 @@ pub struct UistV1 {
     trade_log: Vec<Trade>,
     //This is cleared on every tick
     order_buffer: Vec<Order>,
+    //Updates that happen between ticks, i.e. cancellations, are reported on the next tick
+    order_updates: Vec<OrderUpdate>,
+    symbols: Option<HashSet<String>>,
 }
 
 impl UistV1 {
@@ -165,9 +424,49 @@ impl UistV1 {
             orderbook: OrderBook::default(),
             trade_log: Vec::new(),
             order_buffer: Vec::new(),
+            order_updates: Vec::new(),
+            symbols: None,
         }
     }
 
+    /// Sets the symbols that can be traded, orders for any other symbol are rejected. If this is
+    /// not set then orders for symbols without quotes will rest in the book.
+    pub fn with_symbols(mut self, symbols: Vec<String>) -> Self {
+        self.symbols = Some(symbols.into_iter().collect());
+        self
+    }
+
+    /// Sets the model used to calculate the price impact of each trade. Defaults to
+    /// [NoSlippage]. Limit orders never execute through their limit price.
+    pub fn with_slippage(mut self, slippage: impl SlippageModel + 'static) -> Self {
+        self.orderbook.slippage = Arc::new(slippage);
+        self
+    }
+
+    /// Caps the shares filled against each quote at `participation_rate` of the quote volume.
+    /// Buys and sells take liquidity from opposite sides of the book, so each side has its own
+    /// cap. Any remainder rests in the book and is filled on later ticks, so one order can produce
+    /// several trades. Quotes without volume are treated as having unlimited liquidity.
+    ///
+    /// # Panics
+    ///
+    /// If `participation_rate` is not greater than zero and at most one.
+    pub fn with_liquidity(mut self, participation_rate: f64) -> Self {
+        assert!(
+            is_valid_participation_rate(participation_rate),
+            "participation rate must be in (0, 1], got {participation_rate}"
+        );
+        self.orderbook.participation_rate = Some(participation_rate);
+        self
+    }
+
+    /// Sets how orders are matched against quotes, see [ExecutionMode]. Defaults to
+    /// [ExecutionMode::Quote].
+    pub fn with_execution(mut self, execution: ExecutionMode) -> Self {
+        self.orderbook.execution = execution;
+        self
+    }
+
     fn sort_order_buffer(&mut self) {
         self.order_buffer.sort_by(|a, _b| match a.get_order_type() {
             OrderType::LimitSell | OrderType::StopSell | OrderType::MarketSell => {
@@ -177,33 +476,105 @@ impl UistV1 {
         })
     }
 
-    pub fn insert_order(&mut self, order: Order) {
-        // Orders are only inserted into the book when tick is called, this is to ensure proper
-        // ordering of trades
-        // This impacts order_id where an order X can come in before order X+1 but the latter can
-        // have an order_id that is less than the former.
+    /// Returns the id of the order immediately, any id set on the order is overwritten. Orders are
+    /// only inserted into the book when tick is called, this is to ensure proper ordering of
+    /// trades. This means that order_id reflects the order of insertion, not execution priority.
+    pub fn insert_order(&mut self, mut order: Order) -> OrderId {
+        self.orderbook.assign_order_id(&mut order);
+        let order_id = order.order_id.unwrap();
         self.order_buffer.push(order);
+        order_id
+    }
+
+    /// Returns the cancellation if the order was waiting for the next tick or was in the book,
+    /// this is also reported on the next tick. Returns None if the order doesn't exist or has
+    /// already completed.
+    pub fn delete_order(&mut self, order_id: OrderId) -> Option<OrderUpdate> {
+        let buffered = self
+            .order_buffer
+            .iter()
+            .position(|order| order.order_id == Some(order_id));
+        if let Some(position) = buffered {
+            self.order_buffer.remove(position);
+        }
+
+        if buffered.is_some() || self.orderbook.delete_order(order_id) {
+            let update = OrderUpdate::new(order_id, OrderState::Cancelled);
+            self.order_updates.push(update.clone());
+            return Some(update);
+        }
+        None
+    }
+
+    fn validate_order(&self, order: &Order) -> Result<(), String> {
+        if order.shares <= 0.0 || !order.shares.is_finite() {
+            return Err(format!("invalid shares {}", order.shares));
+        }
+        let needs_price = !matches!(
+            order.order_type,
+            OrderType::MarketBuy | OrderType::MarketSell
+        );
+        if needs_price && !order.price.is_some_and(|price| price.is_finite()) {
+            return Err(format!("{:?} order has no price", order.order_type));
+        }
+        if let Some(symbols) = &self.symbols {
+            if !symbols.contains(&order.symbol) {
+                return Err(format!("unknown symbol {}", order.symbol));
+            }
+        }
+        Ok(())
     }
 
-    pub fn delete_order(&mut self, order_id: OrderId) {
-        self.orderbook.delete_order(order_id);
+    /// Quotes can be of any type that converts into [UistQuote].
+    pub fn tick<Q: Clone + Into<UistQuote>>(
+        &mut self,
+        quotes: &HashMap<String, Q>,
+    ) -> TickOutput<Self> {
+        self.execute_tick(&convert_quotes(quotes))
     }
 
-    pub fn tick(&mut self, quotes: &PenelopeQuoteByDate) -> (Vec<Trade>, Vec<Order>) {
+    fn execute_tick(&mut self, quotes: &UistQuoteByDate) -> TickOutput<Self> {
+        let mut order_updates = std::mem::take(&mut self.order_updates);
+        let date = quotes.values().next().map(|quote| quote.date);
+
+        if let Some(date) = date {
+            order_updates.append(&mut self.orderbook.expire_orders(date));
+        }
+
         //To eliminate lookahead bias, we only insert new orders after we have executed any orders
         //that were on the stack first
-        let executed_trades = self.orderbook.execute_orders(quotes);
+        let (executed_trades, mut execution_updates) = self.orderbook.execute_orders(quotes);
         for executed_trade in &executed_trades {
             self.trade_log.push(executed_trade.clone());
         }
+        order_updates.append(&mut execution_updates);
+        order_updates.append(&mut self.orderbook.expire_immediate_orders());
 
         self.sort_order_buffer();
-        for order in self.order_buffer.iter_mut() {
-            self.orderbook.insert_order(order);
+        let inserted_orders = std::mem::take(&mut self.order_buffer);
+        for order in inserted_orders.iter() {
+            match self.validate_order(order) {
+                Ok(()) => {
+                    self.orderbook.insert_order(order, date);
+                    order_updates.push(OrderUpdate::new(
+                        order.order_id.unwrap(),
+                        OrderState::Accepted,
+                    ));
+                }
+                Err(reason) => {
+                    order_updates.push(OrderUpdate::new(
+                        order.order_id.unwrap(),
+                        OrderState::Rejected { reason },
+                    ));
+                }
+            }
         }
 
-        let inserted_orders = std::mem::take(&mut self.order_buffer);
-        (executed_trades, inserted_orders)
+        TickOutput {
+            fills: executed_trades,
+            inserted_orders,
+            events: order_updates,
+        }
     }
 }
 
@@ -213,10 +584,49 @@ impl Default for UistV1 {
     }
 }
 
+impl Exchange for UistV1 {
+    type Quote = UistQuote;
+    type Order = Order;
+    type OrderId = OrderId;
+    type Fill = Trade;
+    type InsertResponse = OrderId;
+    type DeleteResponse = Option<OrderUpdate>;
+    type TickEvents = Vec<OrderUpdate>;
+
+    fn for_dataset<I: Input>(&self, dataset: &I) -> Self {
+        self.clone().with_symbols(dataset.get_symbols().to_vec())
+    }
+
+    fn insert_order(&mut self, order: Order) -> OrderId {
+        UistV1::insert_order(self, order)
+    }
+
+    fn delete_order(&mut self, order_id: OrderId) -> Option<OrderUpdate> {
+        UistV1::delete_order(self, order_id)
+    }
+}
+
+impl<I: Input> Tick<I> for UistV1 {
+    fn tick(&mut self, dataset: &I, date: i64) -> TickOutput<Self>
+    where
+        I::Quote: Into<UistQuote>,
+    {
+        match self.quotes(dataset, date) {
+            Some(quotes) => self.execute_tick(&quotes),
+            None => TickOutput::default(),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct OrderBook {
     inner: VecDeque<Order>,
+    // Last date on which each order with an expiry can execute
+    expiries: HashMap<OrderId, i64>,
     last_inserted: u64,
+    slippage: Arc<dyn SlippageModel>,
+    participation_rate: Option<f64>,
+    execution: ExecutionMode,
 }
 
 impl Default for OrderBook {
@@ -229,11 +639,15 @@ impl OrderBook {
     pub fn new() -> Self {
         Self {
             inner: std::collections::VecDeque::new(),
+            expiries: HashMap::new(),
             last_inserted: 0,
+            slippage: Arc::new(NoSlippage),
+            participation_rate: None,
+            execution: ExecutionMode::Quote,
         }
     }
 
-    pub fn delete_order(&mut self, delete_order_id: u64) {
+    pub fn delete_order(&mut self, delete_order_id: u64) -> bool {
         let mut delete_position: Option<usize> = None;
         for (position, order) in self.inner.iter().enumerate() {
             if let Some(order_id) = order.order_id {
@@ -245,112 +659,291 @@ impl OrderBook {
         }
         if let Some(position) = delete_position {
             self.inner.remove(position);
+            self.expiries.remove(&delete_order_id);
+            return true;
         }
+        false
     }
 
-    pub fn insert_order(&mut self, order: &mut Order) {
+    pub fn assign_order_id(&mut self, order: &mut Order) {
         order.set_order_id(self.last_inserted);
-        self.inner.push_back(order.clone());
         self.last_inserted += 1;
     }
 
+    pub fn insert_order(&mut self, order: &Order, date: Option<i64>) {
+        let order_id = order.order_id.unwrap();
+        match (order.time_in_force, date) {
+            (TimeInForce::Gtd(expiry), _) => {
+                self.expiries.insert(order_id, expiry);
+            }
+            (TimeInForce::Day, Some(date)) => {
+                let end_of_day = (date.div_euclid(SECONDS_IN_DAY) + 1) * SECONDS_IN_DAY - 1;
+                self.expiries.insert(order_id, end_of_day);
+            }
+            _ => {}
+        }
+        self.inner.push_back(order.clone());
+    }
+
+    // Removes orders that can no longer execute on `date`
+    pub fn expire_orders(&mut self, date: i64) -> Vec<OrderUpdate> {
+        let mut expired: Vec<OrderId> = self
+            .expiries
+            .iter()
+            .filter(|(_, expiry)| **expiry < date)
+            .map(|(order_id, _)| *order_id)
+            .collect();
+        expired.sort();
+
+        let mut order_updates = Vec::new();
+        for order_id in expired {
+            self.delete_order(order_id);
+            order_updates.push(OrderUpdate::new(order_id, OrderState::Expired));
+        }
+        order_updates
+    }
+
+    // Every order in the book has had one chance to execute after execute_orders is called
+    pub fn expire_immediate_orders(&mut self) -> Vec<OrderUpdate> {
+        let mut order_updates = Vec::new();
+        self.inner.retain(|order| {
+            if matches!(order.time_in_force, TimeInForce::Ioc | TimeInForce::Fok) {
+                order_updates.push(OrderUpdate::new(
+                    order.order_id.unwrap(),
+                    OrderState::Expired,
+                ));
+                return false;
+            }
+            true
+        });
+        order_updates
+    }
+
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
 
-    fn execute_buy(quote: UistQuote, order: &Order, date: i64) -> Trade {
-        let trade_price = quote.ask;
-        let value = trade_price * order.get_shares();
-        Trade {
-            symbol: order.get_symbol().to_string(),
-            value,
-            quantity: order.get_shares(),
-            date,
-            typ: TradeType::Buy,
+    // Returns the volume printed through the price of a limit order that is resting rather than
+    // crossing the spread, None if the order doesn't execute against trades. Only trades that
+    // hit the side of the book that the order rests on can fill it.
+    fn printed_through(&self, order: &Order, quote: &UistQuote) -> Option<f64> {
+        if self.execution != ExecutionMode::Trade {
+            return None;
+        }
+        let (trades, price) = (quote.trades.as_ref()?, order.price?);
+        let is_buy = match order.order_type {
+            OrderType::LimitBuy if price < quote.ask => true,
+            OrderType::LimitSell if price > quote.bid => false,
+            _ => return None,
+        };
+        Some(
+            trades
+                .iter()
+                .filter(|trade| {
+                    if is_buy {
+                        trade.aggressor == TradeType::Sell && trade.price < price
+                    } else {
+                        trade.aggressor == TradeType::Buy && trade.price > price
+                    }
+                })
+                .map(|trade| trade.size)
+                .sum(),
+        )
+    }
+
+    // Returns the side and the price before slippage if the order executes against this quote
+    fn trigger(&self, order: &Order, quote: &UistQuote) -> Option<(TradeType, f64)> {
+        let order_price = order.price;
+        if let Some(printed) = self.printed_through(order, quote) {
+            let typ = match order.order_type {
+                OrderType::LimitBuy => TradeType::Buy,
+                _ => TradeType::Sell,
+            };
+            return order_price
+                .filter(|_| printed > 0.0)
+                .map(|price| (typ, price));
+        }
+        if let (ExecutionMode::Bar, Some(bar)) = (self.execution, &quote.bar) {
+            // Orders that are already through their price at the open fill at the open
+            return match order.order_type {
+                OrderType::MarketBuy => Some((TradeType::Buy, bar.open)),
+                OrderType::MarketSell => Some((TradeType::Sell, bar.open)),
+                OrderType::LimitBuy => order_price
+                    .filter(|price| *price >= bar.low)
+                    .map(|price| (TradeType::Buy, bar.open.min(price))),
+                OrderType::LimitSell => order_price
+                    .filter(|price| *price <= bar.high)
+                    .map(|price| (TradeType::Sell, bar.open.max(price))),
+                OrderType::StopBuy => order_price
+                    .filter(|price| *price <= bar.high)
+                    .map(|price| (TradeType::Buy, bar.open.max(price))),
+                OrderType::StopSell => order_price
+                    .filter(|price| *price >= bar.low)
+                    .map(|price| (TradeType::Sell, bar.open.min(price))),
+            };
+        }
+
+        match order.order_type {
+            OrderType::MarketBuy => Some((TradeType::Buy, quote.ask)),
+            OrderType::MarketSell => Some((TradeType::Sell, quote.bid)),
+            OrderType::LimitBuy if order_price >= Some(quote.ask) => {
+                Some((TradeType::Buy, quote.ask))
+            }
+            OrderType::LimitSell if order_price <= Some(quote.bid) => {
+                Some((TradeType::Sell, quote.bid))
+            }
+            OrderType::StopBuy if order_price <= Some(quote.ask) => {
+                Some((TradeType::Buy, quote.ask))
+            }
+            OrderType::StopSell if order_price >= Some(quote.bid) => {
+                Some((TradeType::Sell, quote.bid))
+            }
+            _ => None,
         }
     }
 
-    fn execute_sell(quote: UistQuote, order: &Order, date: i64) -> Trade {
-        let trade_price = quote.bid;
-        let value = trade_price * order.get_shares();
+    fn execute(
+        &self,
+        quote: &UistQuote,
+        order: &Order,
+        shares: f64,
+        (typ, touch): (TradeType, f64),
+        date: i64,
+    ) -> Trade {
+        let impact = self.slippage.impact(quote, shares, &typ).max(0.0);
+        let trade_price = match typ {
+            TradeType::Buy => {
+                let price = touch + impact;
+                match order.order_type {
+                    OrderType::LimitBuy => price.min(order.price.unwrap_or(price)),
+                    _ => price,
+                }
+            }
+            TradeType::Sell => {
+                let price = touch - impact;
+                match order.order_type {
+                    OrderType::LimitSell => price.max(order.price.unwrap_or(price)),
+                    _ => price,
+                }
+            }
+        };
         Trade {
             symbol: order.get_symbol().to_string(),
-            value,
-            quantity: order.get_shares(),
+            value: trade_price * shares,
+            quantity: shares,
             date,
-            typ: TradeType::Sell,
+            typ,
+            price: trade_price,
+            impact: (trade_price - touch).abs() * shares,
+            order_id: order.order_id,
         }
     }
 
-    pub fn execute_orders(&mut self, quotes: &PenelopeQuoteByDate) -> Vec<Trade> {
+    // Returns the number of shares that can be filled against this quote. Orders earlier in the
+    // queue take liquidity first, so `used_volume` tracks what has been taken from each side of
+    // the book on this tick.
+    fn fillable_shares(
+        &self,
+        order: &Order,
+        quote: &UistQuote,
+        typ: &TradeType,
+        used_volume: &HashMap<(String, TradeType), f64>,
+    ) -> f64 {
+        let (Some(participation_rate), Some(volume)) = (self.participation_rate, quote.volume)
+        else {
+            return order.get_shares();
+        };
+
+        let used = used_volume
+            .get(&(quote.symbol.clone(), typ.clone()))
+            .unwrap_or(&0.0);
+        let available = (volume * participation_rate - used).max(0.0);
+        order.get_shares().min(available)
+    }
+
+    pub fn execute_orders(&mut self, quotes: &UistQuoteByDate) -> (Vec<Trade>, Vec<OrderUpdate>) {
         let mut completed_orderids = Vec::new();
         let mut trade_results = Vec::new();
+        let mut order_updates = Vec::new();
         if self.is_empty() {
-            return trade_results;
+            return (trade_results, order_updates);
         }
-        for order in self.inner.iter() {
+        let mut used_volume = HashMap::new();
+        let mut used_prints: HashMap<(String, OrderType), f64> = HashMap::new();
+        for pos in 0..self.inner.len() {
+            let order = &self.inner[pos];
             let security_id = &order.symbol;
             if let Some(quote) = quotes.get(security_id) {
-                let quote_copy: UistQuote = quote.clone().into();
+                let quote_copy = quote.clone();
                 let date = quote_copy.date;
-                let result = match order.order_type {
-                    OrderType::MarketBuy => Some(Self::execute_buy(quote_copy, order, date)),
-                    OrderType::MarketSell => Some(Self::execute_sell(quote_copy, order, date)),
-                    OrderType::LimitBuy => {
-                        //Unwrap is safe because LimitBuy will always have a price
-                        let order_price = order.price;
-                        if order_price >= Some(quote_copy.ask) {
-                            Some(Self::execute_buy(quote_copy, order, date))
-                        } else {
-                            None
-                        }
-                    }
-                    OrderType::LimitSell => {
-                        //Unwrap is safe because LimitSell will always have a price
-                        let order_price = order.price;
-                        if order_price <= Some(quote_copy.bid) {
-                            Some(Self::execute_sell(quote_copy, order, date))
-                        } else {
-                            None
-                        }
-                    }
-                    OrderType::StopBuy => {
-                        //Unwrap is safe because StopBuy will always have a price
-                        let order_price = order.price;
-                        if order_price <= Some(quote_copy.ask) {
-                            Some(Self::execute_buy(quote_copy, order, date))
-                        } else {
-                            None
-                        }
-                    }
-                    OrderType::StopSell => {
-                        //Unwrap is safe because StopSell will always have a price
-                        let order_price = order.price;
-                        if order_price >= Some(quote_copy.bid) {
-                            Some(Self::execute_sell(quote_copy, order, date))
-                        } else {
-                            None
-                        }
-                    }
+                let Some(trigger) = self.trigger(order, &quote_copy) else {
+                    continue;
                 };
-                if let Some(trade) = &result {
-                    completed_orderids.push(order.order_id.unwrap());
-                    trade_results.push(trade.clone());
+                let mut shares = self.fillable_shares(order, &quote_copy, &trigger.0, &used_volume);
+                let printed = self.printed_through(order, &quote_copy);
+                if let Some(printed) = printed {
+                    let used = used_prints
+                        .get(&(quote_copy.symbol.clone(), order.order_type))
+                        .unwrap_or(&0.0);
+                    shares = shares.min((printed - used).max(0.0));
+                }
+                if shares <= 0.0 {
+                    continue;
+                }
+                // Fok orders that can't fill in full are expired after execution
+                if order.time_in_force == TimeInForce::Fok && shares < order.get_shares() {
+                    continue;
+                }
+                *used_volume
+                    .entry((quote_copy.symbol.clone(), trigger.0.clone()))
+                    .or_default() += shares;
+                if printed.is_some() {
+                    *used_prints
+                        .entry((quote_copy.symbol.clone(), order.order_type))
+                        .or_default() += shares;
+                }
+
+                let trade = self.execute(&quote_copy, order, shares, trigger, date);
+                trade_results.push(trade);
+
+                let order_id = order.order_id.unwrap();
+                if shares >= order.get_shares() {
+                    completed_orderids.push(order_id);
+                    order_updates.push(OrderUpdate::new(order_id, OrderState::Filled));
+                } else {
+                    order_updates.push(OrderUpdate::new(order_id, OrderState::PartiallyFilled));
+                    // The remainder rests in the book, a stop that has triggered rests as a market
+                    // order so that it doesn't have to trigger again
+                    let order = &mut self.inner[pos];
+                    order.shares -= shares;
+                    order.order_type = match order.order_type {
+                        OrderType::StopBuy => OrderType::MarketBuy,
+                        OrderType::StopSell => OrderType::MarketSell,
+                        order_type => order_type,
+                    };
                 }
             }
         }
         for order_id in completed_orderids {
             self.delete_order(order_id);
         }
-        trade_results
+        (trade_results, order_updates)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::UistV1;
-    use crate::exchange::uist_v1::OrderType;
+    use super::{
+        ExecutionMode, FixedBpsSlippage, OrderState, OrderUpdate, SpreadSlippage,
+        SqrtImpactSlippage, Trade, TradeType, UistV1,
+    };
+    use crate::exchange::uist_v1::{OrderType, TimeInForce};
+    use crate::exchange::TickOutput;
+    use crate::input::ohlcv::Ohlcv;
     use crate::input::penelope::Penelope;
+    use crate::input::tape::Tape;
+    use crate::input::Side;
+    use crate::source::TradePrint;
 
     use super::Order;
 
@@ -369,8 +962,8 @@ mod tests {
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_buy("ABC", 100.0));
-        exchange.tick(source.get_quotes_unchecked(&100));
-        exchange.tick(source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
 
         //TODO: no abstraction!
         assert_eq!(exchange.trade_log.len(), 1);
@@ -385,8 +978,8 @@ mod tests {
         exchange.insert_order(Order::market_buy("ABC", 25.0));
         exchange.insert_order(Order::market_buy("ABC", 25.0));
 
-        exchange.tick(source.get_quotes_unchecked(&100));
-        exchange.tick(source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
         assert_eq!(exchange.trade_log.len(), 4);
     }
 
@@ -395,12 +988,12 @@ mod tests {
         let (source, mut exchange) = setup();
         exchange.insert_order(Order::market_buy("ABC", 25.0));
         exchange.insert_order(Order::market_buy("ABC", 25.0));
-        exchange.tick(source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&100));
 
         exchange.insert_order(Order::market_buy("ABC", 25.0));
         exchange.insert_order(Order::market_buy("ABC", 25.0));
-        exchange.tick(source.get_quotes_unchecked(&101));
-        exchange.tick(source.get_quotes_unchecked(&102));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&102));
 
         assert_eq!(exchange.trade_log.len(), 4);
     }
@@ -411,8 +1004,8 @@ mod tests {
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_buy("ABC", 100.0));
-        exchange.tick(source.get_quotes_unchecked(&100));
-        exchange.tick(source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
 
         assert_eq!(exchange.trade_log.len(), 1);
         let trade = exchange.trade_log.remove(0);
@@ -421,14 +1014,189 @@ mod tests {
         assert_eq!(trade.date, 101);
     }
 
+    #[test]
+    fn test_that_slippage_moves_price_against_order() {
+        let (source, _) = setup();
+        let mut exchange = UistV1::new().with_slippage(FixedBpsSlippage { bps: 100.0 });
+
+        exchange.insert_order(Order::market_buy("ABC", 100.0));
+        exchange.insert_order(Order::market_sell("ABC", 100.0));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert_eq!(exchange.trade_log.len(), 2);
+        let sell = exchange.trade_log.remove(0);
+        assert!((sell.price - 100.98).abs() < 1e-9);
+        assert_eq!(sell.value, sell.price * 100.0);
+        assert!((sell.impact - 102.0).abs() < 1e-9);
+        let buy = exchange.trade_log.remove(0);
+        assert!((buy.price - 104.03).abs() < 1e-9);
+        assert!((buy.impact - 103.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_that_spread_slippage_is_proportional_to_spread() {
+        let (source, _) = setup();
+        let mut exchange = UistV1::new().with_slippage(SpreadSlippage { fraction: 0.5 });
+
+        exchange.insert_order(Order::market_buy("ABC", 100.0));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let trades = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+
+        assert_eq!(trades[0].price, 103.50);
+        assert_eq!(trades[0].impact, 50.0);
+    }
+
+    #[test]
+    fn test_that_sqrt_impact_grows_with_size_relative_to_volume() {
+        let mut source = Penelope::new();
+        source.add_quote_with_volume(101.00, 102.00, 10_000.0, 100, "ABC");
+        source.add_quote_with_volume(99.00, 100.00, 10_000.0, 101, "ABC");
+        let mut exchange = UistV1::new().with_slippage(SqrtImpactSlippage { coefficient: 0.1 });
+
+        exchange.insert_order(Order::market_buy("ABC", 100.0));
+        exchange.insert_order(Order::market_buy("ABC", 400.0));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let trades = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+
+        //0.1 * 100 * sqrt(100 / 10_000) = 1.0
+        assert_eq!(trades[0].price, 101.0);
+        //0.1 * 100 * sqrt(400 / 10_000) = 2.0
+        assert_eq!(trades[1].price, 102.0);
+    }
+
+    #[test]
+    fn test_that_slippage_does_not_move_limit_through_price() {
+        let (source, _) = setup();
+        let mut exchange = UistV1::new().with_slippage(FixedBpsSlippage { bps: 100.0 });
+
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 103.50));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let trades = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+
+        assert_eq!(trades[0].price, 103.50);
+        assert_eq!(trades[0].impact, 50.0);
+    }
+
+    #[test]
+    fn test_that_trade_without_quantity_has_zero_price() {
+        let trade = Trade::new("ABC", 0.0, 0.0, 100, TradeType::Buy);
+        assert_eq!(trade.price, 0.0);
+    }
+
+    #[test]
+    fn test_that_fills_are_capped_at_participation_rate() {
+        let mut source = Penelope::new();
+        source.add_quote_with_volume(101.00, 102.00, 1000.0, 100, "ABC");
+        source.add_quote_with_volume(102.00, 103.00, 1000.0, 101, "ABC");
+        source.add_quote_with_volume(103.00, 104.00, 1000.0, 102, "ABC");
+        source.add_quote_with_volume(104.00, 105.00, 1000.0, 103, "ABC");
+        let mut exchange = UistV1::new().with_liquidity(0.1);
+
+        exchange.insert_order(Order::market_buy("ABC", 250.0));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let trades = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 100.0);
+        assert_eq!(trades[0].value, 103.00 * 100.0);
+
+        let trades = exchange.tick(&source.get_quotes_unchecked(&102)).fills;
+        assert_eq!(trades[0].quantity, 100.0);
+        let trades = exchange.tick(&source.get_quotes_unchecked(&103)).fills;
+        assert_eq!(trades[0].quantity, 50.0);
+
+        assert_eq!(exchange.trade_log.len(), 3);
+        assert!(exchange
+            .trade_log
+            .iter()
+            .all(|trade| trade.order_id == Some(0)));
+        assert!(exchange.orderbook.is_empty());
+    }
+
+    #[test]
+    fn test_that_orders_share_liquidity_in_queue_order() {
+        let mut source = Penelope::new();
+        source.add_quote_with_volume(101.00, 102.00, 1000.0, 100, "ABC");
+        source.add_quote_with_volume(102.00, 103.00, 1000.0, 101, "ABC");
+        let mut exchange = UistV1::new().with_liquidity(0.1);
+
+        exchange.insert_order(Order::market_buy("ABC", 80.0));
+        exchange.insert_order(Order::market_buy("ABC", 80.0));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let trades = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].quantity, 80.0);
+        assert_eq!(trades[1].quantity, 20.0);
+    }
+
+    #[test]
+    fn test_that_buys_and_sells_take_liquidity_from_separate_sides() {
+        let mut source = Penelope::new();
+        source.add_quote_with_volume(101.00, 102.00, 1000.0, 100, "ABC");
+        source.add_quote_with_volume(102.00, 103.00, 1000.0, 101, "ABC");
+        let mut exchange = UistV1::new().with_liquidity(0.1);
+
+        exchange.insert_order(Order::market_buy("ABC", 100.0));
+        exchange.insert_order(Order::market_sell("ABC", 100.0));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let trades = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|trade| trade.quantity == 100.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_that_participation_rate_above_one_panics() {
+        let _ = UistV1::new().with_liquidity(1.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_that_zero_participation_rate_panics() {
+        let _ = UistV1::new().with_liquidity(0.0);
+    }
+
+    #[test]
+    fn test_that_quotes_without_volume_fill_in_full_with_liquidity_model() {
+        let (source, _) = setup();
+        let mut exchange = UistV1::new().with_liquidity(0.1);
+
+        exchange.insert_order(Order::market_buy("ABC", 10_000.0));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let trades = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].quantity, 10_000.0);
+    }
+
+    #[test]
+    fn test_that_partially_filled_stop_rests_as_market() {
+        let mut source = Penelope::new();
+        source.add_quote_with_volume(101.00, 102.00, 1000.0, 100, "ABC");
+        source.add_quote_with_volume(105.00, 106.00, 1000.0, 101, "ABC");
+        source.add_quote_with_volume(101.00, 102.00, 1000.0, 102, "ABC");
+        let mut exchange = UistV1::new().with_liquidity(0.1);
+
+        exchange.insert_order(Order::stop_buy("ABC", 150.0, 104.00));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let trades = exchange.tick(&source.get_quotes_unchecked(&101)).fills;
+        assert_eq!(trades[0].quantity, 100.0);
+
+        //Price falls back below the stop but the remainder still executes
+        let trades = exchange.tick(&source.get_quotes_unchecked(&102)).fills;
+        assert_eq!(trades[0].quantity, 50.0);
+    }
+
     #[test]
     fn test_that_sell_market_executes_on_next_tick() {
         //Verifies that trades do not execute instaneously removing lookahead bias
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_sell("ABC", 100.0));
-        exchange.tick(source.get_quotes_unchecked(&100));
-        exchange.tick(source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
 
         assert_eq!(exchange.trade_log.len(), 1);
         let trade = exchange.trade_log.remove(0);
@@ -438,22 +1206,279 @@ mod tests {
     }
 
     #[test]
-    fn test_that_order_for_nonexistent_stock_fails_silently() {
+    fn test_that_order_for_nonexistent_stock_is_rejected() {
+        let (source, _) = setup();
+        let mut exchange = UistV1::new().with_symbols(vec!["ABC".to_string()]);
+
+        exchange.insert_order(Order::market_buy("XYZ", 100.0));
+        let TickOutput {
+            inserted_orders: inserted,
+            events: updates,
+            ..
+        } = exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert_eq!(exchange.trade_log.len(), 0);
+        assert!(exchange.orderbook.is_empty());
+        assert_eq!(inserted.len(), 1);
+        assert_eq!(
+            updates,
+            vec![OrderUpdate {
+                order_id: 0,
+                state: OrderState::Rejected {
+                    reason: "unknown symbol XYZ".to_string()
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn test_that_order_without_universe_for_nonexistent_stock_rests() {
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_buy("XYZ", 100.0));
-        exchange.tick(source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert_eq!(exchange.trade_log.len(), 0);
+        assert!(!exchange.orderbook.is_empty());
+    }
+
+    #[test]
+    fn test_that_order_id_is_returned_on_insert() {
+        let (source, mut exchange) = setup();
+
+        let first = exchange.insert_order(Order::market_buy("ABC", 100.0));
+        let second = exchange.insert_order(Order::market_sell("ABC", 100.0));
+        assert_eq!(first, 0);
+        assert_eq!(second, 1);
+
+        //Sells are inserted into the book first but keep the id returned on insert
+        let inserted = exchange
+            .tick(&source.get_quotes_unchecked(&100))
+            .inserted_orders;
+        assert_eq!(inserted[0].order_id, Some(second));
+        assert_eq!(inserted[1].order_id, Some(first));
+    }
+
+    #[test]
+    fn test_that_buffered_order_can_be_cancelled_before_tick() {
+        let (source, mut exchange) = setup();
 
+        let order_id = exchange.insert_order(Order::market_buy("ABC", 100.0));
+        assert_eq!(
+            exchange.delete_order(order_id),
+            Some(OrderUpdate::new(order_id, OrderState::Cancelled))
+        );
+
+        let TickOutput {
+            inserted_orders: inserted,
+            events: updates,
+            ..
+        } = exchange.tick(&source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&101));
+
+        assert!(inserted.is_empty());
+        assert_eq!(
+            updates,
+            vec![OrderUpdate::new(order_id, OrderState::Cancelled)]
+        );
         assert_eq!(exchange.trade_log.len(), 0);
     }
 
+    #[test]
+    fn test_that_ioc_order_cancels_remainder_after_one_tick() {
+        let mut source = Penelope::new();
+        source.add_quote_with_volume(101.00, 102.00, 1000.0, 100, "ABC");
+        source.add_quote_with_volume(102.00, 103.00, 1000.0, 101, "ABC");
+        source.add_quote_with_volume(103.00, 104.00, 1000.0, 102, "ABC");
+        let mut exchange = UistV1::new().with_liquidity(0.1);
+
+        let order_id = exchange
+            .insert_order(Order::market_buy("ABC", 150.0).with_time_in_force(TimeInForce::Ioc));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let TickOutput {
+            fills: trades,
+            events: updates,
+            ..
+        } = exchange.tick(&source.get_quotes_unchecked(&101));
+        exchange.tick(&source.get_quotes_unchecked(&102));
+
+        assert_eq!(trades[0].quantity, 100.0);
+        assert_eq!(exchange.trade_log.len(), 1);
+        assert_eq!(
+            updates,
+            vec![
+                OrderUpdate::new(order_id, OrderState::PartiallyFilled),
+                OrderUpdate::new(order_id, OrderState::Expired)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_that_ioc_limit_that_does_not_cross_expires() {
+        let (source, mut exchange) = setup();
+
+        let order_id = exchange.insert_order(
+            Order::limit_buy("ABC", 100.0, 90.0).with_time_in_force(TimeInForce::Ioc),
+        );
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let updates = exchange.tick(&source.get_quotes_unchecked(&101)).events;
+
+        assert_eq!(
+            updates,
+            vec![OrderUpdate::new(order_id, OrderState::Expired)]
+        );
+        assert!(exchange.orderbook.is_empty());
+    }
+
+    #[test]
+    fn test_that_fok_order_fills_in_full_or_not_at_all() {
+        let mut source = Penelope::new();
+        source.add_quote_with_volume(101.00, 102.00, 1000.0, 100, "ABC");
+        source.add_quote_with_volume(102.00, 103.00, 1000.0, 101, "ABC");
+        let mut exchange = UistV1::new().with_liquidity(0.1);
+
+        let too_big = exchange
+            .insert_order(Order::market_buy("ABC", 150.0).with_time_in_force(TimeInForce::Fok));
+        let fits = exchange
+            .insert_order(Order::market_buy("ABC", 80.0).with_time_in_force(TimeInForce::Fok));
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let TickOutput {
+            fills: trades,
+            events: updates,
+            ..
+        } = exchange.tick(&source.get_quotes_unchecked(&101));
+
+        //The first order doesn't take any liquidity from the second
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].order_id, Some(fits));
+        assert_eq!(trades[0].quantity, 80.0);
+        assert_eq!(
+            updates,
+            vec![
+                OrderUpdate::new(fits, OrderState::Filled),
+                OrderUpdate::new(too_big, OrderState::Expired)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_that_day_order_expires_on_next_day() {
+        let mut source = Penelope::new();
+        source.add_quote(101.00, 102.00, 86_400, "ABC");
+        source.add_quote(101.00, 102.00, 86_500, "ABC");
+        source.add_quote(101.00, 102.00, 172_800, "ABC");
+        let mut exchange = UistV1::new();
+
+        let order_id = exchange.insert_order(
+            Order::limit_buy("ABC", 100.0, 90.0).with_time_in_force(TimeInForce::Day),
+        );
+        exchange.tick(&source.get_quotes_unchecked(&86_400));
+        let updates = exchange.tick(&source.get_quotes_unchecked(&86_500)).events;
+        assert!(updates.is_empty());
+
+        let updates = exchange.tick(&source.get_quotes_unchecked(&172_800)).events;
+        assert_eq!(
+            updates,
+            vec![OrderUpdate::new(order_id, OrderState::Expired)]
+        );
+        assert!(exchange.orderbook.is_empty());
+    }
+
+    #[test]
+    fn test_that_gtd_order_can_execute_until_date() {
+        let (source, mut exchange) = setup();
+
+        let expires = exchange.insert_order(
+            Order::limit_buy("ABC", 100.0, 90.0).with_time_in_force(TimeInForce::Gtd(101)),
+        );
+        let executes = exchange.insert_order(
+            Order::limit_buy("ABC", 100.0, 106.0).with_time_in_force(TimeInForce::Gtd(102)),
+        );
+        exchange.tick(&source.get_quotes_unchecked(&100));
+        let updates = exchange.tick(&source.get_quotes_unchecked(&101)).events;
+        assert_eq!(
+            updates,
+            vec![OrderUpdate::new(executes, OrderState::Filled)]
+        );
+
+        let updates = exchange.tick(&source.get_quotes_unchecked(&102)).events;
+        assert_eq!(
+            updates,
+            vec![OrderUpdate::new(expires, OrderState::Expired)]
+        );
+    }
+
+    #[test]
+    fn test_that_invalid_orders_are_rejected() {
+        let (source, mut exchange) = setup();
+
+        exchange.insert_order(Order::market_buy("ABC", -100.0));
+        exchange.insert_order(Order {
+            order_id: None,
+            order_type: OrderType::LimitBuy,
+            symbol: "ABC".to_string(),
+            shares: 100.0,
+            price: None,
+            time_in_force: TimeInForce::Gtc,
+        });
+        let updates = exchange.tick(&source.get_quotes_unchecked(&100)).events;
+
+        assert_eq!(updates.len(), 2);
+        assert!(updates
+            .iter()
+            .all(|update| matches!(update.state, OrderState::Rejected { .. })));
+    }
+
+    #[test]
+    fn test_that_tick_reports_order_lifecycle() {
+        let mut source = Penelope::new();
+        source.add_quote_with_volume(101.00, 102.00, 1000.0, 100, "ABC");
+        source.add_quote_with_volume(102.00, 103.00, 1000.0, 101, "ABC");
+        source.add_quote_with_volume(103.00, 104.00, 1000.0, 102, "ABC");
+        let mut exchange = UistV1::new().with_liquidity(0.1);
+
+        exchange.insert_order(Order::market_buy("ABC", 150.0));
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 50.0));
+        let updates = exchange.tick(&source.get_quotes_unchecked(&100)).events;
+        assert_eq!(
+            updates,
+            vec![
+                OrderUpdate::new(0, OrderState::Accepted),
+                OrderUpdate::new(1, OrderState::Accepted)
+            ]
+        );
+
+        let updates = exchange.tick(&source.get_quotes_unchecked(&101)).events;
+        assert_eq!(
+            updates,
+            vec![OrderUpdate::new(0, OrderState::PartiallyFilled)]
+        );
+
+        assert_eq!(
+            exchange.delete_order(1),
+            Some(OrderUpdate::new(1, OrderState::Cancelled))
+        );
+        assert_eq!(exchange.delete_order(1), None);
+
+        let updates = exchange.tick(&source.get_quotes_unchecked(&102)).events;
+        assert_eq!(
+            updates,
+            vec![
+                OrderUpdate::new(1, OrderState::Cancelled),
+                OrderUpdate::new(0, OrderState::Filled)
+            ]
+        );
+    }
+
     #[test]
     fn test_that_order_buffer_clears() {
         //Sounds redundant but accidentally removing the clear could cause unusual errors elsewhere
         let (source, mut exchange) = setup();
 
         exchange.insert_order(Order::market_buy("ABC", 100.0));
-        exchange.tick(source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&100));
 
         assert!(exchange.order_buffer.is_empty());
     }
@@ -466,11 +1491,11 @@ mod tests {
         let mut exchange = UistV1::new();
 
         exchange.insert_order(Order::market_buy("ABC", 100.0));
-        exchange.tick(source.get_quotes_unchecked(&100));
+        exchange.tick(&source.get_quotes_unchecked(&100));
         //Orderbook should have one order and trade log has no executed trades
         assert_eq!(exchange.trade_log.len(), 0);
 
-        exchange.tick(source.get_quotes_unchecked(&102));
+        exchange.tick(&source.get_quotes_unchecked(&102));
         //Order should execute now
         assert_eq!(exchange.trade_log.len(), 1);
     }
@@ -482,12 +1507,188 @@ mod tests {
         exchange.insert_order(Order::market_buy("ABC", 100.0));
         exchange.insert_order(Order::market_buy("ABC", 100.0));
         exchange.insert_order(Order::market_sell("ABC", 100.0));
-        let res = exchange.tick(source.get_quotes_unchecked(&100));
+        let res = exchange.tick(&source.get_quotes_unchecked(&100));
 
-        assert_eq!(res.1.len(), 3);
+        assert_eq!(res.inserted_orders.len(), 3);
         assert_eq!(
-            res.1.first().unwrap().clone().order_type,
+            res.inserted_orders.first().unwrap().clone().order_type,
             OrderType::MarketSell
         )
     }
+
+    fn setup_bars() -> (Ohlcv, UistV1) {
+        let mut source = Ohlcv::new();
+        source
+            .add_bar(100.0, 101.0, 99.0, 100.0, None, 100, "ABC")
+            .unwrap();
+        source
+            .add_bar(101.0, 104.0, 96.0, 102.0, None, 101, "ABC")
+            .unwrap();
+        //Gaps up through 105
+        source
+            .add_bar(107.0, 108.0, 93.0, 94.0, None, 102, "ABC")
+            .unwrap();
+
+        let exchange = UistV1::new().with_execution(ExecutionMode::Bar);
+        (source, exchange)
+    }
+
+    #[test]
+    fn test_that_market_order_fills_at_next_open_in_bar_mode() {
+        let (source, mut exchange) = setup_bars();
+
+        exchange.insert_order(Order::market_buy("ABC", 100.0));
+        exchange.insert_order(Order::market_sell("ABC", 50.0));
+        exchange.tick(source.get_bars(&100).unwrap());
+        exchange.tick(source.get_bars(&101).unwrap());
+
+        assert_eq!(exchange.trade_log.len(), 2);
+        assert!(exchange.trade_log.iter().all(|trade| trade.price == 101.0));
+        assert!(exchange.trade_log.iter().all(|trade| trade.date == 101));
+    }
+
+    #[test]
+    fn test_that_limit_fills_when_bar_range_touches_price() {
+        let (source, mut exchange) = setup_bars();
+
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 97.0));
+        exchange.insert_order(Order::limit_sell("ABC", 100.0, 103.0));
+        //Range of the bar on 101 is 96 to 104 so neither of these are touched until 102
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 95.0));
+        exchange.insert_order(Order::limit_sell("ABC", 100.0, 106.0));
+        exchange.tick(source.get_bars(&100).unwrap());
+        exchange.tick(source.get_bars(&101).unwrap());
+
+        //Limits inside the range fill at the limit, not the close
+        assert_eq!(exchange.trade_log.len(), 2);
+        let mut prices: Vec<f64> = exchange.trade_log.iter().map(|t| t.price).collect();
+        prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(prices, vec![97.0, 103.0]);
+
+        //Bar on 102 opens through the sell limit so it fills at the better open
+        exchange.tick(source.get_bars(&102).unwrap());
+        assert_eq!(exchange.trade_log.len(), 4);
+        let sell = exchange
+            .trade_log
+            .iter()
+            .find(|trade| trade.date == 102 && trade.typ == super::TradeType::Sell)
+            .unwrap();
+        assert_eq!(sell.price, 107.0);
+        let buy = exchange
+            .trade_log
+            .iter()
+            .find(|trade| trade.date == 102 && trade.typ == super::TradeType::Buy)
+            .unwrap();
+        assert_eq!(buy.price, 95.0);
+    }
+
+    #[test]
+    fn test_that_stop_fills_at_open_when_bar_gaps_through_price() {
+        let (source, mut exchange) = setup_bars();
+
+        exchange.insert_order(Order::stop_buy("ABC", 100.0, 103.0));
+        exchange.insert_order(Order::stop_sell("ABC", 100.0, 97.0));
+        exchange.insert_order(Order::stop_buy("ABC", 100.0, 105.0));
+        exchange.insert_order(Order::stop_sell("ABC", 100.0, 95.0));
+        exchange.tick(source.get_bars(&100).unwrap());
+        exchange.tick(source.get_bars(&101).unwrap());
+
+        //Stops that trigger within the range fill at the stop price
+        assert_eq!(exchange.trade_log.len(), 2);
+        let mut prices: Vec<f64> = exchange.trade_log.iter().map(|t| t.price).collect();
+        prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(prices, vec![97.0, 103.0]);
+
+        //Bar gaps through the buy stop so it fills at the open, which is worse than the stop
+        exchange.tick(source.get_bars(&102).unwrap());
+        assert_eq!(exchange.trade_log.len(), 4);
+        let buy = exchange
+            .trade_log
+            .iter()
+            .find(|trade| trade.date == 102 && trade.typ == super::TradeType::Buy)
+            .unwrap();
+        assert_eq!(buy.price, 107.0);
+        let sell = exchange
+            .trade_log
+            .iter()
+            .find(|trade| trade.date == 102 && trade.typ == super::TradeType::Sell)
+            .unwrap();
+        assert_eq!(sell.price, 95.0);
+    }
+
+    fn setup_tape(prints: Vec<(i64, f64, f64, Side)>) -> (Tape, UistV1) {
+        let mut quotes = Penelope::new();
+        quotes.add_quote(100.00, 101.00, 100, "ABC");
+        quotes.add_quote(100.00, 101.00, 103, "ABC");
+        let trades = prints
+            .into_iter()
+            .map(|(date, price, size, aggressor)| TradePrint {
+                symbol: "ABC".to_string(),
+                date,
+                price,
+                size,
+                aggressor,
+            })
+            .collect();
+
+        let exchange = UistV1::new().with_execution(ExecutionMode::Trade);
+        (Tape::new(&quotes, trades).unwrap(), exchange)
+    }
+
+    #[test]
+    fn test_that_resting_limit_fills_only_when_trade_prints_through_price() {
+        let (source, mut exchange) = setup_tape(vec![
+            (101, 100.0, 500.0, Side::Sell),
+            (102, 99.5, 30.0, Side::Sell),
+        ]);
+
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 100.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+
+        //Bid touches the limit and trades print at the limit but not through it
+        exchange.tick(source.get_quotes(&101).unwrap());
+        assert!(exchange.trade_log.is_empty());
+
+        //Fill is capped at the volume printed through the limit
+        let updates = exchange.tick(source.get_quotes(&102).unwrap()).events;
+        assert_eq!(exchange.trade_log.len(), 1);
+        assert_eq!(exchange.trade_log[0].price, 100.0);
+        assert_eq!(exchange.trade_log[0].quantity, 30.0);
+        assert_eq!(updates[0].state, OrderState::PartiallyFilled);
+
+        //No trades print on 103 so the remainder keeps resting
+        exchange.tick(source.get_quotes(&103).unwrap());
+        assert_eq!(exchange.trade_log.len(), 1);
+    }
+
+    #[test]
+    fn test_that_buy_aggressor_printing_through_resting_bid_does_not_fill() {
+        let (source, mut exchange) = setup_tape(vec![
+            (101, 99.5, 30.0, Side::Buy),
+            (102, 99.5, 30.0, Side::Sell),
+        ]);
+
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 100.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+
+        //Buyers lifting offers below the bid did not trade against resting bids
+        exchange.tick(source.get_quotes(&101).unwrap());
+        assert!(exchange.trade_log.is_empty());
+
+        exchange.tick(source.get_quotes(&102).unwrap());
+        assert_eq!(exchange.trade_log.len(), 1);
+        assert_eq!(exchange.trade_log[0].quantity, 30.0);
+    }
+
+    #[test]
+    fn test_that_marketable_limit_executes_against_quote_in_trade_mode() {
+        let (source, mut exchange) = setup_tape(Vec::new());
+
+        exchange.insert_order(Order::limit_buy("ABC", 100.0, 101.0));
+        exchange.tick(source.get_quotes(&100).unwrap());
+        exchange.tick(source.get_quotes(&103).unwrap());
+
+        assert_eq!(exchange.trade_log.len(), 1);
+        assert_eq!(exchange.trade_log[0].price, 101.0);
+    }
 }
@@ -0,0 +1,466 @@
+//! Backtest state and endpoints that are shared by every [Exchange].
+//!
+//! [AppState] holds the datasets served and the backtests created against them, each backtest
+//! has its own copy of the exchange. Datasets can be any [Input] with quotes that convert into
+//! the quotes of the exchange, and default to [Penelope].
+//!
+//! Exchange servers are built by calling [configure], which registers the endpoints that are the
+//! same for every exchange (init, fetch_quotes, info, now), and then adding the endpoints that
+//! are specific to the exchange (tick, order operations).
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use actix_web::{error, guard, web};
+use derive_more::{Display, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::exchange::{Exchange, Tick, TickOutput};
+use crate::input::penelope::Penelope;
+use crate::input::Input;
+
+pub type BacktestId = u64;
+
+/// Returned by [AppState::tick], the [TickOutput] of the exchange and whether there is another
+/// tick.
+pub struct TickResult<E: Exchange> {
+    pub has_next: bool,
+    pub fills: Vec<E::Fill>,
+    pub inserted_orders: Vec<E::Order>,
+    pub events: E::TickEvents,
+}
+
+pub struct BacktestState<E> {
+    pub id: BacktestId,
+    pub date: i64,
+    pub pos: usize,
+    pub exchange: E,
+    pub dataset_name: String,
+}
+
+pub struct AppState<E, I = Penelope> {
+    pub backtests: HashMap<BacktestId, BacktestState<E>>,
+    pub last: BacktestId,
+    pub datasets: HashMap<String, I>,
+    /// New backtests start with a copy of this exchange, see
+    /// [with_exchange](AppState::with_exchange).
+    pub exchange: E,
+    /// Exchanges used instead of `exchange` for backtests on a dataset, see
+    /// [with_dataset_exchange](AppState::with_dataset_exchange).
+    pub dataset_exchanges: HashMap<String, E>,
+}
+
+impl<E: Exchange + Default, I: Input> AppState<E, I> {
+    pub fn create(datasets: &mut HashMap<String, I>) -> Self {
+        Self {
+            backtests: HashMap::new(),
+            last: 0,
+            datasets: std::mem::take(datasets),
+            exchange: E::default(),
+            dataset_exchanges: HashMap::new(),
+        }
+    }
+
+    /// Creates a backtest with id 0 on the dataset, unless the dataset is empty.
+    pub fn single(name: &str, data: I) -> Self {
+        let mut backtests = HashMap::new();
+        if let Some(date) = data.get_date(0) {
+            let backtest = BacktestState {
+                id: 0,
+                date: *date,
+                pos: 0,
+                exchange: E::default().for_dataset(&data),
+                dataset_name: name.into(),
+            };
+            backtests.insert(0, backtest);
+        }
+
+        let mut datasets = HashMap::new();
+        datasets.insert(name.into(), data);
+
+        Self {
+            backtests,
+            last: 1,
+            datasets,
+            exchange: E::default(),
+            dataset_exchanges: HashMap::new(),
+        }
+    }
+}
+
+impl<E, I> AppState<E, I>
+where
+    E: Tick<I>,
+    I: Input,
+    I::Quote: Into<E::Quote>,
+{
+    /// Sets the exchange, and its settings, that new backtests are created with. Does not change
+    /// backtests that have already been created.
+    pub fn with_exchange(mut self, exchange: E) -> Self {
+        self.exchange = exchange;
+        self
+    }
+
+    /// Sets the exchange that new backtests on a dataset are created with, overriding the
+    /// exchange set with [with_exchange](AppState::with_exchange) for that dataset.
+    pub fn with_dataset_exchange(mut self, dataset_name: &str, exchange: E) -> Self {
+        self.dataset_exchanges.insert(dataset_name.into(), exchange);
+        self
+    }
+
+    fn new_exchange(&self, dataset_name: &str, dataset: &I) -> E {
+        self.dataset_exchanges
+            .get(dataset_name)
+            .unwrap_or(&self.exchange)
+            .for_dataset(dataset)
+    }
+
+    pub fn tick(&mut self, backtest_id: BacktestId) -> Option<TickResult<E>> {
+        let backtest = self.backtests.get_mut(&backtest_id)?;
+        let dataset = self.datasets.get(&backtest.dataset_name)?;
+
+        let TickOutput {
+            fills,
+            inserted_orders,
+            events,
+        } = backtest.exchange.tick(dataset, backtest.date);
+
+        let mut has_next = false;
+        let new_pos = backtest.pos + 1;
+        if dataset.has_next(new_pos) {
+            has_next = true;
+            backtest.date = *dataset.get_date(new_pos).unwrap();
+        }
+        backtest.pos = new_pos;
+        Some(TickResult {
+            has_next,
+            fills,
+            inserted_orders,
+            events,
+        })
+    }
+
+    pub fn fetch_quotes(&self, backtest_id: BacktestId) -> Option<HashMap<String, E::Quote>> {
+        let backtest = self.backtests.get(&backtest_id)?;
+        let dataset = self.datasets.get(&backtest.dataset_name)?;
+        backtest.exchange.quotes(dataset, backtest.date)
+    }
+
+    /// Returns the date of the backtest and whether there is another tick.
+    pub fn now(&self, backtest_id: BacktestId) -> Option<(i64, bool)> {
+        let backtest = self.backtests.get(&backtest_id)?;
+        let dataset = self.datasets.get(&backtest.dataset_name)?;
+        Some((backtest.date, dataset.has_next(backtest.pos)))
+    }
+
+    pub fn init(&mut self, dataset_name: String) -> Option<BacktestId> {
+        self.new_backtest(&dataset_name)
+    }
+
+    pub fn insert_order(
+        &mut self,
+        order: E::Order,
+        backtest_id: BacktestId,
+    ) -> Option<E::InsertResponse> {
+        let backtest = self.backtests.get_mut(&backtest_id)?;
+        Some(backtest.exchange.insert_order(order))
+    }
+
+    pub fn delete_order(
+        &mut self,
+        order_id: E::OrderId,
+        backtest_id: BacktestId,
+    ) -> Option<E::DeleteResponse> {
+        let backtest = self.backtests.get_mut(&backtest_id)?;
+        Some(backtest.exchange.delete_order(order_id))
+    }
+
+    /// Returns None if the dataset doesn't exist or is empty.
+    pub fn new_backtest(&mut self, dataset_name: &str) -> Option<BacktestId> {
+        let new_id = self.last + 1;
+
+        // Check that dataset exists and has a date to start from
+        let dataset = self.datasets.get(dataset_name)?;
+        let date = *dataset.get_date(0)?;
+        let exchange = self.new_exchange(dataset_name, dataset);
+
+        let backtest = BacktestState {
+            id: new_id,
+            date,
+            pos: 0,
+            exchange,
+            dataset_name: dataset_name.into(),
+        };
+
+        self.backtests.insert(new_id, backtest);
+
+        self.last = new_id;
+        Some(new_id)
+    }
+}
+
+#[derive(Debug, Display, Error)]
+pub enum BacktestError {
+    UnknownBacktest,
+    UnknownDataset,
+}
+
+impl error::ResponseError for BacktestError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            BacktestError::UnknownBacktest => actix_web::http::StatusCode::BAD_REQUEST,
+            BacktestError::UnknownDataset => actix_web::http::StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FetchQuotesResponse<Q> {
+    pub quotes: HashMap<String, Q>,
+}
+
+pub async fn fetch_quotes<E, I>(
+    app: web::Data<Mutex<AppState<E, I>>>,
+    path: web::Path<(BacktestId,)>,
+) -> Result<web::Json<FetchQuotesResponse<E::Quote>>, BacktestError>
+where
+    E: Tick<I>,
+    I: Input,
+    I::Quote: Into<E::Quote>,
+{
+    let state = app.lock().unwrap();
+    let (backtest_id,) = path.into_inner();
+
+    if let Some(quotes) = state.fetch_quotes(backtest_id) {
+        Ok(web::Json(FetchQuotesResponse { quotes }))
+    } else {
+        Err(BacktestError::UnknownBacktest)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InitResponse {
+    pub backtest_id: BacktestId,
+}
+
+pub async fn init<E, I>(
+    app: web::Data<Mutex<AppState<E, I>>>,
+    path: web::Path<(String,)>,
+) -> Result<web::Json<InitResponse>, BacktestError>
+where
+    E: Tick<I>,
+    I: Input,
+    I::Quote: Into<E::Quote>,
+{
+    let mut state = app.lock().unwrap();
+    let (dataset_name,) = path.into_inner();
+
+    if let Some(backtest_id) = state.init(dataset_name) {
+        Ok(web::Json(InitResponse { backtest_id }))
+    } else {
+        Err(BacktestError::UnknownDataset)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InfoResponse {
+    pub version: String,
+    pub dataset: String,
+}
+
+pub async fn info<E, I>(
+    app: web::Data<Mutex<AppState<E, I>>>,
+    path: web::Path<(BacktestId,)>,
+) -> Result<web::Json<InfoResponse>, BacktestError> {
+    let state = app.lock().unwrap();
+    let (backtest_id,) = path.into_inner();
+
+    if let Some(resp) = state.backtests.get(&backtest_id) {
+        Ok(web::Json(InfoResponse {
+            version: "v1".to_string(),
+            dataset: resp.dataset_name.clone(),
+        }))
+    } else {
+        Err(BacktestError::UnknownBacktest)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NowResponse {
+    pub now: i64,
+    pub has_next: bool,
+}
+
+pub async fn now<E, I>(
+    app: web::Data<Mutex<AppState<E, I>>>,
+    path: web::Path<(BacktestId,)>,
+) -> Result<web::Json<NowResponse>, BacktestError>
+where
+    E: Tick<I>,
+    I: Input,
+    I::Quote: Into<E::Quote>,
+{
+    let state = app.lock().unwrap();
+    let (backtest_id,) = path.into_inner();
+
+    if let Some((now, has_next)) = state.now(backtest_id) {
+        Ok(web::Json(NowResponse { now, has_next }))
+    } else {
+        Err(BacktestError::UnknownBacktest)
+    }
+}
+
+/// Registers the endpoints shared by every exchange. The app data must be a
+/// `web::Data<Mutex<AppState<E, I>>>`.
+pub fn configure<E, I>(cfg: &mut web::ServiceConfig)
+where
+    E: Tick<I> + 'static,
+    E::Quote: Serialize,
+    I: Input + 'static,
+    I::Quote: Into<E::Quote>,
+{
+    // Resources are guarded by method, as with the route macros, so that exchanges can serve
+    // other methods on the same path
+    cfg.service(
+        web::resource("/init/{dataset_name}")
+            .guard(guard::Get())
+            .to(init::<E, I>),
+    )
+    .service(
+        web::resource("/backtest/{backtest_id}/fetch_quotes")
+            .guard(guard::Get())
+            .to(fetch_quotes::<E, I>),
+    )
+    .service(
+        web::resource("/backtest/{backtest_id}/info")
+            .guard(guard::Get())
+            .to(info::<E, I>),
+    )
+    .service(
+        web::resource("/backtest/{backtest_id}/now")
+            .guard(guard::Get())
+            .to(now::<E, I>),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use actix_web::{test, web, App};
+
+    use super::{configure, AppState, InfoResponse, InitResponse, NowResponse};
+    use crate::exchange::uist_v1::{Order, UistQuote, UistV1};
+    use crate::input::penelope::Penelope;
+    use crate::input::Input;
+
+    #[derive(Clone)]
+    struct Mid {
+        symbol: String,
+        date: i64,
+        price: f64,
+    }
+
+    impl From<Mid> for UistQuote {
+        fn from(value: Mid) -> Self {
+            Self {
+                bid: value.price,
+                ask: value.price,
+                date: value.date,
+                symbol: value.symbol,
+                volume: None,
+                bar: None,
+                trades: None,
+            }
+        }
+    }
+
+    struct Mids {
+        dates: Vec<i64>,
+        symbols: Vec<String>,
+    }
+
+    impl Input for Mids {
+        type Quote = Mid;
+
+        fn get_quotes(&self, date: &i64) -> Option<impl Iterator<Item = (&str, Mid)>> {
+            let quote = Mid {
+                symbol: self.symbols[0].clone(),
+                date: *date,
+                price: *date as f64,
+            };
+            Some(std::iter::once((self.symbols[0].as_str(), quote)))
+        }
+
+        fn get_symbols(&self) -> &[String] {
+            &self.symbols
+        }
+
+        fn get_date(&self, pos: usize) -> Option<&i64> {
+            self.dates.get(pos)
+        }
+
+        fn has_next(&self, pos: usize) -> bool {
+            self.dates.len() > pos
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_that_backtest_runs_against_custom_input() {
+        let input = Mids {
+            dates: vec![100, 101, 102],
+            symbols: vec!["ABC".to_string()],
+        };
+        let mut state = AppState::<UistV1, Mids>::single("mids", input);
+
+        assert_eq!(state.fetch_quotes(0).unwrap()["ABC"].ask, 100.0);
+        state.insert_order(Order::market_buy("ABC", 10.0), 0);
+        state.tick(0);
+        let trades = state.tick(0).unwrap().fills;
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].value, 1010.0);
+    }
+
+    #[actix_web::test]
+    async fn test_that_shared_endpoints_are_served_for_exchange() {
+        let mut source = Penelope::new();
+        source.add_quote(100.00, 101.00, 100, "ABC");
+        source.add_quote(102.00, 103.00, 101, "ABC");
+
+        let mut datasets = [("fake".to_string(), source)].into_iter().collect();
+        let state = AppState::<UistV1>::create(&mut datasets);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Mutex::new(state)))
+                .configure(configure::<UistV1, Penelope>),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/init/fake").to_request();
+        let resp: InitResponse = test::call_and_read_body_json(&app, req).await;
+        let backtest_id = resp.backtest_id;
+
+        let req = test::TestRequest::get()
+            .uri(format!("/backtest/{backtest_id}/info").as_str())
+            .to_request();
+        let resp: InfoResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp.dataset, "fake");
+
+        let req = test::TestRequest::get()
+            .uri(format!("/backtest/{backtest_id}/now").as_str())
+            .to_request();
+        let resp: NowResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp.now, 100);
+        assert!(resp.has_next);
+
+        let req = test::TestRequest::get().uri("/init/missing").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_client_error());
+    }
+
+    #[actix_web::test]
+    async fn test_that_backtest_is_not_created_on_empty_dataset() {
+        let mut state = AppState::<UistV1>::single("empty", Penelope::new());
+        assert!(state.backtests.is_empty());
+        assert!(state.new_backtest("empty").is_none());
+    }
+}
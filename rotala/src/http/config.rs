@@ -0,0 +1,519 @@
+//! Configuration for the server binaries. A server can be started with an address and port, which
+//! serves a single randomly generated dataset, or with a TOML file that lists named datasets:
+//!
+//! ```toml
+//! address = "127.0.0.1"
+//! port = 8080
+//! log_level = "info"
+//! # Every file with a .penelope extension in this directory is loaded, named by file name
+//! dataset_dir = "/data/prebuilt"
+//!
+//! [[datasets]]
+//! name = "btc_2022"
+//! format = "penelope"
+//! path = "/data/btc_2022.penelope"
+//!
+//! [[datasets]]
+//! name = "equities"
+//! format = "csv"
+//! path = "/data/equities.csv"
+//! columns = { date = "timestamp", symbol = "ticker" }
+//!
+//! [[datasets]]
+//! name = "eth"
+//! format = "binance"
+//! path = "/data/binance"
+//! symbols = ["ETHUSDT"]
+//! start = "2022-08-01"
+//! end = "2022-08-31"
+//! # Assets that Jura can trade, asset ids are the position in this list. Defaults to every symbol
+//! # in the dataset with default trading rules.
+//! universe = [{ name = "ETHUSDT", sz_decimals = 4, tick_size = 0.1, max_leverage = 25 }]
+//! # CSV file of funding rates with date, symbol and rate columns, paid by Jura
+//! funding = "/data/binance/ETHUSDT_funding.csv"
+//!
+//! [uist]
+//! slippage = { model = "fixed", bps = 2.5 }
+//! participation_rate = 0.1
+//!
+//! [jura]
+//! slippage = 0.05
+//! balance = 10000.0
+//! ```
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use time::macros::format_description;
+use time::Date;
+
+use crate::exchange::jura_v1::{AssetMeta, JuraV1, Universe, DEFAULT_BALANCE};
+use crate::exchange::uist_v1::{
+    is_valid_participation_rate, FixedBpsSlippage, SpreadSlippage, SqrtImpactSlippage, UistV1,
+};
+use crate::input::penelope::{Penelope, PenelopeColumns, PenelopeError};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    InvalidArgs(String),
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    InvalidDate(String),
+    InvalidParticipationRate(f64),
+    DuplicateDataset(String),
+    EmptyDataset(String),
+    Dataset { name: String, err: PenelopeError },
+}
+
+impl Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidArgs(reason) => write!(f, "InvalidArgs: {reason}"),
+            ConfigError::Io(err) => write!(f, "Io: {err}"),
+            ConfigError::Toml(err) => write!(f, "Toml: {err}"),
+            ConfigError::InvalidDate(date) => write!(f, "InvalidDate: {date}"),
+            ConfigError::InvalidParticipationRate(rate) => {
+                write!(f, "InvalidParticipationRate: {rate}, must be in (0, 1]")
+            }
+            ConfigError::DuplicateDataset(name) => write!(f, "DuplicateDataset: {name}"),
+            ConfigError::EmptyDataset(name) => write!(f, "EmptyDataset: {name}"),
+            ConfigError::Dataset { name, err } => write!(f, "Dataset: {name}, {err}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(value: std::io::Error) -> Self {
+        ConfigError::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(value: toml::de::Error) -> Self {
+        ConfigError::Toml(value)
+    }
+}
+
+/// Where a dataset is loaded from, set with the `format` key.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum DatasetSource {
+    /// File created by [Penelope::save].
+    Penelope { path: PathBuf },
+    /// CSV file loaded with [Penelope::from_csv].
+    Csv {
+        path: PathBuf,
+        #[serde(default)]
+        columns: PenelopeColumns,
+    },
+    /// Directory of Binance kline archives loaded with [Penelope::from_binance]. Dates are
+    /// formatted as `YYYY-MM-DD`.
+    Binance {
+        path: PathBuf,
+        symbols: Vec<String>,
+        start: String,
+        end: String,
+    },
+    /// Random data created with [Penelope::random].
+    Random { length: i64, symbols: Vec<String> },
+}
+
+/// Trading rules for a Jura asset, unset rules take the defaults in [AssetMeta::new].
+#[derive(Clone, Debug, Deserialize)]
+pub struct AssetConfig {
+    pub name: String,
+    pub sz_decimals: Option<u32>,
+    pub tick_size: Option<f64>,
+    pub max_leverage: Option<u32>,
+    pub min_notional: Option<f64>,
+}
+
+impl From<&AssetConfig> for AssetMeta {
+    fn from(value: &AssetConfig) -> Self {
+        let meta = AssetMeta::new(value.name.clone());
+        Self {
+            sz_decimals: value.sz_decimals.unwrap_or(meta.sz_decimals),
+            tick_size: value.tick_size.unwrap_or(meta.tick_size),
+            max_leverage: value.max_leverage.unwrap_or(meta.max_leverage),
+            min_notional: value.min_notional.unwrap_or(meta.min_notional),
+            ..meta
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DatasetConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub source: DatasetSource,
+    #[serde(default)]
+    pub universe: Vec<AssetConfig>,
+    /// CSV file of funding rates loaded with [Penelope::add_funding_from_csv].
+    pub funding: Option<PathBuf>,
+}
+
+impl DatasetConfig {
+    fn parse_date(date: &str) -> Result<Date, ConfigError> {
+        Date::parse(date, format_description!("[year]-[month]-[day]"))
+            .map_err(|_| ConfigError::InvalidDate(date.to_string()))
+    }
+
+    pub fn load(&self) -> Result<Penelope, ConfigError> {
+        let res = match &self.source {
+            DatasetSource::Penelope { path } => Penelope::load(path),
+            DatasetSource::Csv { path, columns } => Penelope::from_csv(path, columns),
+            DatasetSource::Binance {
+                path,
+                symbols,
+                start,
+                end,
+            } => Penelope::from_binance(
+                path,
+                symbols.iter().map(|symbol| symbol.as_str()).collect(),
+                Self::parse_date(start)?,
+                Self::parse_date(end)?,
+            ),
+            DatasetSource::Random { length, symbols } => Ok(Penelope::random(
+                *length,
+                symbols.iter().map(|symbol| symbol.as_str()).collect(),
+            )),
+        };
+        let res = res.and_then(|mut dataset| {
+            if let Some(path) = &self.funding {
+                dataset.add_funding_from_csv(path)?;
+            }
+            Ok(dataset)
+        });
+        res.map_err(|err| ConfigError::Dataset {
+            name: self.name.clone(),
+            err,
+        })
+    }
+}
+
+/// Slippage model used by Uist, set with the `model` key. See
+/// [SlippageModel](crate::exchange::uist_v1::SlippageModel).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "model", rename_all = "lowercase")]
+pub enum SlippageConfig {
+    Fixed { bps: f64 },
+    Spread { fraction: f64 },
+    Sqrt { coefficient: f64 },
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct UistConfig {
+    pub slippage: Option<SlippageConfig>,
+    /// Caps fills at this fraction of quote volume, see [UistV1::with_liquidity].
+    pub participation_rate: Option<f64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct JuraConfig {
+    pub slippage: f64,
+    /// Collateral that each backtest starts with, see [JuraV1::with_balance].
+    pub balance: f64,
+}
+
+impl Default for JuraConfig {
+    fn default() -> Self {
+        Self {
+            slippage: 0.1,
+            balance: DEFAULT_BALANCE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub address: String,
+    pub port: u16,
+    /// Filter passed to env_logger, i.e. `info` or `rotala=debug,actix_web=info`.
+    pub log_level: String,
+    pub dataset_dir: Option<PathBuf>,
+    pub datasets: Vec<DatasetConfig>,
+    pub uist: UistConfig,
+    pub jura: JuraConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: "127.0.0.1".to_string(),
+            port: 8080,
+            log_level: "info".to_string(),
+            dataset_dir: None,
+            datasets: Vec::new(),
+            uist: UistConfig::default(),
+            jura: JuraConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn parse(config: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(config)?;
+        if let Some(rate) = config.uist.participation_rate {
+            if !is_valid_participation_rate(rate) {
+                return Err(ConfigError::InvalidParticipationRate(rate));
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Builds config from command line arguments, without the program name. Accepts either
+    /// `--config [path]` or `[address] [port] [dataset_dir]` where `dataset_dir` is optional.
+    pub fn from_args(args: &[String]) -> Result<Self, ConfigError> {
+        match args {
+            [flag, path] if flag == "--config" => Self::from_file(path),
+            [address, port, rest @ ..] if rest.len() <= 1 => {
+                let port = port
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidArgs(format!("invalid port {port}")))?;
+                Ok(Self {
+                    address: address.clone(),
+                    port,
+                    dataset_dir: rest.first().map(PathBuf::from),
+                    ..Self::default()
+                })
+            }
+            _ => Err(ConfigError::InvalidArgs(
+                "expected --config [path] or [address] [port] [dataset_dir]".to_string(),
+            )),
+        }
+    }
+
+    /// Loads every dataset in `dataset_dir` and then every dataset listed in `datasets`. Dataset
+    /// names must be unique across both.
+    pub fn load_datasets(&self) -> Result<HashMap<String, Penelope>, ConfigError> {
+        let mut datasets = match &self.dataset_dir {
+            Some(dir) => Penelope::load_dir(dir).map_err(|err| ConfigError::Dataset {
+                name: dir.display().to_string(),
+                err,
+            })?,
+            None => HashMap::new(),
+        };
+
+        for dataset in &self.datasets {
+            if datasets.contains_key(&dataset.name) {
+                return Err(ConfigError::DuplicateDataset(dataset.name.clone()));
+            }
+            datasets.insert(dataset.name.clone(), dataset.load()?);
+        }
+
+        //Backtests start on the first date of a dataset
+        if let Some(name) = datasets
+            .iter()
+            .find(|(_, dataset)| dataset.get_date(0).is_none())
+            .map(|(name, _)| name)
+        {
+            return Err(ConfigError::EmptyDataset(name.clone()));
+        }
+        Ok(datasets)
+    }
+
+    /// Jura universe of every dataset that sets one, keyed by dataset name.
+    pub fn universes(&self) -> HashMap<String, Universe> {
+        self.datasets
+            .iter()
+            .filter(|dataset| !dataset.universe.is_empty())
+            .map(|dataset| {
+                let assets = dataset.universe.iter().map(AssetMeta::from).collect();
+                (dataset.name.clone(), Universe::new(assets))
+            })
+            .collect()
+    }
+
+    pub fn init_logger(&self) {
+        env_logger::Builder::new()
+            .parse_filters(&self.log_level)
+            .init();
+    }
+
+    pub fn uist_exchange(&self) -> UistV1 {
+        let exchange = match &self.uist.slippage {
+            Some(SlippageConfig::Fixed { bps }) => {
+                UistV1::new().with_slippage(FixedBpsSlippage { bps: *bps })
+            }
+            Some(SlippageConfig::Spread { fraction }) => {
+                UistV1::new().with_slippage(SpreadSlippage {
+                    fraction: *fraction,
+                })
+            }
+            Some(SlippageConfig::Sqrt { coefficient }) => {
+                UistV1::new().with_slippage(SqrtImpactSlippage {
+                    coefficient: *coefficient,
+                })
+            }
+            None => UistV1::new(),
+        };
+        match self.uist.participation_rate {
+            Some(participation_rate) => exchange.with_liquidity(participation_rate),
+            None => exchange,
+        }
+    }
+
+    pub fn jura_exchange(&self) -> JuraV1 {
+        JuraV1::new()
+            .with_slippage(self.jura.slippage)
+            .with_balance(self.jura.balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigError, DatasetSource, ServerConfig, SlippageConfig};
+    use crate::exchange::jura_v1::DEFAULT_TICK_SIZE;
+
+    #[test]
+    fn test_that_config_file_parses_datasets_and_settings() {
+        let config = ServerConfig::parse(
+            r#"
+            address = "0.0.0.0"
+            port = 9000
+            log_level = "debug"
+
+            [[datasets]]
+            name = "first"
+            format = "random"
+            length = 10
+            symbols = ["ABC", "BCD"]
+
+            [[datasets]]
+            name = "second"
+            format = "csv"
+            path = "/data/second.csv"
+            columns = { date = "timestamp" }
+            universe = [{ name = "ABC", sz_decimals = 2, max_leverage = 10 }]
+
+            [uist]
+            slippage = { model = "sqrt", coefficient = 0.1 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.address, "0.0.0.0");
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.log_level, "debug");
+        assert!(matches!(
+            config.uist.slippage,
+            Some(SlippageConfig::Sqrt { coefficient }) if coefficient == 0.1
+        ));
+        assert_eq!(config.jura.slippage, 0.1);
+        assert_eq!(config.jura.balance, 100_000.0);
+        assert_eq!(config.datasets.len(), 2);
+        if let DatasetSource::Csv { columns, .. } = &config.datasets[1].source {
+            assert_eq!(columns.date, "timestamp");
+            assert_eq!(columns.symbol, "symbol");
+        } else {
+            panic!("expected csv dataset");
+        }
+
+        let universes = config.universes();
+        assert_eq!(universes.len(), 1);
+        let meta = universes["second"].get(0).unwrap();
+        assert_eq!(meta.name, "ABC");
+        assert_eq!(meta.sz_decimals, 2);
+        assert_eq!(meta.max_leverage, 10);
+        assert_eq!(meta.tick_size, DEFAULT_TICK_SIZE);
+
+        let datasets = config.load_datasets().unwrap_err();
+        assert!(matches!(datasets, ConfigError::Dataset { name, .. } if name == "second"));
+    }
+
+    #[test]
+    fn test_that_datasets_are_loaded_by_name() {
+        let config = ServerConfig::parse(
+            r#"
+            [[datasets]]
+            name = "first"
+            format = "random"
+            length = 10
+            symbols = ["ABC"]
+            "#,
+        )
+        .unwrap();
+
+        let datasets = config.load_datasets().unwrap();
+        assert!(datasets.get("first").unwrap().get_quotes(&100).is_some());
+    }
+
+    #[test]
+    fn test_that_duplicate_dataset_names_error() {
+        let config = ServerConfig::parse(
+            r#"
+            [[datasets]]
+            name = "first"
+            format = "random"
+            length = 10
+            symbols = ["ABC"]
+
+            [[datasets]]
+            name = "first"
+            format = "random"
+            length = 10
+            symbols = ["BCD"]
+            "#,
+        )
+        .unwrap();
+
+        let res = config.load_datasets();
+        assert!(matches!(res, Err(ConfigError::DuplicateDataset(..))));
+    }
+
+    #[test]
+    fn test_that_empty_dataset_errors() {
+        let config = ServerConfig::parse(
+            r#"
+            [[datasets]]
+            name = "first"
+            format = "random"
+            length = 0
+            symbols = ["ABC"]
+            "#,
+        )
+        .unwrap();
+
+        let res = config.load_datasets();
+        assert!(matches!(res, Err(ConfigError::EmptyDataset(name)) if name == "first"));
+    }
+
+    #[test]
+    fn test_that_participation_rate_outside_unit_interval_errors() {
+        let res = ServerConfig::parse(
+            r#"
+            [uist]
+            participation_rate = 1.5
+            "#,
+        );
+        assert!(matches!(
+            res,
+            Err(ConfigError::InvalidParticipationRate(..))
+        ));
+    }
+
+    #[test]
+    fn test_that_positional_args_are_supported() {
+        let args = vec!["127.0.0.1".to_string(), "8000".to_string()];
+        let config = ServerConfig::from_args(&args).unwrap();
+        assert_eq!(config.port, 8000);
+        assert!(config.dataset_dir.is_none());
+
+        let args = vec!["127.0.0.1".to_string(), "port".to_string()];
+        assert!(matches!(
+            ServerConfig::from_args(&args),
+            Err(ConfigError::InvalidArgs(..))
+        ));
+    }
+}
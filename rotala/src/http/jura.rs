@@ -1,153 +1,71 @@
-use std::collections::HashMap;
+use crate::exchange::jura_v1::{JuraV1, Order, OrderId, OrderStatus, Universe};
 
-use crate::{
-    exchange::jura_v1::{Fill, JuraV1, Order, OrderId},
-    input::penelope::{Penelope, PenelopeQuoteByDate},
-};
+use super::backtest::BacktestId;
 
-type BacktestId = u64;
-
-pub struct BacktestState {
-    pub id: BacktestId,
-    pub date: i64,
-    pub pos: usize,
-    pub exchange: JuraV1,
-    pub dataset_name: String,
-}
-
-pub struct AppState {
-    pub backtests: HashMap<BacktestId, BacktestState>,
-    pub last: BacktestId,
-    pub datasets: HashMap<String, Penelope>,
-}
-
-pub type TickResult = (bool, Vec<Fill>, Vec<Order>, Vec<u64>);
+pub type TickResult = super::backtest::TickResult<JuraV1>;
+pub type BacktestState = super::backtest::BacktestState<JuraV1>;
+pub type AppState = super::backtest::AppState<JuraV1>;
 
 impl AppState {
-    pub fn create(datasets: &mut HashMap<String, Penelope>) -> Self {
-        Self {
-            backtests: HashMap::new(),
-            last: 0,
-            datasets: std::mem::take(datasets),
-        }
-    }
-
-    pub fn single(name: &str, data: Penelope) -> Self {
-        let exchange = JuraV1::new();
-        let backtest = BacktestState {
-            id: 0,
-            date: *data.get_date(0).unwrap(),
-            pos: 0,
-            exchange,
-            dataset_name: name.into(),
-        };
-
-        let mut datasets = HashMap::new();
-        datasets.insert(name.into(), data);
-
-        let mut backtests = HashMap::new();
-        backtests.insert(0, backtest);
-
-        Self {
-            backtests,
-            last: 1,
-            datasets,
-        }
+    /// Sets the assets that can be traded in a dataset. Datasets without a universe trade every
+    /// symbol in the dataset with default rules, numbered in the order that symbols first appear.
+    /// Uses the exchange set by [with_exchange](AppState::with_exchange) if there isn't one for
+    /// the dataset already.
+    pub fn with_universe(self, dataset_name: &str, universe: Universe) -> Self {
+        let exchange = self
+            .dataset_exchanges
+            .get(dataset_name)
+            .unwrap_or(&self.exchange)
+            .clone()
+            .with_universe(universe);
+        self.with_dataset_exchange(dataset_name, exchange)
     }
 
-    pub fn tick(&mut self, backtest_id: BacktestId) -> Option<TickResult> {
+    pub fn update_leverage(
+        &mut self,
+        asset: u64,
+        is_cross: bool,
+        leverage: u32,
+        backtest_id: BacktestId,
+    ) -> Option<Result<(), String>> {
         if let Some(backtest) = self.backtests.get_mut(&backtest_id) {
-            if let Some(dataset) = self.datasets.get(&backtest.dataset_name) {
-                let mut has_next = false;
-                let mut fills = Vec::new();
-                let mut orders = Vec::new();
-                let mut order_ids = Vec::new();
-
-                if let Some(quotes) = dataset.get_quotes(&backtest.date) {
-                    let mut res = backtest.exchange.tick(quotes);
-                    fills.append(&mut res.0);
-                    orders.append(&mut res.1);
-                    order_ids.append(&mut res.2);
-                }
-
-                let new_pos = backtest.pos + 1;
-                if dataset.has_next(new_pos) {
-                    has_next = true;
-                    backtest.date = *dataset.get_date(new_pos).unwrap();
-                }
-
-                return Some((has_next, fills, orders, order_ids));
-            }
+            return Some(backtest.exchange.update_leverage(asset, is_cross, leverage));
         }
         None
     }
 
-    pub fn fetch_quotes(&self, backtest_id: BacktestId) -> Option<&PenelopeQuoteByDate> {
-        if let Some(backtest) = self.backtests.get(&backtest_id) {
-            if let Some(dataset) = self.datasets.get(&backtest.dataset_name) {
-                return dataset.get_quotes(&backtest.date);
-            }
-        }
-        None
+    /// Places an order against the current quotes, see [JuraV1::place_order].
+    pub fn place_order(&mut self, order: Order, backtest_id: BacktestId) -> Option<OrderStatus> {
+        let quotes = self.fetch_quotes(backtest_id).unwrap_or_default();
+        let backtest = self.backtests.get_mut(&backtest_id)?;
+        Some(backtest.exchange.place_order(order, &quotes))
     }
 
-    pub fn init(&mut self, dataset_name: String) -> Option<BacktestId> {
-        if let Some(dataset) = self.datasets.get(&dataset_name) {
-            let new_id = self.last + 1;
-            let exchange = JuraV1::new();
-            let backtest = BacktestState {
-                id: new_id,
-                date: *dataset.get_date(0).unwrap(),
-                pos: 0,
-                exchange,
-                dataset_name,
-            };
-            self.backtests.insert(new_id, backtest);
-            return Some(new_id);
-        }
-        None
-    }
-
-    pub fn insert_order(&mut self, order: Order, backtest_id: BacktestId) -> Option<()> {
-        if let Some(backtest) = self.backtests.get_mut(&backtest_id) {
-            backtest.exchange.insert_order(order);
-            return Some(());
-        }
-        None
+    pub fn modify_order(
+        &mut self,
+        order_id: OrderId,
+        order: Order,
+        backtest_id: BacktestId,
+    ) -> Option<OrderStatus> {
+        let quotes = self.fetch_quotes(backtest_id).unwrap_or_default();
+        let backtest = self.backtests.get_mut(&backtest_id)?;
+        let asset = order.get_asset();
+        Some(
+            backtest
+                .exchange
+                .modify_order(asset, order_id, order, &quotes),
+        )
     }
 
-    pub fn delete_order(
+    /// Returns false if there is no resting order with the cloid.
+    pub fn delete_order_by_cloid(
         &mut self,
         asset: u64,
-        order_id: OrderId,
+        cloid: &str,
         backtest_id: BacktestId,
-    ) -> Option<()> {
+    ) -> Option<bool> {
         if let Some(backtest) = self.backtests.get_mut(&backtest_id) {
-            backtest.exchange.delete_order(asset, order_id);
-            return Some(());
-        }
-        None
-    }
-
-    pub fn new_backtest(&mut self, dataset_name: &str) -> Option<BacktestId> {
-        let new_id = self.last + 1;
-
-        // Check that dataset exists
-        if let Some(dataset) = self.datasets.get(dataset_name) {
-            let exchange = JuraV1::new();
-
-            let backtest = BacktestState {
-                id: new_id,
-                date: *dataset.get_date(0).unwrap(),
-                pos: 0,
-                exchange,
-                dataset_name: dataset_name.into(),
-            };
-
-            self.backtests.insert(new_id, backtest);
-
-            self.last = new_id;
-            return Some(new_id);
+            return Some(backtest.exchange.delete_order_by_cloid(asset, cloid));
         }
         None
     }
@@ -155,19 +73,23 @@ impl AppState {
 
 pub mod jurav1_client {
 
+    use std::future;
     use std::future::Future;
 
-    use anyhow::Result;
+    use anyhow::{Error, Result};
 
     use super::{
+        hyperliquid_server::{InfoRequest, Meta},
         jurav1_server::{
-            DeleteOrderRequest, FetchQuotesResponse, InfoResponse, InitResponse,
-            InsertOrderRequest, TickResponse,
+            DeleteOrderByCloidRequest, DeleteOrderRequest, FetchQuotesResponse, InfoResponse,
+            InitResponse, InsertOrderRequest, JuraV1Error, NowResponse, TickResponse,
+            UpdateLeverageRequest,
         },
-        BacktestId,
+        AppState, BacktestId,
     };
 
     use crate::exchange::jura_v1::{Order, OrderId};
+    use crate::input::penelope::Penelope;
 
     pub trait JuraClient {
         fn tick(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<TickResponse>>;
@@ -177,6 +99,19 @@ pub mod jurav1_client {
             order_id: OrderId,
             backtest_id: BacktestId,
         ) -> impl Future<Output = Result<()>>;
+        fn delete_order_by_cloid(
+            &mut self,
+            asset: u64,
+            cloid: String,
+            backtest_id: BacktestId,
+        ) -> impl Future<Output = Result<()>>;
+        fn update_leverage(
+            &mut self,
+            asset: u64,
+            is_cross: bool,
+            leverage: u32,
+            backtest_id: BacktestId,
+        ) -> impl Future<Output = Result<()>>;
         fn insert_order(
             &mut self,
             order: Order,
@@ -188,6 +123,138 @@ pub mod jurav1_client {
         ) -> impl Future<Output = Result<FetchQuotesResponse>>;
         fn init(&mut self, dataset_name: String) -> impl Future<Output = Result<InitResponse>>;
         fn info(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<InfoResponse>>;
+        fn now(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<NowResponse>>;
+        /// Assets that can be traded in the backtest, an asset id is the index into the universe.
+        fn meta(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<Meta>>;
+    }
+
+    pub struct TestClient {
+        state: AppState,
+    }
+
+    impl JuraClient for TestClient {
+        fn init(&mut self, dataset_name: String) -> impl Future<Output = Result<InitResponse>> {
+            if let Some(id) = self.state.init(dataset_name) {
+                future::ready(Ok(InitResponse { backtest_id: id }))
+            } else {
+                future::ready(Err(Error::new(JuraV1Error::UnknownDataset)))
+            }
+        }
+
+        fn tick(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<TickResponse>> {
+            if let Some(result) = self.state.tick(backtest_id) {
+                future::ready(Ok(TickResponse::from(result)))
+            } else {
+                future::ready(Err(Error::new(JuraV1Error::UnknownBacktest)))
+            }
+        }
+
+        fn delete_order(
+            &mut self,
+            asset: u64,
+            order_id: OrderId,
+            backtest_id: BacktestId,
+        ) -> impl Future<Output = Result<()>> {
+            if self
+                .state
+                .delete_order((asset, order_id), backtest_id)
+                .is_some()
+            {
+                future::ready(Ok(()))
+            } else {
+                future::ready(Err(Error::new(JuraV1Error::UnknownBacktest)))
+            }
+        }
+
+        fn delete_order_by_cloid(
+            &mut self,
+            asset: u64,
+            cloid: String,
+            backtest_id: BacktestId,
+        ) -> impl Future<Output = Result<()>> {
+            match self.state.delete_order_by_cloid(asset, &cloid, backtest_id) {
+                Some(true) => future::ready(Ok(())),
+                Some(false) => future::ready(Err(Error::new(JuraV1Error::UnknownOrder))),
+                None => future::ready(Err(Error::new(JuraV1Error::UnknownBacktest))),
+            }
+        }
+
+        fn update_leverage(
+            &mut self,
+            asset: u64,
+            is_cross: bool,
+            leverage: u32,
+            backtest_id: BacktestId,
+        ) -> impl Future<Output = Result<()>> {
+            match self
+                .state
+                .update_leverage(asset, is_cross, leverage, backtest_id)
+            {
+                Some(Ok(())) => future::ready(Ok(())),
+                Some(Err(_)) => future::ready(Err(Error::new(JuraV1Error::InvalidLeverage))),
+                None => future::ready(Err(Error::new(JuraV1Error::UnknownBacktest))),
+            }
+        }
+
+        fn insert_order(
+            &mut self,
+            order: Order,
+            backtest_id: BacktestId,
+        ) -> impl Future<Output = Result<()>> {
+            if let Some(()) = self.state.insert_order(order, backtest_id) {
+                future::ready(Ok(()))
+            } else {
+                future::ready(Err(Error::new(JuraV1Error::UnknownBacktest)))
+            }
+        }
+
+        fn fetch_quotes(
+            &mut self,
+            backtest_id: BacktestId,
+        ) -> impl Future<Output = Result<FetchQuotesResponse>> {
+            if let Some(quotes) = self.state.fetch_quotes(backtest_id) {
+                future::ready(Ok(FetchQuotesResponse { quotes }))
+            } else {
+                future::ready(Err(Error::new(JuraV1Error::UnknownBacktest)))
+            }
+        }
+
+        fn info(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<InfoResponse>> {
+            if let Some(backtest) = self.state.backtests.get(&backtest_id) {
+                future::ready(Ok(InfoResponse {
+                    version: "v1".to_string(),
+                    dataset: backtest.dataset_name.clone(),
+                }))
+            } else {
+                future::ready(Err(Error::new(JuraV1Error::UnknownBacktest)))
+            }
+        }
+
+        fn now(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<NowResponse>> {
+            if let Some((now, has_next)) = self.state.now(backtest_id) {
+                future::ready(Ok(NowResponse { now, has_next }))
+            } else {
+                future::ready(Err(Error::new(JuraV1Error::UnknownBacktest)))
+            }
+        }
+
+        fn meta(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<Meta>> {
+            if let Some(backtest) = self.state.backtests.get(&backtest_id) {
+                future::ready(Ok(Meta {
+                    universe: backtest.exchange.get_universe().get_assets().to_vec(),
+                }))
+            } else {
+                future::ready(Err(Error::new(JuraV1Error::UnknownBacktest)))
+            }
+        }
+    }
+
+    impl TestClient {
+        pub fn single(name: &str, data: Penelope) -> Self {
+            Self {
+                state: AppState::single(name, data),
+            }
+        }
     }
 
     pub struct Client {
@@ -223,6 +290,50 @@ pub mod jurav1_client {
                 .await?)
         }
 
+        async fn delete_order_by_cloid(
+            &mut self,
+            asset: u64,
+            cloid: String,
+            backtest_id: BacktestId,
+        ) -> Result<()> {
+            let req = DeleteOrderByCloidRequest { asset, cloid };
+            Ok(self
+                .client
+                .post(
+                    self.path.clone()
+                        + format!("/backtest/{backtest_id}/delete_order_by_cloid").as_str(),
+                )
+                .json(&req)
+                .send()
+                .await?
+                .json::<()>()
+                .await?)
+        }
+
+        async fn update_leverage(
+            &mut self,
+            asset: u64,
+            is_cross: bool,
+            leverage: u32,
+            backtest_id: BacktestId,
+        ) -> Result<()> {
+            let req = UpdateLeverageRequest {
+                asset,
+                is_cross,
+                leverage,
+            };
+            Ok(self
+                .client
+                .post(
+                    self.path.clone() + format!("/backtest/{backtest_id}/update_leverage").as_str(),
+                )
+                .json(&req)
+                .send()
+                .await?
+                .json::<()>()
+                .await?)
+        }
+
         async fn insert_order(&mut self, order: Order, backtest_id: BacktestId) -> Result<()> {
             let req = InsertOrderRequest { order };
             Ok(self
@@ -264,6 +375,27 @@ pub mod jurav1_client {
                 .json::<InfoResponse>()
                 .await?)
         }
+
+        async fn now(&mut self, backtest_id: BacktestId) -> Result<NowResponse> {
+            Ok(self
+                .client
+                .get(self.path.clone() + format!("/backtest/{backtest_id}/now").as_str())
+                .send()
+                .await?
+                .json::<NowResponse>()
+                .await?)
+        }
+
+        async fn meta(&mut self, backtest_id: BacktestId) -> Result<Meta> {
+            Ok(self
+                .client
+                .post(self.path.clone() + format!("/backtest/{backtest_id}/info").as_str())
+                .json(&InfoRequest::Meta)
+                .send()
+                .await?
+                .json::<Meta>()
+                .await?)
+        }
     }
 
     impl Client {
@@ -280,8 +412,11 @@ pub mod jurav1_server {
     use serde::{Deserialize, Serialize};
     use std::sync::Mutex;
 
-    use crate::exchange::jura_v1::{Fill, Order, OrderId};
-    use crate::input::penelope::PenelopeQuoteByDate;
+    use crate::exchange::jura_v1::{
+        CanceledOrder, Fill, FundingPayment, JuraQuote, JuraV1, Order, OrderId, OrderStatus,
+    };
+    use crate::http::backtest;
+    use crate::input::penelope::Penelope;
     use actix_web::{
         error, get, post,
         web::{self, Path},
@@ -289,15 +424,21 @@ pub mod jurav1_server {
     };
     use derive_more::{Display, Error};
 
-    use super::AppState;
+    use super::hyperliquid_server::{hyperliquid_exchange, hyperliquid_info};
+    use super::{AppState, BacktestId, TickResult};
+
+    pub use crate::http::backtest::{InfoResponse, InitResponse, NowResponse};
+
+    pub type FetchQuotesResponse = backtest::FetchQuotesResponse<JuraQuote>;
 
-    type BacktestId = u64;
     pub type JuraState = Mutex<AppState>;
 
     #[derive(Debug, Display, Error)]
     pub enum JuraV1Error {
         UnknownBacktest,
         UnknownDataset,
+        UnknownOrder,
+        InvalidLeverage,
     }
 
     impl error::ResponseError for JuraV1Error {
@@ -305,6 +446,8 @@ pub mod jurav1_server {
             match self {
                 JuraV1Error::UnknownBacktest => actix_web::http::StatusCode::BAD_REQUEST,
                 JuraV1Error::UnknownDataset => actix_web::http::StatusCode::BAD_REQUEST,
+                JuraV1Error::UnknownOrder => actix_web::http::StatusCode::BAD_REQUEST,
+                JuraV1Error::InvalidLeverage => actix_web::http::StatusCode::BAD_REQUEST,
             }
         }
     }
@@ -314,6 +457,28 @@ pub mod jurav1_server {
         pub has_next: bool,
         pub executed_trades: Vec<Fill>,
         pub inserted_orders: Vec<Order>,
+        /// Status of each order in inserted_orders, in the same order.
+        #[serde(default)]
+        pub order_statuses: Vec<OrderStatus>,
+        /// Funding settled against open positions on this tick.
+        #[serde(default)]
+        pub user_funding: Vec<FundingPayment>,
+        /// Resting orders cancelled by the exchange on this tick, with the reason.
+        #[serde(default)]
+        pub canceled_orders: Vec<CanceledOrder>,
+    }
+
+    impl From<TickResult> for TickResponse {
+        fn from(result: TickResult) -> Self {
+            Self {
+                has_next: result.has_next,
+                executed_trades: result.fills,
+                inserted_orders: result.inserted_orders,
+                order_statuses: result.events.statuses,
+                user_funding: result.events.funding,
+                canceled_orders: result.events.canceled,
+            }
+        }
     }
 
     #[get("/backtest/{backtest_id}/tick")]
@@ -325,11 +490,7 @@ pub mod jurav1_server {
         let (backtest_id,) = path.into_inner();
 
         if let Some(result) = jura.tick(backtest_id) {
-            Ok(web::Json(TickResponse {
-                inserted_orders: result.2,
-                executed_trades: result.1,
-                has_next: result.0,
-            }))
+            Ok(web::Json(TickResponse::from(result)))
         } else {
             Err(JuraV1Error::UnknownBacktest)
         }
@@ -350,7 +511,9 @@ pub mod jurav1_server {
         let mut jura = app.lock().unwrap();
         let (backtest_id,) = path.into_inner();
 
-        if let Some(()) = jura.delete_order(delete_order.asset, delete_order.order_id, backtest_id)
+        if jura
+            .delete_order((delete_order.asset, delete_order.order_id), backtest_id)
+            .is_some()
         {
             Ok(web::Json(()))
         } else {
@@ -358,6 +521,51 @@ pub mod jurav1_server {
         }
     }
 
+    #[derive(Debug, Deserialize, Serialize)]
+    pub struct DeleteOrderByCloidRequest {
+        pub asset: u64,
+        pub cloid: String,
+    }
+
+    #[post("/backtest/{backtest_id}/delete_order_by_cloid")]
+    pub async fn delete_order_by_cloid(
+        app: web::Data<JuraState>,
+        path: web::Path<(BacktestId,)>,
+        cancel: web::Json<DeleteOrderByCloidRequest>,
+    ) -> Result<web::Json<()>, JuraV1Error> {
+        let mut jura = app.lock().unwrap();
+        let (backtest_id,) = path.into_inner();
+
+        match jura.delete_order_by_cloid(cancel.asset, &cancel.cloid, backtest_id) {
+            Some(true) => Ok(web::Json(())),
+            Some(false) => Err(JuraV1Error::UnknownOrder),
+            None => Err(JuraV1Error::UnknownBacktest),
+        }
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    pub struct UpdateLeverageRequest {
+        pub asset: u64,
+        pub is_cross: bool,
+        pub leverage: u32,
+    }
+
+    #[post("/backtest/{backtest_id}/update_leverage")]
+    pub async fn update_leverage(
+        app: web::Data<JuraState>,
+        path: web::Path<(BacktestId,)>,
+        update: web::Json<UpdateLeverageRequest>,
+    ) -> Result<web::Json<()>, JuraV1Error> {
+        let mut jura = app.lock().unwrap();
+        let (backtest_id,) = path.into_inner();
+
+        match jura.update_leverage(update.asset, update.is_cross, update.leverage, backtest_id) {
+            Some(Ok(())) => Ok(web::Json(())),
+            Some(Err(_)) => Err(JuraV1Error::InvalidLeverage),
+            None => Err(JuraV1Error::UnknownBacktest),
+        }
+    }
+
     #[derive(Debug, Deserialize, Serialize)]
     pub struct InsertOrderRequest {
         pub order: Order,
@@ -378,70 +586,348 @@ pub mod jurav1_server {
         }
     }
 
+    /// Registers every Jura endpoint, including the Hyperliquid endpoints in
+    /// [hyperliquid_server](super::hyperliquid_server). The app data must be a
+    /// `web::Data<JuraState>`.
+    pub fn configure(cfg: &mut web::ServiceConfig) {
+        backtest::configure::<JuraV1, Penelope>(cfg);
+        cfg.service(tick)
+            .service(insert_order)
+            .service(delete_order)
+            .service(delete_order_by_cloid)
+            .service(update_leverage)
+            .service(hyperliquid_info)
+            .service(hyperliquid_exchange);
+    }
+}
+
+/// Serves the Hyperliquid `POST /info` and `POST /exchange` endpoints so that the official
+/// Hyperliquid SDKs can be pointed at a backtest unchanged. The backtest id is part of the path, so
+/// the SDK base url should be set to `http://[address]:[port]/backtest/[backtest_id]`. Creating a
+/// backtest and advancing the clock is managed out of band with the endpoints in
+/// [jurav1_server].
+///
+/// Each backtest has a single account so the user address sent with info requests is ignored,
+/// as are signatures and nonces sent with exchange requests. Orders are given an oid immediately
+/// but only enter the book on the next tick, so orders never return a filled status, see
+/// [hyperliquid_exchange](hyperliquid_server::hyperliquid_exchange).
+pub mod hyperliquid_server {
+    use std::collections::HashMap;
+
+    use actix_web::{post, web, HttpResponse, Result};
+    use serde::{Deserialize, Serialize};
+
+    use super::jurav1_server::{JuraState, JuraV1Error};
+    use crate::exchange::jura_v1::{AssetMeta, Order, OrderId, OrderInfo, OrderStatus, OrderWire};
+
+    type BacktestId = u64;
+
     #[derive(Debug, Deserialize, Serialize)]
-    pub struct FetchQuotesResponse {
-        pub quotes: PenelopeQuoteByDate,
+    #[serde(tag = "type", rename_all = "camelCase")]
+    pub enum InfoRequest {
+        Meta,
+        AllMids,
+        OpenOrders { user: String },
+        OrderStatus { user: String, oid: OidOrCloid },
+        UserFills { user: String },
+        ClearinghouseState { user: String },
+        L2Book { coin: String },
     }
 
-    #[get("/backtest/{backtest_id}/fetch_quotes")]
-    pub async fn fetch_quotes(
-        app: web::Data<JuraState>,
-        path: Path<(BacktestId,)>,
-    ) -> Result<web::Json<FetchQuotesResponse>, JuraV1Error> {
-        let jura = app.lock().unwrap();
-        let (backtest_id,) = path.into_inner();
+    /// Orders that are not resting and were not cancelled by the exchange are unknown.
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(tag = "status", rename_all = "camelCase")]
+    pub enum OrderStatusResponse {
+        Order { order: OrderInfo },
+        UnknownOid,
+    }
 
-        if let Some(quotes) = jura.fetch_quotes(backtest_id) {
-            Ok(web::Json(FetchQuotesResponse {
-                quotes: quotes.clone(),
-            }))
-        } else {
-            Err(JuraV1Error::UnknownBacktest)
-        }
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    pub struct Meta {
+        pub universe: Vec<AssetMeta>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    pub struct L2Level {
+        pub px: String,
+        pub sz: String,
+        pub n: u32,
+    }
+
+    /// Only the top of the book is known, sizes are zero when the dataset doesn't have them.
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    pub struct L2Book {
+        pub coin: String,
+        pub time: i64,
+        pub levels: Vec<Vec<L2Level>>,
     }
 
     #[derive(Debug, Deserialize, Serialize)]
-    pub struct InitResponse {
-        pub backtest_id: BacktestId,
+    pub struct CancelRequest {
+        pub a: u64,
+        pub o: OrderId,
     }
 
-    #[get("/init/{dataset_name}")]
-    pub async fn init(
-        app: web::Data<JuraState>,
-        path: Path<(String,)>,
-    ) -> Result<web::Json<InitResponse>, JuraV1Error> {
-        let mut jura = app.lock().unwrap();
-        let (dataset_name,) = path.into_inner();
+    #[derive(Debug, Deserialize, Serialize)]
+    pub struct CancelByCloidRequest {
+        pub asset: u64,
+        pub cloid: String,
+    }
 
-        if let Some(backtest_id) = jura.init(dataset_name) {
-            Ok(web::Json(InitResponse { backtest_id }))
-        } else {
-            Err(JuraV1Error::UnknownDataset)
-        }
+    #[derive(Debug, Deserialize, Serialize)]
+    #[serde(untagged)]
+    pub enum OidOrCloid {
+        Oid(OrderId),
+        Cloid(String),
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    #[serde(tag = "type", rename_all = "camelCase")]
+    pub enum Action {
+        Order {
+            orders: Vec<OrderWire>,
+            #[serde(default)]
+            grouping: Option<String>,
+        },
+        Cancel {
+            cancels: Vec<CancelRequest>,
+        },
+        CancelByCloid {
+            cancels: Vec<CancelByCloidRequest>,
+        },
+        Modify {
+            oid: OidOrCloid,
+            order: OrderWire,
+        },
     }
 
     #[derive(Debug, Deserialize, Serialize)]
-    pub struct InfoResponse {
-        pub version: String,
-        pub dataset: String,
+    #[serde(rename_all = "camelCase")]
+    pub struct ExchangeRequest {
+        pub action: Action,
+        #[serde(default)]
+        pub nonce: u64,
+        #[serde(default)]
+        pub signature: Option<serde_json::Value>,
+        #[serde(default)]
+        pub vault_address: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum CancelStatus {
+        Success,
+        Error(String),
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    pub struct Statuses<T> {
+        pub statuses: Vec<T>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(tag = "type", rename_all = "camelCase")]
+    pub enum ExchangeResponseData {
+        Order { data: Statuses<OrderStatus> },
+        Cancel { data: Statuses<CancelStatus> },
+        Default,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(untagged)]
+    pub enum ExchangeResponseBody {
+        Data(ExchangeResponseData),
+        Error(String),
     }
 
-    #[get("/backtest/{backtest_id}/info")]
-    pub async fn info(
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    pub struct ExchangeResponse {
+        pub status: String,
+        pub response: ExchangeResponseBody,
+    }
+
+    impl ExchangeResponse {
+        fn ok(data: ExchangeResponseData) -> Self {
+            Self {
+                status: "ok".to_string(),
+                response: ExchangeResponseBody::Data(data),
+            }
+        }
+
+        fn err(reason: String) -> Self {
+            Self {
+                status: "err".to_string(),
+                response: ExchangeResponseBody::Error(reason),
+            }
+        }
+    }
+
+    const CANCEL_ERROR: &str = "Order was never placed, already canceled, or filled.";
+
+    #[post("/backtest/{backtest_id}/info")]
+    pub async fn hyperliquid_info(
         app: web::Data<JuraState>,
-        path: Path<(BacktestId,)>,
-    ) -> Result<web::Json<InfoResponse>, JuraV1Error> {
+        path: web::Path<(BacktestId,)>,
+        request: web::Json<InfoRequest>,
+    ) -> Result<HttpResponse, JuraV1Error> {
         let jura = app.lock().unwrap();
         let (backtest_id,) = path.into_inner();
 
-        if let Some(resp) = jura.backtests.get(&backtest_id) {
-            Ok(web::Json(InfoResponse {
-                version: "v1".to_string(),
-                dataset: resp.dataset_name.clone(),
-            }))
-        } else {
-            Err(JuraV1Error::UnknownBacktest)
+        let backtest = jura
+            .backtests
+            .get(&backtest_id)
+            .ok_or(JuraV1Error::UnknownBacktest)?;
+        let quotes = jura.fetch_quotes(backtest_id).unwrap_or_default();
+
+        let response = match request.into_inner() {
+            InfoRequest::Meta => HttpResponse::Ok().json(Meta {
+                universe: backtest.exchange.get_universe().get_assets().to_vec(),
+            }),
+            InfoRequest::AllMids => {
+                let mids: HashMap<String, String> = quotes
+                    .values()
+                    .map(|quote| {
+                        (
+                            quote.symbol.clone(),
+                            ((quote.bid + quote.ask) / 2.0).to_string(),
+                        )
+                    })
+                    .collect();
+                HttpResponse::Ok().json(mids)
+            }
+            InfoRequest::OpenOrders { .. } => {
+                HttpResponse::Ok().json(backtest.exchange.get_open_orders())
+            }
+            InfoRequest::OrderStatus { oid, .. } => {
+                let order = match oid {
+                    OidOrCloid::Oid(oid) => backtest.exchange.get_order_status(oid),
+                    OidOrCloid::Cloid(cloid) => backtest.exchange.get_order_status_by_cloid(&cloid),
+                };
+                HttpResponse::Ok().json(match order {
+                    Some(order) => OrderStatusResponse::Order { order },
+                    None => OrderStatusResponse::UnknownOid,
+                })
+            }
+            InfoRequest::UserFills { .. } => {
+                // Hyperliquid returns the most recent fills first
+                let fills: Vec<_> = backtest.exchange.get_trade_log().iter().rev().collect();
+                HttpResponse::Ok().json(fills)
+            }
+            InfoRequest::ClearinghouseState { .. } => {
+                HttpResponse::Ok().json(backtest.exchange.get_clearinghouse_state(backtest.date))
+            }
+            InfoRequest::L2Book { coin } => match quotes.get(&coin) {
+                Some(quote) => HttpResponse::Ok().json(L2Book {
+                    coin,
+                    time: quote.date,
+                    levels: vec![
+                        vec![L2Level {
+                            px: quote.bid.to_string(),
+                            sz: quote.bid_size.unwrap_or_default().to_string(),
+                            n: 1,
+                        }],
+                        vec![L2Level {
+                            px: quote.ask.to_string(),
+                            sz: quote.ask_size.unwrap_or_default().to_string(),
+                            n: 1,
+                        }],
+                    ],
+                }),
+                None => HttpResponse::Ok().json(serde_json::Value::Null),
+            },
+        };
+        Ok(response)
+    }
+
+    /// Orders are validated and given an oid when they are placed but only execute on the next
+    /// tick. Hyperliquid returns `filled`, or an error if nothing matched, for Ioc orders and
+    /// market orders sent as Ioc. Here every valid order returns `resting`, an Ioc order that
+    /// doesn't fill on the next tick is cancelled with `iocCancelRejected`, which is reported by
+    /// tick and the `orderStatus` info request.
+    #[post("/backtest/{backtest_id}/exchange")]
+    pub async fn hyperliquid_exchange(
+        app: web::Data<JuraState>,
+        path: web::Path<(BacktestId,)>,
+        request: web::Json<ExchangeRequest>,
+    ) -> Result<web::Json<ExchangeResponse>, JuraV1Error> {
+        let mut jura = app.lock().unwrap();
+        let (backtest_id,) = path.into_inner();
+        if !jura.backtests.contains_key(&backtest_id) {
+            return Err(JuraV1Error::UnknownBacktest);
         }
+
+        let response = match request.into_inner().action {
+            Action::Order { orders, .. } => {
+                let mut statuses = Vec::with_capacity(orders.len());
+                for wire in orders {
+                    let status = match Order::try_from(wire) {
+                        Ok(order) => jura
+                            .place_order(order, backtest_id)
+                            .ok_or(JuraV1Error::UnknownBacktest)?,
+                        Err(reason) => OrderStatus::Error(reason),
+                    };
+                    statuses.push(status);
+                }
+                ExchangeResponse::ok(ExchangeResponseData::Order {
+                    data: Statuses { statuses },
+                })
+            }
+            Action::Cancel { cancels } => {
+                let mut statuses = Vec::with_capacity(cancels.len());
+                for cancel in cancels {
+                    let deleted = jura
+                        .backtests
+                        .get_mut(&backtest_id)
+                        .is_some_and(|backtest| backtest.exchange.delete_order(cancel.a, cancel.o));
+                    statuses.push(if deleted {
+                        CancelStatus::Success
+                    } else {
+                        CancelStatus::Error(CANCEL_ERROR.to_string())
+                    });
+                }
+                ExchangeResponse::ok(ExchangeResponseData::Cancel {
+                    data: Statuses { statuses },
+                })
+            }
+            Action::CancelByCloid { cancels } => {
+                let mut statuses = Vec::with_capacity(cancels.len());
+                for cancel in cancels {
+                    let deleted =
+                        jura.delete_order_by_cloid(cancel.asset, &cancel.cloid, backtest_id);
+                    statuses.push(if deleted == Some(true) {
+                        CancelStatus::Success
+                    } else {
+                        CancelStatus::Error(CANCEL_ERROR.to_string())
+                    });
+                }
+                ExchangeResponse::ok(ExchangeResponseData::Cancel {
+                    data: Statuses { statuses },
+                })
+            }
+            Action::Modify { oid, order } => {
+                let order_id = match oid {
+                    OidOrCloid::Oid(order_id) => Some(order_id),
+                    OidOrCloid::Cloid(cloid) => jura
+                        .backtests
+                        .get(&backtest_id)
+                        .and_then(|backtest| backtest.exchange.get_oid_by_cloid(&cloid)),
+                };
+                let status = match (order_id, Order::try_from(order)) {
+                    (Some(order_id), Ok(order)) => jura
+                        .modify_order(order_id, order, backtest_id)
+                        .ok_or(JuraV1Error::UnknownBacktest)?,
+                    (None, _) => {
+                        OrderStatus::Error("Cannot modify canceled or filled order".to_string())
+                    }
+                    (_, Err(reason)) => OrderStatus::Error(reason),
+                };
+                match status {
+                    OrderStatus::Error(reason) => ExchangeResponse::err(reason),
+                    _ => ExchangeResponse::ok(ExchangeResponseData::Default),
+                }
+            }
+        };
+        Ok(web::Json(response))
     }
 }
 
@@ -451,9 +937,10 @@ mod tests {
 
     use super::jurav1_server::*;
     use super::AppState;
-    use crate::exchange::jura_v1::Order;
+    use crate::exchange::jura_v1::{AssetMeta, JuraV1, Order, OrderStatus, Universe};
     use crate::input::penelope::Penelope;
 
+    use std::collections::HashMap;
     use std::sync::Mutex;
 
     #[actix_web::test]
@@ -465,17 +952,7 @@ mod tests {
         let app_state = Mutex::new(state);
         let jura_state = web::Data::new(app_state);
 
-        let app = test::init_service(
-            App::new()
-                .app_data(jura_state)
-                .service(info)
-                .service(init)
-                .service(fetch_quotes)
-                .service(tick)
-                .service(insert_order)
-                .service(delete_order),
-        )
-        .await;
+        let app = test::init_service(App::new().app_data(jura_state).configure(configure)).await;
 
         let req = test::TestRequest::get()
             .uri(format!("/init/{dataset_name}").as_str())
@@ -494,9 +971,11 @@ mod tests {
             .to_request();
         let _resp2: TickResponse = test::call_and_read_body_json(&app, req2).await;
 
+        //Random quotes are between 90 and 100, market orders only tolerate 10% slippage from
+        //this price
         let req3 = test::TestRequest::post()
             .set_json(InsertOrderRequest {
-                order: Order::market_buy(0, "100.0", "90.00"),
+                order: Order::market_buy(0, "100.0", "100.00"),
             })
             .uri(format!("/backtest/{backtest_id}/insert_order").as_str())
             .to_request();
@@ -515,4 +994,374 @@ mod tests {
         assert!(resp5.executed_trades.len() == 1);
         assert!(resp5.executed_trades.first().unwrap().coin == "0")
     }
+
+    #[actix_web::test]
+    async fn test_that_rejected_post_only_order_is_returned_to_client() {
+        let mut jura = Penelope::new();
+        jura.add_quote(101.00, 102.00, 100, "0");
+        jura.add_quote(102.00, 103.00, 101, "0");
+        let dataset_name = "fake";
+        let state = AppState::single(dataset_name, jura);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Mutex::new(state)))
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(format!("/init/{dataset_name}").as_str())
+            .to_request();
+        let resp: InitResponse = test::call_and_read_body_json(&app, req).await;
+        let backtest_id = resp.backtest_id;
+
+        let req1 = test::TestRequest::post()
+            .set_json(InsertOrderRequest {
+                order: Order::post_only_buy(0_u64, "100.0", "102.00"),
+            })
+            .uri(format!("/backtest/{backtest_id}/insert_order").as_str())
+            .to_request();
+        test::call_and_read_body(&app, req1).await;
+
+        let req2 = test::TestRequest::get()
+            .uri(format!("/backtest/{backtest_id}/tick").as_str())
+            .to_request();
+        let resp2: TickResponse = test::call_and_read_body_json(&app, req2).await;
+
+        assert_eq!(resp2.inserted_orders.len(), 1);
+        assert!(matches!(
+            resp2.order_statuses.first(),
+            Some(OrderStatus::Error(..))
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_that_order_can_be_cancelled_by_cloid() {
+        let mut jura = Penelope::new();
+        jura.add_quote(101.00, 102.00, 100, "0");
+        jura.add_quote(102.00, 103.00, 101, "0");
+        let dataset_name = "fake";
+        let state = AppState::single(dataset_name, jura);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Mutex::new(state)))
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(format!("/init/{dataset_name}").as_str())
+            .to_request();
+        let resp: InitResponse = test::call_and_read_body_json(&app, req).await;
+        let backtest_id = resp.backtest_id;
+
+        let req1 = test::TestRequest::post()
+            .set_json(InsertOrderRequest {
+                order: Order::limit_buy(0_u64, "100.0", "90.00").with_cloid("0x1"),
+            })
+            .uri(format!("/backtest/{backtest_id}/insert_order").as_str())
+            .to_request();
+        test::call_and_read_body(&app, req1).await;
+
+        let req2 = test::TestRequest::get()
+            .uri(format!("/backtest/{backtest_id}/tick").as_str())
+            .to_request();
+        let resp2: TickResponse = test::call_and_read_body_json(&app, req2).await;
+        assert!(matches!(
+            resp2.order_statuses.first(),
+            Some(OrderStatus::Resting { cloid: Some(cloid), .. }) if cloid == "0x1"
+        ));
+
+        let delete = || {
+            test::TestRequest::post()
+                .set_json(DeleteOrderByCloidRequest {
+                    asset: 0,
+                    cloid: "0x1".to_string(),
+                })
+                .uri(format!("/backtest/{backtest_id}/delete_order_by_cloid").as_str())
+                .to_request()
+        };
+        let resp3 = test::call_service(&app, delete()).await;
+        assert!(resp3.status().is_success());
+
+        let resp4 = test::call_service(&app, delete()).await;
+        assert!(resp4.status().is_client_error());
+    }
+
+    #[actix_web::test]
+    async fn test_that_funding_payments_are_returned_to_client() {
+        let mut jura = Penelope::new();
+        jura.add_quote(101.00, 102.00, 100, "0");
+        jura.add_quote(102.00, 103.00, 101, "0");
+        jura.add_quote(104.00, 105.00, 102, "0");
+        jura.add_funding_rate(0.001, 101, "0");
+        jura.add_funding_rate(0.001, 102, "0");
+        let dataset_name = "fake";
+        let state = AppState::single(dataset_name, jura);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Mutex::new(state)))
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(format!("/init/{dataset_name}").as_str())
+            .to_request();
+        let resp: InitResponse = test::call_and_read_body_json(&app, req).await;
+        let backtest_id = resp.backtest_id;
+
+        let req1 = test::TestRequest::post()
+            .set_json(InsertOrderRequest {
+                order: Order::market_buy(0_u64, "100.0", "102.00"),
+            })
+            .uri(format!("/backtest/{backtest_id}/insert_order").as_str())
+            .to_request();
+        test::call_and_read_body(&app, req1).await;
+
+        let tick_req = || {
+            test::TestRequest::get()
+                .uri(format!("/backtest/{backtest_id}/tick").as_str())
+                .to_request()
+        };
+        let _resp2: TickResponse = test::call_and_read_body_json(&app, tick_req()).await;
+        //Order executes at 101, position is opened after funding is paid
+        let resp3: TickResponse = test::call_and_read_body_json(&app, tick_req()).await;
+        assert_eq!(resp3.executed_trades.len(), 1);
+        assert!(resp3.user_funding.is_empty());
+
+        let resp4: TickResponse = test::call_and_read_body_json(&app, tick_req()).await;
+        assert_eq!(resp4.user_funding.len(), 1);
+        assert_eq!(resp4.user_funding[0].funding_rate, "0.001");
+    }
+
+    #[actix_web::test]
+    async fn test_that_hyperliquid_endpoints_trade_and_report_state() {
+        use super::hyperliquid_server::*;
+        use crate::exchange::jura_v1::{ClearinghouseState, Fill, OpenOrder};
+
+        let mut jura = Penelope::new();
+        jura.add_quote(101.00, 102.00, 100, "0");
+        jura.add_quote(102.00, 103.00, 101, "0");
+        jura.add_quote(105.00, 106.00, 102, "0");
+        let dataset_name = "fake";
+        let state = AppState::single(dataset_name, jura);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Mutex::new(state)))
+                .configure(configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(format!("/init/{dataset_name}").as_str())
+            .to_request();
+        let resp: InitResponse = test::call_and_read_body_json(&app, req).await;
+        let backtest_id = resp.backtest_id;
+
+        let post = |endpoint: &str, body: &str| {
+            test::TestRequest::post()
+                .uri(format!("/backtest/{backtest_id}/{endpoint}").as_str())
+                .insert_header(("content-type", "application/json"))
+                .set_payload(body.to_string())
+                .to_request()
+        };
+        let tick_req = || {
+            test::TestRequest::get()
+                .uri(format!("/backtest/{backtest_id}/tick").as_str())
+                .to_request()
+        };
+
+        let meta: Meta =
+            test::call_and_read_body_json(&app, post("info", r#"{"type":"meta"}"#)).await;
+        assert_eq!(meta.universe[0].name, "0");
+
+        let mids: HashMap<String, String> =
+            test::call_and_read_body_json(&app, post("info", r#"{"type":"allMids"}"#)).await;
+        assert_eq!(mids.get("0"), Some(&"101.5".to_string()));
+
+        let book: L2Book =
+            test::call_and_read_body_json(&app, post("info", r#"{"type":"l2Book","coin":"0"}"#))
+                .await;
+        assert_eq!(book.levels[1][0].px, "102");
+        assert_eq!(book.levels[1][0].sz, "0");
+
+        let order = r#"{"action":{"type":"order","orders":[
+            {"a":0,"b":true,"p":"103","s":"10","r":false,"t":{"limit":{"tif":"Gtc"}},"c":"0x1"},
+            {"a":0,"b":true,"p":"90","s":"10","r":false,"t":{"limit":{"tif":"Gtc"}}}
+        ],"grouping":"na"},"nonce":1,"signature":{"r":"0x0","s":"0x0","v":27},"vaultAddress":null}"#;
+        let resp: ExchangeResponse =
+            test::call_and_read_body_json(&app, post("exchange", order)).await;
+        assert_eq!(resp.status, "ok");
+        assert_eq!(
+            resp.response,
+            ExchangeResponseBody::Data(ExchangeResponseData::Order {
+                data: Statuses {
+                    statuses: vec![
+                        OrderStatus::Resting {
+                            oid: 0,
+                            cloid: Some("0x1".to_string())
+                        },
+                        OrderStatus::Resting {
+                            oid: 1,
+                            cloid: None
+                        }
+                    ]
+                }
+            })
+        );
+
+        let open_orders: Vec<OpenOrder> = test::call_and_read_body_json(
+            &app,
+            post("info", r#"{"type":"openOrders","user":"0x0"}"#),
+        )
+        .await;
+        assert_eq!(open_orders.len(), 2);
+
+        let status: OrderStatusResponse = test::call_and_read_body_json(
+            &app,
+            post("info", r#"{"type":"orderStatus","user":"0x0","oid":"0x1"}"#),
+        )
+        .await;
+        assert!(matches!(status, OrderStatusResponse::Order { order } if order.status == "open"));
+        let status: OrderStatusResponse = test::call_and_read_body_json(
+            &app,
+            post("info", r#"{"type":"orderStatus","user":"0x0","oid":99}"#),
+        )
+        .await;
+        assert_eq!(status, OrderStatusResponse::UnknownOid);
+
+        let cancel =
+            r#"{"action":{"type":"cancel","cancels":[{"a":0,"o":1},{"a":0,"o":99}]},"nonce":2}"#;
+        let resp: ExchangeResponse =
+            test::call_and_read_body_json(&app, post("exchange", cancel)).await;
+        assert!(matches!(
+            resp.response,
+            ExchangeResponseBody::Data(ExchangeResponseData::Cancel { data })
+                if data.statuses[0] == CancelStatus::Success
+                    && matches!(data.statuses[1], CancelStatus::Error(..))
+        ));
+
+        //Order enters the book on the first tick and fills on the second
+        let _resp: TickResponse = test::call_and_read_body_json(&app, tick_req()).await;
+        let resp: TickResponse = test::call_and_read_body_json(&app, tick_req()).await;
+        assert_eq!(resp.executed_trades.len(), 1);
+
+        let fills: Vec<Fill> = test::call_and_read_body_json(
+            &app,
+            post("info", r#"{"type":"userFills","user":"0x0"}"#),
+        )
+        .await;
+        assert_eq!(fills[0].cloid, Some("0x1".to_string()));
+
+        let state: ClearinghouseState = test::call_and_read_body_json(
+            &app,
+            post("info", r#"{"type":"clearinghouseState","user":"0x0"}"#),
+        )
+        .await;
+        assert_eq!(state.asset_positions[0].position.szi, "10");
+
+        let modify = r#"{"action":{"type":"modify","oid":"0x1","order":
+            {"a":0,"b":true,"p":"90","s":"10","r":false,"t":{"limit":{"tif":"Gtc"}}}},"nonce":3}"#;
+        let resp: ExchangeResponse =
+            test::call_and_read_body_json(&app, post("exchange", modify)).await;
+        assert_eq!(resp.status, "err");
+    }
+
+    #[actix_web::test]
+    async fn test_that_now_endpoint_returns_date_until_dataset_ends() {
+        let mut jura = Penelope::new();
+        jura.add_quote(101.00, 102.00, 100, "0");
+        jura.add_quote(102.00, 103.00, 101, "0");
+        let state = AppState::single("fake", jura);
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(Mutex::new(state)))
+                .configure(configure),
+        )
+        .await;
+
+        let now_req = || test::TestRequest::get().uri("/backtest/0/now").to_request();
+        let resp: NowResponse = test::call_and_read_body_json(&app, now_req()).await;
+        assert_eq!(resp.now, 100);
+        assert!(resp.has_next);
+
+        let req = test::TestRequest::get()
+            .uri("/backtest/0/tick")
+            .to_request();
+        let _resp: TickResponse = test::call_and_read_body_json(&app, req).await;
+        let resp: NowResponse = test::call_and_read_body_json(&app, now_req()).await;
+        assert_eq!(resp.now, 101);
+    }
+
+    #[actix_web::test]
+    async fn test_that_test_client_runs_backtest_without_server() {
+        use super::jurav1_client::{JuraClient, TestClient};
+
+        let mut jura = Penelope::new();
+        jura.add_quote(101.00, 102.00, 100, "0");
+        jura.add_quote(102.00, 103.00, 101, "0");
+        jura.add_quote(105.00, 106.00, 102, "0");
+        let mut client = TestClient::single("fake", jura);
+
+        let backtest_id = client.init("fake".to_string()).await.unwrap().backtest_id;
+        assert!(client.init("unknown".to_string()).await.is_err());
+        assert_eq!(client.info(backtest_id).await.unwrap().dataset, "fake");
+        assert_eq!(client.now(backtest_id).await.unwrap().now, 100);
+
+        client
+            .insert_order(
+                Order::market_buy(0_u64, "100.0", "102.00").with_cloid("0x1"),
+                backtest_id,
+            )
+            .await
+            .unwrap();
+        assert!(client
+            .delete_order_by_cloid(0, "0x2".to_string(), backtest_id)
+            .await
+            .is_err());
+        assert!(client
+            .update_leverage(0, true, 0, backtest_id)
+            .await
+            .is_err());
+
+        let resp = client.tick(backtest_id).await.unwrap();
+        assert!(resp.has_next);
+        let resp = client.tick(backtest_id).await.unwrap();
+        assert_eq!(resp.executed_trades.len(), 1);
+        assert_eq!(resp.executed_trades[0].cloid, Some("0x1".to_string()));
+
+        let resp = client.tick(backtest_id).await.unwrap();
+        assert!(!resp.has_next);
+        assert!(!client.now(backtest_id).await.unwrap().has_next);
+    }
+
+    #[actix_web::test]
+    async fn test_that_dataset_universe_is_used_for_new_backtests() {
+        let mut datasets = HashMap::new();
+        datasets.insert("first".to_string(), Penelope::random(10, vec!["ABC"]));
+        datasets.insert("second".to_string(), Penelope::random(10, vec!["ABC"]));
+
+        let mut meta = AssetMeta::new("ABC");
+        meta.max_leverage = 5;
+        let mut state = AppState::create(&mut datasets)
+            .with_exchange(JuraV1::new().with_balance(1_000.0))
+            .with_universe("second", Universe::new(vec![meta.clone()]));
+
+        let first = state.init("first".to_string()).unwrap();
+        let second = state.init("second".to_string()).unwrap();
+
+        let first = &state.backtests[&first].exchange;
+        assert_eq!(first.get_universe().get_assets()[0].max_leverage, 50);
+        assert_eq!(first.get_balance(), 1_000.0);
+
+        let second = &state.backtests[&second].exchange;
+        assert_eq!(second.get_universe().get_assets(), &[meta]);
+        assert_eq!(second.get_balance(), 1_000.0);
+    }
 }
@@ -1,2 +1,4 @@
+pub mod backtest;
+pub mod config;
 pub mod jura;
 pub mod uist;
@@ -1,146 +1,10 @@
-use std::collections::HashMap;
+use crate::exchange::uist_v1::UistV1;
 
-use crate::exchange::uist_v1::{Order, OrderId, Trade, UistV1};
-use crate::input::penelope::{Penelope, PenelopeQuoteByDate};
+use super::backtest::BacktestId;
 
-type BacktestId = u64;
-
-pub struct BacktestState {
-    pub id: BacktestId,
-    pub date: i64,
-    pub pos: usize,
-    pub exchange: UistV1,
-    pub dataset_name: String,
-}
-
-pub struct AppState {
-    pub backtests: HashMap<BacktestId, BacktestState>,
-    pub last: BacktestId,
-    pub datasets: HashMap<String, Penelope>,
-}
-
-impl AppState {
-    pub fn create(datasets: &mut HashMap<String, Penelope>) -> Self {
-        Self {
-            backtests: HashMap::new(),
-            last: 0,
-            datasets: std::mem::take(datasets),
-        }
-    }
-
-    pub fn single(name: &str, data: Penelope) -> Self {
-        let exchange = UistV1::new();
-        let backtest = BacktestState {
-            id: 0,
-            date: *data.get_date(0).unwrap(),
-            pos: 0,
-            exchange,
-            dataset_name: name.into(),
-        };
-
-        let mut datasets = HashMap::new();
-        datasets.insert(name.into(), data);
-
-        let mut backtests = HashMap::new();
-        backtests.insert(0, backtest);
-
-        Self {
-            backtests,
-            last: 1,
-            datasets,
-        }
-    }
-
-    pub fn tick(&mut self, backtest_id: BacktestId) -> Option<(bool, Vec<Trade>, Vec<Order>)> {
-        if let Some(backtest) = self.backtests.get_mut(&backtest_id) {
-            if let Some(dataset) = self.datasets.get(&backtest.dataset_name) {
-                let mut has_next = false;
-                let mut executed_trades = Vec::new();
-                let mut inserted_orders = Vec::new();
-
-                if let Some(quotes) = dataset.get_quotes(&backtest.date) {
-                    let mut res = backtest.exchange.tick(quotes);
-                    executed_trades.append(&mut res.0);
-                    inserted_orders.append(&mut res.1);
-                }
-
-                let new_pos = backtest.pos + 1;
-                if dataset.has_next(new_pos) {
-                    has_next = true;
-                    backtest.date = *dataset.get_date(new_pos).unwrap();
-                }
-                backtest.pos = new_pos;
-                return Some((has_next, executed_trades, inserted_orders));
-            }
-        }
-        None
-    }
-
-    pub fn fetch_quotes(&self, backtest_id: BacktestId) -> Option<&PenelopeQuoteByDate> {
-        if let Some(backtest) = self.backtests.get(&backtest_id) {
-            if let Some(dataset) = self.datasets.get(&backtest.dataset_name) {
-                return dataset.get_quotes(&backtest.date);
-            }
-        }
-        None
-    }
-
-    pub fn init(&mut self, dataset_name: String) -> Option<BacktestId> {
-        if let Some(dataset) = self.datasets.get(&dataset_name) {
-            let new_id = self.last + 1;
-            let exchange = UistV1::new();
-            let backtest = BacktestState {
-                id: new_id,
-                date: *dataset.get_date(0).unwrap(),
-                pos: 0,
-                exchange,
-                dataset_name,
-            };
-            self.backtests.insert(new_id, backtest);
-            return Some(new_id);
-        }
-        None
-    }
-
-    pub fn insert_order(&mut self, order: Order, backtest_id: BacktestId) -> Option<()> {
-        if let Some(backtest) = self.backtests.get_mut(&backtest_id) {
-            backtest.exchange.insert_order(order);
-            return Some(());
-        }
-        None
-    }
-
-    pub fn delete_order(&mut self, order_id: OrderId, backtest_id: BacktestId) -> Option<()> {
-        if let Some(backtest) = self.backtests.get_mut(&backtest_id) {
-            backtest.exchange.delete_order(order_id);
-            return Some(());
-        }
-        None
-    }
-
-    pub fn new_backtest(&mut self, dataset_name: &str) -> Option<BacktestId> {
-        let new_id = self.last + 1;
-
-        // Check that dataset exists
-        if let Some(dataset) = self.datasets.get(dataset_name) {
-            let exchange = UistV1::new();
-
-            let backtest = BacktestState {
-                id: new_id,
-                date: *dataset.get_date(0).unwrap(),
-                pos: 0,
-                exchange,
-                dataset_name: dataset_name.into(),
-            };
-
-            self.backtests.insert(new_id, backtest);
-
-            self.last = new_id;
-            return Some(new_id);
-        }
-        None
-    }
-}
+pub type TickResult = super::backtest::TickResult<UistV1>;
+pub type BacktestState = super::backtest::BacktestState<UistV1>;
+pub type AppState = super::backtest::AppState<UistV1>;
 
 pub mod uistv1_client {
 
@@ -150,8 +14,8 @@ pub mod uistv1_client {
     use anyhow::{Error, Result};
 
     use super::uistv1_server::{
-        DeleteOrderRequest, FetchQuotesResponse, InfoResponse, InitResponse, InsertOrderRequest,
-        NowResponse, TickResponse, UistV1Error,
+        DeleteOrderRequest, DeleteOrderResponse, FetchQuotesResponse, InfoResponse, InitResponse,
+        InsertOrderRequest, InsertOrderResponse, NowResponse, TickResponse, UistV1Error,
     };
     use super::AppState;
 
@@ -166,12 +30,12 @@ pub mod uistv1_client {
             &mut self,
             order_id: OrderId,
             backtest_id: BacktestId,
-        ) -> impl Future<Output = Result<()>>;
+        ) -> impl Future<Output = Result<DeleteOrderResponse>>;
         fn insert_order(
             &mut self,
             order: Order,
             backtest_id: BacktestId,
-        ) -> impl Future<Output = Result<()>>;
+        ) -> impl Future<Output = Result<InsertOrderResponse>>;
         fn fetch_quotes(
             &mut self,
             backtest_id: BacktestId,
@@ -197,9 +61,10 @@ pub mod uistv1_client {
         fn tick(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<TickResponse>> {
             if let Some(resp) = self.state.tick(backtest_id) {
                 future::ready(Ok(TickResponse {
-                    inserted_orders: resp.2,
-                    executed_trades: resp.1,
-                    has_next: resp.0,
+                    order_updates: resp.events,
+                    inserted_orders: resp.inserted_orders,
+                    executed_trades: resp.fills,
+                    has_next: resp.has_next,
                 }))
             } else {
                 future::ready(Err(Error::new(UistV1Error::UnknownBacktest)))
@@ -210,9 +75,9 @@ pub mod uistv1_client {
             &mut self,
             order: Order,
             backtest_id: BacktestId,
-        ) -> impl Future<Output = Result<()>> {
-            if let Some(()) = self.state.insert_order(order, backtest_id) {
-                future::ready(Ok(()))
+        ) -> impl Future<Output = Result<InsertOrderResponse>> {
+            if let Some(order_id) = self.state.insert_order(order, backtest_id) {
+                future::ready(Ok(InsertOrderResponse { order_id }))
             } else {
                 future::ready(Err(Error::new(UistV1Error::UnknownBacktest)))
             }
@@ -222,11 +87,11 @@ pub mod uistv1_client {
             &mut self,
             order_id: OrderId,
             backtest_id: BacktestId,
-        ) -> impl Future<Output = Result<()>> {
-            if let Some(()) = self.state.delete_order(order_id, backtest_id) {
-                future::ready(Ok(()))
-            } else {
-                future::ready(Err(Error::new(UistV1Error::UnknownBacktest)))
+        ) -> impl Future<Output = Result<DeleteOrderResponse>> {
+            match self.state.delete_order(order_id, backtest_id) {
+                Some(Some(order_update)) => future::ready(Ok(DeleteOrderResponse { order_update })),
+                Some(None) => future::ready(Err(Error::new(UistV1Error::UnknownOrder))),
+                None => future::ready(Err(Error::new(UistV1Error::UnknownBacktest))),
             }
         }
 
@@ -235,9 +100,7 @@ pub mod uistv1_client {
             backtest_id: BacktestId,
         ) -> impl Future<Output = Result<FetchQuotesResponse>> {
             if let Some(quotes) = self.state.fetch_quotes(backtest_id) {
-                future::ready(Ok(FetchQuotesResponse {
-                    quotes: quotes.to_owned(),
-                }))
+                future::ready(Ok(FetchQuotesResponse { quotes }))
             } else {
                 future::ready(Err(Error::new(UistV1Error::UnknownBacktest)))
             }
@@ -255,17 +118,8 @@ pub mod uistv1_client {
         }
 
         fn now(&mut self, backtest_id: BacktestId) -> impl Future<Output = Result<NowResponse>> {
-            if let Some(backtest) = self.state.backtests.get(&backtest_id) {
-                if let Some(dataset) = self.state.datasets.get(&backtest.dataset_name) {
-                    let now = backtest.date;
-                    let mut has_next = false;
-                    if dataset.has_next(backtest.pos) {
-                        has_next = true;
-                    }
-                    future::ready(Ok(NowResponse { now, has_next }))
-                } else {
-                    future::ready(Err(Error::new(UistV1Error::UnknownDataset)))
-                }
+            if let Some((now, has_next)) = self.state.now(backtest_id) {
+                future::ready(Ok(NowResponse { now, has_next }))
             } else {
                 future::ready(Err(Error::new(UistV1Error::UnknownBacktest)))
             }
@@ -297,7 +151,11 @@ pub mod uistv1_client {
                 .await?)
         }
 
-        async fn delete_order(&mut self, order_id: OrderId, backtest_id: BacktestId) -> Result<()> {
+        async fn delete_order(
+            &mut self,
+            order_id: OrderId,
+            backtest_id: BacktestId,
+        ) -> Result<DeleteOrderResponse> {
             let req = DeleteOrderRequest { order_id };
             Ok(self
                 .client
@@ -305,11 +163,15 @@ pub mod uistv1_client {
                 .json(&req)
                 .send()
                 .await?
-                .json::<()>()
+                .json::<DeleteOrderResponse>()
                 .await?)
         }
 
-        async fn insert_order(&mut self, order: Order, backtest_id: BacktestId) -> Result<()> {
+        async fn insert_order(
+            &mut self,
+            order: Order,
+            backtest_id: BacktestId,
+        ) -> Result<InsertOrderResponse> {
             let req = InsertOrderRequest { order };
             Ok(self
                 .client
@@ -317,7 +179,7 @@ pub mod uistv1_client {
                 .json(&req)
                 .send()
                 .await?
-                .json::<()>()
+                .json::<InsertOrderResponse>()
                 .await?)
         }
 
@@ -377,18 +239,24 @@ pub mod uistv1_server {
     use serde::{Deserialize, Serialize};
     use std::{error::Error, sync::Mutex};
 
-    use crate::exchange::uist_v1::{Order, OrderId, Trade};
-    use crate::input::penelope::PenelopeQuoteByDate;
+    use crate::exchange::uist_v1::{Order, OrderId, OrderUpdate, Trade, UistQuote, UistV1};
+    use crate::http::backtest;
+    use crate::input::penelope::Penelope;
     use actix_web::{get, post, web, ResponseError};
 
     use super::{AppState, BacktestId};
 
+    pub use crate::http::backtest::{InfoResponse, InitResponse, NowResponse};
+
+    pub type FetchQuotesResponse = backtest::FetchQuotesResponse<UistQuote>;
+
     pub type UistState = Mutex<AppState>;
 
     #[derive(Debug)]
     pub enum UistV1Error {
         UnknownBacktest,
         UnknownDataset,
+        UnknownOrder,
     }
 
     impl Error for UistV1Error {}
@@ -398,6 +266,7 @@ pub mod uistv1_server {
             match self {
                 UistV1Error::UnknownBacktest => write!(f, "UnknownBacktest"),
                 UistV1Error::UnknownDataset => write!(f, "UnknownDataset"),
+                UistV1Error::UnknownOrder => write!(f, "UnknownOrder"),
             }
         }
     }
@@ -407,6 +276,7 @@ pub mod uistv1_server {
             match self {
                 UistV1Error::UnknownBacktest => actix_web::http::StatusCode::BAD_REQUEST,
                 UistV1Error::UnknownDataset => actix_web::http::StatusCode::BAD_REQUEST,
+                UistV1Error::UnknownOrder => actix_web::http::StatusCode::BAD_REQUEST,
             }
         }
     }
@@ -416,6 +286,8 @@ pub mod uistv1_server {
         pub has_next: bool,
         pub executed_trades: Vec<Trade>,
         pub inserted_orders: Vec<Order>,
+        #[serde(default)]
+        pub order_updates: Vec<OrderUpdate>,
     }
 
     #[get("/backtest/{backtest_id}/tick")]
@@ -428,9 +300,10 @@ pub mod uistv1_server {
 
         if let Some(result) = uist.tick(backtest_id) {
             Ok(web::Json(TickResponse {
-                inserted_orders: result.2,
-                executed_trades: result.1,
-                has_next: result.0,
+                order_updates: result.events,
+                inserted_orders: result.inserted_orders,
+                executed_trades: result.fills,
+                has_next: result.has_next,
             }))
         } else {
             Err(UistV1Error::UnknownBacktest)
@@ -442,19 +315,24 @@ pub mod uistv1_server {
         pub order_id: OrderId,
     }
 
+    #[derive(Debug, Deserialize, Serialize)]
+    pub struct DeleteOrderResponse {
+        pub order_update: OrderUpdate,
+    }
+
     #[post("/backtest/{backtest_id}/delete_order")]
     pub async fn delete_order(
         app: web::Data<UistState>,
         path: web::Path<(BacktestId,)>,
         delete_order: web::Json<DeleteOrderRequest>,
-    ) -> Result<web::Json<()>, UistV1Error> {
+    ) -> Result<web::Json<DeleteOrderResponse>, UistV1Error> {
         let mut uist = app.lock().unwrap();
         let (backtest_id,) = path.into_inner();
 
-        if let Some(()) = uist.delete_order(delete_order.order_id, backtest_id) {
-            Ok(web::Json(()))
-        } else {
-            Err(UistV1Error::UnknownBacktest)
+        match uist.delete_order(delete_order.order_id, backtest_id) {
+            Some(Some(order_update)) => Ok(web::Json(DeleteOrderResponse { order_update })),
+            Some(None) => Err(UistV1Error::UnknownOrder),
+            None => Err(UistV1Error::UnknownBacktest),
         }
     }
 
@@ -463,115 +341,32 @@ pub mod uistv1_server {
         pub order: Order,
     }
 
-    #[post("/backtest/{backtest_id}/insert_order")]
-    pub async fn insert_order(
-        app: web::Data<UistState>,
-        path: web::Path<(BacktestId,)>,
-        insert_order: web::Json<InsertOrderRequest>,
-    ) -> Result<web::Json<()>, UistV1Error> {
-        let mut uist = app.lock().unwrap();
-        let (backtest_id,) = path.into_inner();
-        if let Some(()) = uist.insert_order(insert_order.order.clone(), backtest_id) {
-            Ok(web::Json(()))
-        } else {
-            Err(UistV1Error::UnknownBacktest)
-        }
-    }
-
     #[derive(Debug, Deserialize, Serialize)]
-    pub struct FetchQuotesResponse {
-        pub quotes: PenelopeQuoteByDate,
+    pub struct InsertOrderResponse {
+        pub order_id: OrderId,
     }
 
-    #[get("/backtest/{backtest_id}/fetch_quotes")]
-    pub async fn fetch_quotes(
+    #[post("/backtest/{backtest_id}/insert_order")]
+    pub async fn insert_order(
         app: web::Data<UistState>,
         path: web::Path<(BacktestId,)>,
-    ) -> Result<web::Json<FetchQuotesResponse>, UistV1Error> {
-        let uist = app.lock().unwrap();
-        let (backtest_id,) = path.into_inner();
-
-        if let Some(quotes) = uist.fetch_quotes(backtest_id) {
-            Ok(web::Json(FetchQuotesResponse {
-                quotes: quotes.clone(),
-            }))
-        } else {
-            Err(UistV1Error::UnknownBacktest)
-        }
-    }
-
-    #[derive(Debug, Deserialize, Serialize)]
-    pub struct InitResponse {
-        pub backtest_id: BacktestId,
-    }
-
-    #[get("/init/{dataset_name}")]
-    pub async fn init(
-        app: web::Data<UistState>,
-        path: web::Path<(String,)>,
-    ) -> Result<web::Json<InitResponse>, UistV1Error> {
+        insert_order: web::Json<InsertOrderRequest>,
+    ) -> Result<web::Json<InsertOrderResponse>, UistV1Error> {
         let mut uist = app.lock().unwrap();
-        let (dataset_name,) = path.into_inner();
-
-        if let Some(backtest_id) = uist.init(dataset_name) {
-            Ok(web::Json(InitResponse { backtest_id }))
-        } else {
-            Err(UistV1Error::UnknownDataset)
-        }
-    }
-
-    #[derive(Debug, Deserialize, Serialize)]
-    pub struct InfoResponse {
-        pub version: String,
-        pub dataset: String,
-    }
-
-    #[get("/backtest/{backtest_id}/info")]
-    pub async fn info(
-        app: web::Data<UistState>,
-        path: web::Path<(BacktestId,)>,
-    ) -> Result<web::Json<InfoResponse>, UistV1Error> {
-        let uist = app.lock().unwrap();
         let (backtest_id,) = path.into_inner();
-
-        if let Some(resp) = uist.backtests.get(&backtest_id) {
-            Ok(web::Json(InfoResponse {
-                version: "v1".to_string(),
-                dataset: resp.dataset_name.clone(),
-            }))
+        if let Some(order_id) = uist.insert_order(insert_order.order.clone(), backtest_id) {
+            Ok(web::Json(InsertOrderResponse { order_id }))
         } else {
             Err(UistV1Error::UnknownBacktest)
         }
     }
 
-    #[derive(Debug, Deserialize, Serialize)]
-    pub struct NowResponse {
-        pub now: i64,
-        pub has_next: bool,
-    }
-
-    #[get("/backtest/{backtest_id}/now")]
-    pub async fn now(
-        app: web::Data<UistState>,
-        path: web::Path<(BacktestId,)>,
-    ) -> Result<web::Json<NowResponse>, UistV1Error> {
-        let uist = app.lock().unwrap();
-        let (backtest_id,) = path.into_inner();
-
-        if let Some(backtest) = uist.backtests.get(&backtest_id) {
-            let now = backtest.date;
-            if let Some(dataset) = uist.datasets.get(&backtest.dataset_name) {
-                let mut has_next = false;
-                if dataset.has_next(backtest.pos) {
-                    has_next = true;
-                }
-                Ok(web::Json(NowResponse { now, has_next }))
-            } else {
-                Err(UistV1Error::UnknownDataset)
-            }
-        } else {
-            Err(UistV1Error::UnknownBacktest)
-        }
+    /// Registers every Uist endpoint, the app data must be a `web::Data<UistState>`.
+    pub fn configure(cfg: &mut web::ServiceConfig) {
+        backtest::configure::<UistV1, Penelope>(cfg);
+        cfg.service(tick)
+            .service(insert_order)
+            .service(delete_order);
     }
 }
 
@@ -579,7 +374,7 @@ pub mod uistv1_server {
 mod tests {
     use actix_web::{test, web, App};
 
-    use crate::exchange::uist_v1::Order;
+    use crate::exchange::uist_v1::{Order, OrderState};
     use crate::input::penelope::Penelope;
 
     use super::uistv1_server::*;
@@ -595,17 +390,7 @@ mod tests {
         let app_state = Mutex::new(state);
         let uist_state = web::Data::new(app_state);
 
-        let app = test::init_service(
-            App::new()
-                .app_data(uist_state)
-                .service(info)
-                .service(init)
-                .service(fetch_quotes)
-                .service(tick)
-                .service(insert_order)
-                .service(delete_order),
-        )
-        .await;
+        let app = test::init_service(App::new().app_data(uist_state).configure(configure)).await;
 
         let req = test::TestRequest::get()
             .uri(format!("/init/{dataset_name}").as_str())
@@ -645,4 +430,82 @@ mod tests {
         assert!(resp5.executed_trades.len() == 1);
         assert!(resp5.executed_trades.first().unwrap().symbol == "ABC")
     }
+
+    #[actix_web::test]
+    async fn test_that_order_updates_are_returned_to_client() {
+        let uist = Penelope::random(100, vec!["ABC", "BCD"]);
+        let dataset_name = "fake";
+        let state = AppState::single(dataset_name, uist);
+
+        let uist_state = web::Data::new(Mutex::new(state));
+
+        let app = test::init_service(App::new().app_data(uist_state).configure(configure)).await;
+
+        let req = test::TestRequest::get()
+            .uri(format!("/init/{dataset_name}").as_str())
+            .to_request();
+        let resp: InitResponse = test::call_and_read_body_json(&app, req).await;
+        let backtest_id = resp.backtest_id;
+
+        let mut order_ids = Vec::new();
+        for order in [
+            Order::limit_buy("ABC", 100.0, 1.0),
+            Order::market_buy("XYZ", 100.0),
+            Order::market_buy("ABC", 100.0),
+        ] {
+            let req = test::TestRequest::post()
+                .set_json(InsertOrderRequest { order })
+                .uri(format!("/backtest/{backtest_id}/insert_order").as_str())
+                .to_request();
+            let resp: InsertOrderResponse = test::call_and_read_body_json(&app, req).await;
+            order_ids.push(resp.order_id);
+        }
+
+        //Orders can be cancelled before they reach the book
+        let req = test::TestRequest::post()
+            .set_json(DeleteOrderRequest {
+                order_id: order_ids[2],
+            })
+            .uri(format!("/backtest/{backtest_id}/delete_order").as_str())
+            .to_request();
+        let resp: DeleteOrderResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp.order_update.state, OrderState::Cancelled);
+
+        let req = test::TestRequest::get()
+            .uri(format!("/backtest/{backtest_id}/tick").as_str())
+            .to_request();
+        let resp: TickResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp.order_updates.len(), 3);
+        assert_eq!(resp.order_updates[0].order_id, order_ids[2]);
+        let rejected = resp
+            .order_updates
+            .iter()
+            .find(|update| matches!(update.state, OrderState::Rejected { .. }))
+            .unwrap();
+        let accepted = resp
+            .order_updates
+            .iter()
+            .find(|update| update.state == OrderState::Accepted)
+            .unwrap();
+        assert_eq!(rejected.order_id, order_ids[1]);
+        assert_eq!(accepted.order_id, order_ids[0]);
+
+        let req = test::TestRequest::post()
+            .set_json(DeleteOrderRequest {
+                order_id: accepted.order_id,
+            })
+            .uri(format!("/backtest/{backtest_id}/delete_order").as_str())
+            .to_request();
+        let resp: DeleteOrderResponse = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp.order_update.state, OrderState::Cancelled);
+
+        let req = test::TestRequest::post()
+            .set_json(DeleteOrderRequest {
+                order_id: rejected.order_id,
+            })
+            .uri(format!("/backtest/{backtest_id}/delete_order").as_str())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_client_error());
+    }
 }
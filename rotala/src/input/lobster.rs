@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::{DatedQuotes, Input, Side};
+
+// LOBSTER prices are integers in units of 1/10000 of a dollar
+const PRICE_SCALE: f64 = 10_000.0;
+// LOBSTER pads levels past the end of the book with these prices
+const EMPTY_ASK: i64 = 9_999_999_999;
+const EMPTY_BID: i64 = -9_999_999_999;
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+// LOBSTER times are seconds after midnight with up to nine decimal places. These are parsed from
+// the text, as an f64 can't hold every nanosecond in a trading day exactly.
+fn parse_nanos(time: &str) -> Option<i64> {
+    let (seconds, fraction) = time.split_once('.').unwrap_or((time, ""));
+    if seconds.is_empty()
+        || fraction.len() > 9
+        || !seconds
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let nanos = format!("{fraction:0<9}").parse::<i64>().ok()?;
+    Some(seconds.parse::<i64>().ok()? * NANOS_PER_SECOND + nanos)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct LobsterLevel {
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Execution against a resting order, `aggressor` is the side of the order that crossed the
+/// spread.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct LobsterTrade {
+    pub price: f64,
+    pub size: f64,
+    pub aggressor: Side,
+}
+
+/// Depth of the book at the end of the period starting at `date` and every trade within that
+/// period. Levels are sorted from the best price outwards.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct LobsterQuote {
+    pub symbol: String,
+    pub date: i64,
+    pub bids: Vec<LobsterLevel>,
+    pub asks: Vec<LobsterLevel>,
+    #[serde(default)]
+    pub trades: Vec<LobsterTrade>,
+}
+
+pub type LobsterQuoteByDate = HashMap<String, LobsterQuote>;
+
+#[derive(Debug)]
+pub enum LobsterError {
+    Csv(csv::Error),
+    MalformedRow { line: u64, reason: String },
+    UnsortedRow { line: u64, date: i64, previous: i64 },
+    RowCountMismatch { messages: u64, orderbook: u64 },
+}
+
+impl Error for LobsterError {}
+
+impl fmt::Display for LobsterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LobsterError::Csv(err) => write!(f, "Csv: {err}"),
+            LobsterError::MalformedRow { line, reason } => {
+                write!(f, "MalformedRow: line {line}, {reason}")
+            }
+            LobsterError::UnsortedRow {
+                line,
+                date,
+                previous,
+            } => write!(
+                f,
+                "UnsortedRow: line {line}, date {date} is before previous date {previous}"
+            ),
+            LobsterError::RowCountMismatch {
+                messages,
+                orderbook,
+            } => write!(
+                f,
+                "RowCountMismatch: {messages} message rows and {orderbook} orderbook rows"
+            ),
+        }
+    }
+}
+
+impl From<csv::Error> for LobsterError {
+    fn from(value: csv::Error) -> Self {
+        LobsterError::Csv(value)
+    }
+}
+
+/// Lobster replays L2 order book data in the format produced by
+/// [LOBSTER](https://lobsterdata.com). Each symbol is loaded from a message file and an orderbook
+/// file with the same number of rows, row `i` of the orderbook file is the book after message `i`.
+///
+/// Dates are in nanoseconds so that every book state in the files is kept. Messages with the same
+/// timestamp, such as a marketable order executing against several resting orders, are grouped
+/// into one quote. The quote is the book after the last message with that timestamp, with every
+/// execution of a visible or hidden order at that time replayed as a trade.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Lobster {
+    quotes: DatedQuotes<LobsterQuote>,
+}
+
+impl Lobster {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_quotes(&self, date: &i64) -> Option<&LobsterQuoteByDate> {
+        self.quotes.get(date)
+    }
+
+    /// Adds a quote, replacing any quote already held for the symbol on that date.
+    pub fn add_quote(&mut self, quote: LobsterQuote) {
+        let symbol = quote.symbol.clone();
+        self.quotes.insert(&symbol, quote.date, quote);
+    }
+
+    /// Loads one symbol from a pair of LOBSTER files. LOBSTER timestamps are seconds after
+    /// midnight so `midnight` is the start of the trading day in seconds, dates in the dataset are
+    /// nanoseconds.
+    pub fn from_csv(
+        symbol: &str,
+        midnight: i64,
+        messages: impl AsRef<Path>,
+        orderbook: impl AsRef<Path>,
+    ) -> Result<Self, LobsterError> {
+        let mut lobster = Self::new();
+        lobster.add_csv(symbol, midnight, messages, orderbook)?;
+        Ok(lobster)
+    }
+
+    /// Loads another symbol, or another day, into this dataset. See [Lobster::from_csv].
+    pub fn add_csv(
+        &mut self,
+        symbol: &str,
+        midnight: i64,
+        messages: impl AsRef<Path>,
+        orderbook: impl AsRef<Path>,
+    ) -> Result<(), LobsterError> {
+        let mut messages = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(messages)?;
+        let mut orderbook = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(orderbook)?;
+
+        let mut messages = messages.records();
+        let mut orderbook = orderbook.records();
+        let mut current: Option<LobsterQuote> = None;
+        let mut line = 0;
+        loop {
+            let (message, book) = match (messages.next(), orderbook.next()) {
+                (Some(message), Some(book)) => (message?, book?),
+                (None, None) => break,
+                (message, _) => {
+                    let (mut messages_count, mut orderbook_count) = (line, line);
+                    if message.is_some() {
+                        messages_count += 1 + messages.count() as u64;
+                    } else {
+                        orderbook_count += 1 + orderbook.count() as u64;
+                    }
+                    return Err(LobsterError::RowCountMismatch {
+                        messages: messages_count,
+                        orderbook: orderbook_count,
+                    });
+                }
+            };
+            line += 1;
+
+            let malformed = |reason: String| LobsterError::MalformedRow { line, reason };
+            let field = |row: &csv::StringRecord, pos: usize, name: &str| {
+                let val = row.get(pos).map(|val| val.trim()).unwrap_or_default();
+                val.parse::<f64>()
+                    .map_err(|_| malformed(format!("cannot parse {name} from {val:?}")))
+            };
+
+            let time = message.get(0).map(|val| val.trim()).unwrap_or_default();
+            let date = parse_nanos(time)
+                .map(|nanos| midnight * NANOS_PER_SECOND + nanos)
+                .ok_or_else(|| malformed(format!("invalid message time {time:?}")))?;
+            let event_type = field(&message, 1, "message type")?;
+            let size = field(&message, 3, "message size")?;
+            let price = field(&message, 4, "message price")?;
+            let direction = field(&message, 5, "message direction")?;
+
+            if book.len() % 4 != 0 || book.is_empty() {
+                return Err(malformed(format!(
+                    "orderbook row has {} columns, expected a multiple of four",
+                    book.len()
+                )));
+            }
+            let mut bids = Vec::new();
+            let mut asks = Vec::new();
+            for level in 0..book.len() / 4 {
+                let ask_price = field(&book, level * 4, "ask price")?;
+                let ask_size = field(&book, level * 4 + 1, "ask size")?;
+                let bid_price = field(&book, level * 4 + 2, "bid price")?;
+                let bid_size = field(&book, level * 4 + 3, "bid size")?;
+                if ask_price as i64 != EMPTY_ASK && ask_size > 0.0 {
+                    asks.push(LobsterLevel {
+                        price: ask_price / PRICE_SCALE,
+                        size: ask_size,
+                    });
+                }
+                if bid_price as i64 != EMPTY_BID && bid_size > 0.0 {
+                    bids.push(LobsterLevel {
+                        price: bid_price / PRICE_SCALE,
+                        size: bid_size,
+                    });
+                }
+            }
+
+            let quote = match current.take() {
+                Some(quote) if quote.date == date => quote,
+                Some(quote) if quote.date > date => {
+                    return Err(LobsterError::UnsortedRow {
+                        line,
+                        date,
+                        previous: quote.date,
+                    })
+                }
+                previous => {
+                    if let Some(quote) = previous {
+                        self.add_quote(quote);
+                    }
+                    LobsterQuote {
+                        symbol: symbol.to_string(),
+                        date,
+                        bids: Vec::new(),
+                        asks: Vec::new(),
+                        trades: Vec::new(),
+                    }
+                }
+            };
+            let quote = current.insert(quote);
+            quote.bids = bids;
+            quote.asks = asks;
+
+            // Type 4 and 5 are executions of visible and hidden orders, direction is the side of
+            // the resting order so the aggressor is on the other side
+            if event_type == 4.0 || event_type == 5.0 {
+                let aggressor = if direction > 0.0 {
+                    Side::Sell
+                } else {
+                    Side::Buy
+                };
+                quote.trades.push(LobsterTrade {
+                    price: price / PRICE_SCALE,
+                    size,
+                    aggressor,
+                });
+            }
+        }
+        if let Some(quote) = current {
+            self.add_quote(quote);
+        }
+        Ok(())
+    }
+}
+
+impl Input for Lobster {
+    type Quote = LobsterQuote;
+
+    fn get_quotes(&self, date: &i64) -> Option<impl Iterator<Item = (&str, LobsterQuote)>> {
+        Input::get_quotes(&self.quotes, date)
+    }
+
+    fn get_symbols(&self) -> &[String] {
+        self.quotes.get_symbols()
+    }
+
+    fn get_date(&self, pos: usize) -> Option<&i64> {
+        self.quotes.get_date(pos)
+    }
+
+    fn has_next(&self, pos: usize) -> bool {
+        self.quotes.has_next(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{Lobster, LobsterError};
+    use crate::input::{Input, Side};
+
+    fn write_csv(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("lobster_{}_{name}.csv", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_that_messages_are_grouped_by_timestamp() {
+        let messages = write_csv(
+            "grouped_messages",
+            "34200.1,1,1,100,1000100,1\n\
+             34200.1,4,2,50,1000200,-1\n\
+             34200.100000001,4,1,100,1000100,1\n",
+        );
+        let orderbook = write_csv(
+            "grouped_orderbook",
+            "1000200,200,1000100,300,1000300,100,1000000,100\n\
+             1000200,150,1000100,300,1000300,100,1000000,100\n\
+             1000200,150,1000100,200,1000300,100,-9999999999,1\n",
+        );
+        let lobster = Lobster::from_csv("ABC", 0, &messages, &orderbook).unwrap();
+
+        //Messages a nanosecond apart are separate dates
+        assert_eq!(lobster.get_date(0), Some(&34_200_100_000_000));
+        assert_eq!(lobster.get_date(1), Some(&34_200_100_000_001));
+        assert!(!lobster.has_next(2));
+
+        let first = &lobster.get_quotes(&34_200_100_000_000).unwrap()["ABC"];
+        //Book is the state after the last message with the timestamp
+        assert_eq!(first.asks[0].size, 150.0);
+        assert_eq!(first.bids.len(), 2);
+        assert_eq!(first.trades.len(), 1);
+        assert_eq!(first.trades[0].price, 100.02);
+        assert_eq!(first.trades[0].aggressor, Side::Buy);
+
+        let second = &lobster.get_quotes(&34_200_100_000_001).unwrap()["ABC"];
+        //Padded levels are dropped by price even when they have a size
+        assert_eq!(second.bids.len(), 1);
+        assert_eq!(second.bids[0].price, 100.01);
+        assert_eq!(second.trades[0].aggressor, Side::Sell);
+    }
+
+    #[test]
+    fn test_that_files_with_different_lengths_are_rejected() {
+        let messages = write_csv(
+            "mismatch_messages",
+            "34200.1,1,1,100,1000100,1\n34200.5,1,2,50,1000200,-1\n",
+        );
+        let orderbook = write_csv("mismatch_orderbook", "1000200,200,1000100,300\n");
+        let err = Lobster::from_csv("ABC", 0, &messages, &orderbook).unwrap_err();
+        assert!(matches!(
+            err,
+            LobsterError::RowCountMismatch {
+                messages: 2,
+                orderbook: 1
+            }
+        ));
+    }
+}
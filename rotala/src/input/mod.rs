@@ -1,9 +1,169 @@
 //! Inputs wrap around a dataset providing a simple transparent interface producing a custom quote
 //! type that clients should build their operations around.
 //!
-//! This creates a binding between an orderbook and an input type as the orderbook has to work on
-//! quotes of a specific type. It is possible that this becomes more flexible in the future.
+//! Operations common to all inputs are defined in [Input]. Each input produces its own quote type
+//! and exchanges work on their own quote type, an exchange can consume any input whose quotes
+//! convert into the exchange quote with `From`. For example, [UistV1](crate::exchange::uist_v1::UistV1)
+//! can run against any input with quotes that implement `Into<UistQuote>`.
 //!
 //! Sources should be called through inputs so that clients do not have to marshall data into internal
 //! types.
+pub mod lobster;
+pub mod ohlcv;
 pub mod penelope;
+pub mod tape;
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+/// Side of an order or trade. On a trade this is the side of the aggressor, the order that took
+/// liquidity from the book.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Dataset of quotes indexed by date. Dates are accessed by position so that a backtest can step
+/// through the dataset, and quotes are accessed by date.
+pub trait Input {
+    type Quote: Clone;
+
+    /// Returns quotes for every symbol with a quote on `date`, with the symbol of each quote.
+    /// Quotes are built as the iterator is consumed, so callers decide whether to collect them,
+    /// see [collect_quotes].
+    fn get_quotes(&self, date: &i64) -> Option<impl Iterator<Item = (&str, Self::Quote)>>;
+    fn get_symbols(&self) -> &[String];
+    fn get_date(&self, pos: usize) -> Option<&i64>;
+    fn has_next(&self, pos: usize) -> bool;
+}
+
+/// Funding rate for each symbol that pays funding on a date.
+pub type FundingRateByDate = HashMap<String, f64>;
+
+/// Dataset with funding rates, required by exchanges that settle funding.
+pub trait Funding {
+    /// Returns funding rates paid on `date`, None if no symbol pays funding on that date.
+    fn get_funding_rates(&self, date: &i64) -> Option<FundingRateByDate>;
+    /// Returns the sum of funding rates paid after `start` up to and including `end`, None if no
+    /// symbol pays funding in that range.
+    fn get_funding_rates_between(&self, start: &i64, end: &i64) -> Option<FundingRateByDate>;
+}
+
+/// Quotes keyed by symbol for each date, used by inputs that hold a whole quote per symbol and date
+/// rather than building quotes from columns as [Penelope](penelope::Penelope) does.
+///
+/// Dates are kept sorted as quotes are inserted, and the quotes for `dates[i]` are in `quotes[i]`,
+/// so dates can be read by position and quotes found by binary search on the date.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DatedQuotes<Q> {
+    symbols: Vec<String>,
+    symbol_set: HashSet<String>,
+    dates: Vec<i64>,
+    quotes: Vec<HashMap<String, Q>>,
+}
+
+impl<Q> Default for DatedQuotes<Q> {
+    fn default() -> Self {
+        Self {
+            symbols: Vec::new(),
+            symbol_set: HashSet::new(),
+            dates: Vec::new(),
+            quotes: Vec::new(),
+        }
+    }
+}
+
+impl<Q> DatedQuotes<Q> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, date: &i64) -> Option<&HashMap<String, Q>> {
+        let pos = self.dates.binary_search(date).ok()?;
+        Some(&self.quotes[pos])
+    }
+
+    pub fn get_symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    pub fn get_date(&self, pos: usize) -> Option<&i64> {
+        self.dates.get(pos)
+    }
+
+    pub fn has_next(&self, pos: usize) -> bool {
+        self.dates.len() > pos
+    }
+
+    /// Adds a symbol to the list returned by [DatedQuotes::get_symbols] without a quote. Symbols
+    /// are listed in the order they are first added.
+    pub fn add_symbol(&mut self, symbol: &str) {
+        if !self.symbol_set.contains(symbol) {
+            self.symbol_set.insert(symbol.to_string());
+            self.symbols.push(symbol.to_string());
+        }
+    }
+
+    /// Inserts a quote, replacing any quote for the same symbol and date. Dates do not have to be
+    /// inserted in order.
+    pub fn insert(&mut self, symbol: &str, date: i64, quote: Q) {
+        self.add_symbol(symbol);
+        let pos = match self.dates.binary_search(&date) {
+            Ok(pos) => pos,
+            Err(pos) => {
+                self.dates.insert(pos, date);
+                self.quotes.insert(pos, HashMap::new());
+                pos
+            }
+        };
+        self.quotes[pos].insert(symbol.to_string(), quote);
+    }
+}
+
+impl<Q: Clone> Input for DatedQuotes<Q> {
+    type Quote = Q;
+
+    fn get_quotes(&self, date: &i64) -> Option<impl Iterator<Item = (&str, Q)>> {
+        Some(
+            self.get(date)?
+                .iter()
+                .map(|(symbol, quote)| (symbol.as_str(), quote.clone())),
+        )
+    }
+
+    fn get_symbols(&self) -> &[String] {
+        DatedQuotes::get_symbols(self)
+    }
+
+    fn get_date(&self, pos: usize) -> Option<&i64> {
+        DatedQuotes::get_date(self, pos)
+    }
+
+    fn has_next(&self, pos: usize) -> bool {
+        DatedQuotes::has_next(self, pos)
+    }
+}
+
+/// Converts quotes keyed by symbol from one quote type into another.
+pub fn convert_quotes<Q, T>(quotes: &HashMap<String, Q>) -> HashMap<String, T>
+where
+    Q: Clone + Into<T>,
+{
+    quotes
+        .iter()
+        .map(|(symbol, quote)| (symbol.clone(), quote.clone().into()))
+        .collect()
+}
+
+/// Collects quotes from [Input::get_quotes] into a map keyed by symbol, converting each quote into
+/// another quote type as it is read.
+pub fn collect_quotes<'a, Q, T>(quotes: impl Iterator<Item = (&'a str, Q)>) -> HashMap<String, T>
+where
+    Q: Into<T>,
+{
+    quotes
+        .map(|(symbol, quote)| (symbol.to_string(), quote.into()))
+        .collect()
+}
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::{DatedQuotes, Input};
+
+/// Open, high, low, close and volume of a symbol over the period starting at `date`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct OhlcvBar {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    #[serde(default)]
+    pub volume: Option<f64>,
+    pub symbol: String,
+    pub date: i64,
+}
+
+impl OhlcvBar {
+    // High and low must contain every other price in the bar
+    fn validate(&self) -> Result<(), String> {
+        let prices = [self.open, self.high, self.low, self.close];
+        if prices.iter().any(|price| !price.is_finite()) {
+            return Err("prices must be finite".to_string());
+        }
+        if self.high < self.open.max(self.close) || self.low > self.open.min(self.close) {
+            return Err(format!(
+                "high {} and low {} do not contain open {} and close {}",
+                self.high, self.low, self.open, self.close
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub type OhlcvBarByDate = HashMap<String, OhlcvBar>;
+
+#[derive(Debug)]
+pub enum OhlcvError {
+    Csv(csv::Error),
+    MissingColumn(String),
+    MalformedRow { line: u64, reason: String },
+    InvalidBar(String),
+    UnsortedRow { line: u64, date: i64, previous: i64 },
+}
+
+impl Error for OhlcvError {}
+
+impl fmt::Display for OhlcvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OhlcvError::Csv(err) => write!(f, "Csv: {err}"),
+            OhlcvError::MissingColumn(column) => write!(f, "MissingColumn: {column}"),
+            OhlcvError::MalformedRow { line, reason } => {
+                write!(f, "MalformedRow: line {line}, {reason}")
+            }
+            OhlcvError::InvalidBar(reason) => write!(f, "InvalidBar: {reason}"),
+            OhlcvError::UnsortedRow {
+                line,
+                date,
+                previous,
+            } => write!(
+                f,
+                "UnsortedRow: line {line}, date {date} is before previous date {previous}"
+            ),
+        }
+    }
+}
+
+impl From<csv::Error> for OhlcvError {
+    fn from(value: csv::Error) -> Self {
+        OhlcvError::Csv(value)
+    }
+}
+
+/// Maps the header names of a CSV file onto the fields of [OhlcvBar]. Defaults to a file with
+/// columns named `date`, `symbol`, `open`, `high`, `low`, `close` and `volume` in any order.
+/// Volume is not read if the column is set to None.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct OhlcvColumns {
+    pub date: String,
+    pub symbol: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub volume: Option<String>,
+}
+
+impl Default for OhlcvColumns {
+    fn default() -> Self {
+        Self {
+            date: "date".to_string(),
+            symbol: "symbol".to_string(),
+            open: "open".to_string(),
+            high: "high".to_string(),
+            low: "low".to_string(),
+            close: "close".to_string(),
+            volume: Some("volume".to_string()),
+        }
+    }
+}
+
+/// Ohlcv produces bars, rather than quotes, for exchanges to use. Bars carry the range traded over
+/// a period so exchanges can execute orders that were touched within the period, see
+/// [ExecutionMode](crate::exchange::uist_v1::ExecutionMode).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Ohlcv {
+    bars: DatedQuotes<OhlcvBar>,
+}
+
+impl Ohlcv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_bars(&self, date: &i64) -> Option<&OhlcvBarByDate> {
+        self.bars.get(date)
+    }
+
+    /// Adds a bar, replacing any bar already held for the symbol on that date. Returns an error
+    /// if the high and low do not contain the open and close.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_bar(
+        &mut self,
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        volume: Option<f64>,
+        date: i64,
+        symbol: impl Into<String>,
+    ) -> Result<(), OhlcvError> {
+        let symbol = symbol.into();
+        let bar = OhlcvBar {
+            open,
+            high,
+            low,
+            close,
+            volume,
+            symbol: symbol.clone(),
+            date,
+        };
+        bar.validate().map_err(OhlcvError::InvalidBar)?;
+        self.bars.insert(&symbol, date, bar);
+        Ok(())
+    }
+
+    /// Loads bars from a local CSV file with a header row. Rows must be sorted by date, and every
+    /// bar must have a high and low that contain the open and close. Rows that cannot be parsed
+    /// return an error with the line number in the file rather than panicking.
+    pub fn from_csv(path: impl AsRef<Path>, columns: &OhlcvColumns) -> Result<Self, OhlcvError> {
+        let mut rdr = csv::Reader::from_path(path)?;
+
+        let headers = rdr.headers()?.clone();
+        let position = |name: &str| -> Result<usize, OhlcvError> {
+            headers
+                .iter()
+                .position(|header| header.trim() == name)
+                .ok_or_else(|| OhlcvError::MissingColumn(name.to_string()))
+        };
+        let date_pos = position(&columns.date)?;
+        let symbol_pos = position(&columns.symbol)?;
+        let price_pos = [
+            (&columns.open, position(&columns.open)?),
+            (&columns.high, position(&columns.high)?),
+            (&columns.low, position(&columns.low)?),
+            (&columns.close, position(&columns.close)?),
+        ];
+        let volume_pos = match &columns.volume {
+            Some(volume) => Some((volume, position(volume)?)),
+            None => None,
+        };
+
+        let mut ohlcv = Self::new();
+        let mut last_date: Option<i64> = None;
+        for row in rdr.records() {
+            let row = row.map_err(|err| OhlcvError::MalformedRow {
+                line: err.position().map(|pos| pos.line()).unwrap_or_default(),
+                reason: err.to_string(),
+            })?;
+            let line = row.position().map(|pos| pos.line()).unwrap_or_default();
+
+            let field = |pos: usize, name: &str| -> Result<&str, OhlcvError> {
+                row.get(pos)
+                    .map(|val| val.trim())
+                    .ok_or_else(|| OhlcvError::MalformedRow {
+                        line,
+                        reason: format!("missing value for {name}"),
+                    })
+            };
+            let parse = |pos: usize, name: &str| -> Result<f64, OhlcvError> {
+                let val = field(pos, name)?;
+                val.parse::<f64>().map_err(|_| OhlcvError::MalformedRow {
+                    line,
+                    reason: format!("cannot parse {name} from {val:?}"),
+                })
+            };
+
+            let date_str = field(date_pos, &columns.date)?;
+            let date = date_str
+                .parse::<i64>()
+                .map_err(|_| OhlcvError::MalformedRow {
+                    line,
+                    reason: format!("cannot parse {} from {date_str:?}", columns.date),
+                })?;
+            let mut prices = [0.0; 4];
+            for (price, (name, pos)) in prices.iter_mut().zip(price_pos) {
+                *price = parse(pos, name)?;
+            }
+            let volume = match volume_pos {
+                Some((name, pos)) => Some(parse(pos, name)?),
+                None => None,
+            };
+            let symbol = field(symbol_pos, &columns.symbol)?;
+            if symbol.is_empty() {
+                return Err(OhlcvError::MalformedRow {
+                    line,
+                    reason: format!("empty value for {}", columns.symbol),
+                });
+            }
+
+            if let Some(previous) = last_date {
+                if date < previous {
+                    return Err(OhlcvError::UnsortedRow {
+                        line,
+                        date,
+                        previous,
+                    });
+                }
+            }
+            last_date = Some(date);
+
+            let [open, high, low, close] = prices;
+            let bar = OhlcvBar {
+                open,
+                high,
+                low,
+                close,
+                volume,
+                symbol: symbol.to_string(),
+                date,
+            };
+            bar.validate()
+                .map_err(|reason| OhlcvError::MalformedRow { line, reason })?;
+            ohlcv.bars.insert(symbol, date, bar);
+        }
+        Ok(ohlcv)
+    }
+}
+
+impl Input for Ohlcv {
+    type Quote = OhlcvBar;
+
+    fn get_quotes(&self, date: &i64) -> Option<impl Iterator<Item = (&str, OhlcvBar)>> {
+        Input::get_quotes(&self.bars, date)
+    }
+
+    fn get_symbols(&self) -> &[String] {
+        self.bars.get_symbols()
+    }
+
+    fn get_date(&self, pos: usize) -> Option<&i64> {
+        self.bars.get_date(pos)
+    }
+
+    fn has_next(&self, pos: usize) -> bool {
+        self.bars.has_next(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{Ohlcv, OhlcvColumns, OhlcvError};
+    use crate::input::Input;
+
+    fn write_csv(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("ohlcv_{}_{name}.csv", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_that_bars_load_from_csv() {
+        let path = write_csv(
+            "load",
+            "date,symbol,open,high,low,close,volume\n\
+             100,ABC,10.0,12.0,9.0,11.0,1000\n\
+             100,BCD,20.0,21.0,19.0,20.5,500\n\
+             101,ABC,11.0,11.5,10.0,10.5,800\n",
+        );
+        let ohlcv = Ohlcv::from_csv(&path, &OhlcvColumns::default()).unwrap();
+
+        assert_eq!(ohlcv.get_symbols(), &["ABC".to_string(), "BCD".to_string()]);
+        assert_eq!(ohlcv.get_date(1), Some(&101));
+        let bars = ohlcv.get_bars(&100).unwrap();
+        assert_eq!(bars["ABC"].high, 12.0);
+        assert_eq!(bars["BCD"].volume, Some(500.0));
+        assert!(ohlcv.get_bars(&102).is_none());
+    }
+
+    #[test]
+    fn test_that_bar_outside_range_is_rejected() {
+        let path = write_csv(
+            "invalid",
+            "date,symbol,open,high,low,close,volume\n\
+             100,ABC,10.0,12.0,9.0,11.0,1000\n\
+             101,ABC,10.0,10.5,9.0,11.0,1000\n",
+        );
+        let err = Ohlcv::from_csv(&path, &OhlcvColumns::default()).unwrap_err();
+        assert!(matches!(err, OhlcvError::MalformedRow { line: 3, .. }));
+    }
+
+    #[test]
+    fn test_that_bars_can_be_added_out_of_order() {
+        let mut ohlcv = Ohlcv::new();
+        ohlcv
+            .add_bar(10.0, 11.0, 9.0, 10.0, None, 101, "ABC")
+            .unwrap();
+        ohlcv
+            .add_bar(10.0, 11.0, 9.0, 10.5, None, 100, "ABC")
+            .unwrap();
+
+        assert_eq!(ohlcv.get_date(0), Some(&100));
+        assert_eq!(ohlcv.get_bars(&100).unwrap()["ABC"].close, 10.5);
+        assert!(ohlcv.has_next(1));
+        assert!(!ohlcv.has_next(2));
+    }
+
+    #[test]
+    fn test_that_added_bar_outside_range_is_rejected() {
+        let mut ohlcv = Ohlcv::new();
+        let err = ohlcv
+            .add_bar(10.0, 9.5, 9.0, 10.0, None, 100, "ABC")
+            .unwrap_err();
+        assert!(matches!(err, OhlcvError::InvalidBar(_)));
+        assert!(ohlcv.get_bars(&100).is_none());
+        assert!(ohlcv.get_symbols().is_empty());
+    }
+}
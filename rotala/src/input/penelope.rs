@@ -1,10 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::ops::{Bound, Range};
+use std::path::Path;
+use std::str::FromStr;
 
 use rand::thread_rng;
 use rand_distr::{Distribution, Uniform};
 use serde::{Deserialize, Serialize};
 
-use crate::source::get_binance_1m_klines;
+use time::Date;
+
+use super::{Funding, FundingRateByDate, Input};
+use crate::source::{get_binance_1m_klines, SourceError};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PenelopeQuote {
@@ -12,25 +22,416 @@ pub struct PenelopeQuote {
     pub ask: f64,
     pub symbol: String,
     pub date: i64,
+    /// Volume traded, or available, at this quote. Exchanges that model liquidity treat a quote
+    /// without volume as having unlimited liquidity.
+    #[serde(default)]
+    pub volume: Option<f64>,
 }
 
 pub type PenelopeQuoteByDate = HashMap<String, PenelopeQuote>;
 
-// Penelope produces data for exchanges to use. Exchanges bind their underlying data representation
-// to that used by Penelope: `PenelopeQuote`.
+#[derive(Debug)]
+pub enum PenelopeError {
+    Csv(csv::Error),
+    MissingColumn(String),
+    MalformedRow { line: u64, reason: String },
+    UnsortedRow { line: u64, date: i64, previous: i64 },
+    Source(SourceError),
+    Io(std::io::Error),
+    InvalidFormat(String),
+    UnsupportedVersion(u16),
+    ChecksumMismatch { expected: u64, found: u64 },
+}
+
+impl Error for PenelopeError {}
+
+impl fmt::Display for PenelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PenelopeError::Csv(err) => write!(f, "Csv: {err}"),
+            PenelopeError::MissingColumn(column) => write!(f, "MissingColumn: {column}"),
+            PenelopeError::MalformedRow { line, reason } => {
+                write!(f, "MalformedRow: line {line}, {reason}")
+            }
+            PenelopeError::UnsortedRow {
+                line,
+                date,
+                previous,
+            } => write!(
+                f,
+                "UnsortedRow: line {line}, date {date} is before previous date {previous}"
+            ),
+            PenelopeError::Source(err) => write!(f, "Source: {err}"),
+            PenelopeError::Io(err) => write!(f, "Io: {err}"),
+            PenelopeError::InvalidFormat(reason) => write!(f, "InvalidFormat: {reason}"),
+            PenelopeError::UnsupportedVersion(version) => {
+                write!(f, "UnsupportedVersion: {version}")
+            }
+            PenelopeError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "ChecksumMismatch: expected {expected:#018x}, found {found:#018x}"
+            ),
+        }
+    }
+}
+
+impl From<csv::Error> for PenelopeError {
+    fn from(value: csv::Error) -> Self {
+        PenelopeError::Csv(value)
+    }
+}
+
+impl From<SourceError> for PenelopeError {
+    fn from(value: SourceError) -> Self {
+        PenelopeError::Source(value)
+    }
+}
+
+impl From<std::io::Error> for PenelopeError {
+    fn from(value: std::io::Error) -> Self {
+        if value.kind() == ErrorKind::UnexpectedEof {
+            return PenelopeError::InvalidFormat("unexpected end of file".to_string());
+        }
+        PenelopeError::Io(value)
+    }
+}
+
+const PENELOPE_MAGIC: &[u8; 4] = b"PNLP";
+// Version 1 has no volumes, these files are still loaded with every volume set to None. Version 2
+// has no funding rates.
+const PENELOPE_VERSION: u16 = 3;
+// Magic, version and checksum, the checksum covers every byte after this
+const PENELOPE_PREAMBLE_LEN: usize = 14;
+/// Extension used for saved datasets by [Penelope::load_dir].
+pub const PENELOPE_EXTENSION: &str = "penelope";
+
+/// Header of a dataset written by [Penelope::save]. Can be read without loading the rest of the
+/// file with [Penelope::read_header].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PenelopeHeader {
+    pub version: u16,
+    pub checksum: u64,
+    pub symbols: Vec<String>,
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+    pub dates: u64,
+    pub rows: u64,
+}
+
+// FNV-1a, this is only used to catch truncated or corrupted files
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn csv_column(headers: &csv::StringRecord, name: &str) -> Result<usize, PenelopeError> {
+    headers
+        .iter()
+        .position(|header| header.trim() == name)
+        .ok_or_else(|| PenelopeError::MissingColumn(name.to_string()))
+}
+
+fn csv_field<'a>(
+    row: &'a csv::StringRecord,
+    line: u64,
+    pos: usize,
+    name: &str,
+) -> Result<&'a str, PenelopeError> {
+    row.get(pos)
+        .map(|val| val.trim())
+        .ok_or_else(|| PenelopeError::MalformedRow {
+            line,
+            reason: format!("missing value for {name}"),
+        })
+}
+
+fn parse_csv_field<T: FromStr>(
+    row: &csv::StringRecord,
+    line: u64,
+    pos: usize,
+    name: &str,
+) -> Result<T, PenelopeError> {
+    let val = csv_field(row, line, pos, name)?;
+    val.parse::<T>().map_err(|_| PenelopeError::MalformedRow {
+        line,
+        reason: format!("cannot parse {name} from {val:?}"),
+    })
+}
+
+fn csv_rows(
+    rdr: &mut csv::Reader<fs::File>,
+) -> impl Iterator<Item = Result<(u64, csv::StringRecord), PenelopeError>> + '_ {
+    rdr.records().map(|row| {
+        let row = row.map_err(|err| PenelopeError::MalformedRow {
+            line: err.position().map(|pos| pos.line()).unwrap_or_default(),
+            reason: err.to_string(),
+        })?;
+        let line = row.position().map(|pos| pos.line()).unwrap_or_default();
+        Ok((line, row))
+    })
+}
+
+struct PenelopeReader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> PenelopeReader<R> {
+    fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], PenelopeError> {
+        let mut buf = [0; N];
+        self.inner.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, PenelopeError> {
+        Ok(u16::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, PenelopeError> {
+        Ok(u32::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, PenelopeError> {
+        Ok(u64::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_i64(&mut self) -> Result<i64, PenelopeError> {
+        Ok(i64::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, PenelopeError> {
+        Ok(f64::from_le_bytes(self.read_bytes()?))
+    }
+
+    fn read_string(&mut self) -> Result<String, PenelopeError> {
+        let len = self.read_u32()? as usize;
+        let mut buf = Vec::new();
+        (&mut self.inner).take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(PenelopeError::InvalidFormat(
+                "unexpected end of file".to_string(),
+            ));
+        }
+        String::from_utf8(buf)
+            .map_err(|_| PenelopeError::InvalidFormat("symbol is not valid utf-8".to_string()))
+    }
+
+    fn read_header(&mut self) -> Result<PenelopeHeader, PenelopeError> {
+        if &self.read_bytes::<4>()? != PENELOPE_MAGIC {
+            return Err(PenelopeError::InvalidFormat(
+                "file is not a Penelope dataset".to_string(),
+            ));
+        }
+        let version = self.read_u16()?;
+        if version == 0 || version > PENELOPE_VERSION {
+            return Err(PenelopeError::UnsupportedVersion(version));
+        }
+        let checksum = self.read_u64()?;
+
+        let symbol_count = self.read_u32()?;
+        let mut symbols = Vec::new();
+        for _ in 0..symbol_count {
+            symbols.push(self.read_string()?);
+        }
+        let dates = self.read_u64()?;
+        let start = self.read_i64()?;
+        let end = self.read_i64()?;
+        let rows = self.read_u64()?;
+
+        let (start, end) = if dates == 0 {
+            (None, None)
+        } else {
+            (Some(start), Some(end))
+        };
+        Ok(PenelopeHeader {
+            version,
+            checksum,
+            symbols,
+            start,
+            end,
+            dates,
+            rows,
+        })
+    }
+}
+
+/// Maps the header names of a CSV file onto the fields of [PenelopeQuote]. Defaults to a file
+/// with columns named `date`, `symbol`, `bid` and `ask` in any order. Volume is only read if a
+/// column is set.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct PenelopeColumns {
+    pub date: String,
+    pub symbol: String,
+    pub bid: String,
+    pub ask: String,
+    pub volume: Option<String>,
+}
+
+impl Default for PenelopeColumns {
+    fn default() -> Self {
+        Self {
+            date: "date".to_string(),
+            symbol: "symbol".to_string(),
+            bid: "bid".to_string(),
+            ask: "ask".to_string(),
+            volume: None,
+        }
+    }
+}
+
+/// Penelope produces data for exchanges to use. Exchanges bind their underlying data representation
+/// to that used by Penelope: [PenelopeQuote].
+///
+/// Quotes are stored in columns rather than as a map of quotes so that large datasets, for example
+/// years of minute data across hundreds of symbols, can be held in memory. Symbols are interned and
+/// rows are stored in contiguous arrays sorted by date. `dates` holds every unique date in sorted
+/// order and the rows for `dates[i]` are found in `offsets[i]..offsets[i + 1]`, so date lookup is a
+/// binary search. Rows within a date are sorted by interned symbol id, so finding the row for a
+/// symbol is also a binary search. [PenelopeQuote] is only built when a client asks for quotes.
+///
+/// Datasets for perpetual futures can also carry funding rates for each symbol. These are sparse,
+/// usually hourly, so are held separately from quotes. Rates can fall on dates without quotes,
+/// exchanges collect them with [get_funding_rates_between](Penelope::get_funding_rates_between).
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Penelope {
+    symbols: Vec<String>,
+    symbol_ids: HashMap<String, u32>,
     dates: Vec<i64>,
-    inner: HashMap<i64, PenelopeQuoteByDate>,
+    offsets: Vec<usize>,
+    row_symbols: Vec<u32>,
+    bids: Vec<f64>,
+    asks: Vec<f64>,
+    volumes: Vec<Option<f64>>,
+    funding: BTreeMap<i64, Vec<(u32, f64)>>,
 }
 
 impl Penelope {
-    pub fn get_quotes(&self, date: &i64) -> Option<&PenelopeQuoteByDate> {
-        self.inner.get(date)
+    fn build_quote(&self, row: usize, date: i64) -> PenelopeQuote {
+        PenelopeQuote {
+            bid: self.bids[row],
+            ask: self.asks[row],
+            symbol: self.symbols[self.row_symbols[row] as usize].clone(),
+            date,
+            volume: self.volumes[row],
+        }
     }
 
-    pub fn get_quotes_unchecked(&self, date: &i64) -> &PenelopeQuoteByDate {
-        self.get_quotes(date).unwrap()
+    /// Returns the position of `symbol_id` within `rows`, or the position where it should be
+    /// inserted to keep the rows sorted.
+    fn find_row(&self, rows: Range<usize>, symbol_id: u32) -> Result<usize, usize> {
+        let start = rows.start;
+        self.row_symbols[rows]
+            .binary_search(&symbol_id)
+            .map(|row| start + row)
+            .map_err(|row| start + row)
+    }
+
+    /// Sorts the rows within each date by symbol id. Datasets saved before rows were kept sorted
+    /// can have rows in insertion order.
+    fn sort_rows(&mut self) {
+        for pos in 0..self.dates.len() {
+            let (start, end) = (self.offsets[pos], self.offsets[pos + 1]);
+            if self.row_symbols[start..end]
+                .windows(2)
+                .all(|pair| pair[0] < pair[1])
+            {
+                continue;
+            }
+            let mut rows: Vec<usize> = (start..end).collect();
+            rows.sort_by_key(|row| self.row_symbols[*row]);
+            let row_symbols: Vec<u32> = rows.iter().map(|row| self.row_symbols[*row]).collect();
+            let bids: Vec<f64> = rows.iter().map(|row| self.bids[*row]).collect();
+            let asks: Vec<f64> = rows.iter().map(|row| self.asks[*row]).collect();
+            let volumes: Vec<Option<f64>> = rows.iter().map(|row| self.volumes[*row]).collect();
+            self.row_symbols[start..end].copy_from_slice(&row_symbols);
+            self.bids[start..end].copy_from_slice(&bids);
+            self.asks[start..end].copy_from_slice(&asks);
+            self.volumes[start..end].copy_from_slice(&volumes);
+        }
+    }
+
+    /// Returns a view over the quotes on `date`. Quotes are only built when they are read from the
+    /// view, so nothing is allocated until then.
+    pub fn get_quotes(&self, date: &i64) -> Option<PenelopeQuotes<'_>> {
+        let pos = self.index_of(date)?;
+        Some(PenelopeQuotes {
+            source: self,
+            date: *date,
+            rows: self.offsets[pos]..self.offsets[pos + 1],
+        })
+    }
+
+    /// Returns every quote on `date` in a map, panics if there are no quotes on `date`.
+    pub fn get_quotes_unchecked(&self, date: &i64) -> PenelopeQuoteByDate {
+        self.get_quotes(date).unwrap().to_map()
+    }
+
+    /// Returns funding rates paid on `date`, None if no symbol pays funding on that date.
+    pub fn get_funding_rates(&self, date: &i64) -> Option<FundingRateByDate> {
+        let rates = self.funding.get(date)?;
+        Some(
+            rates
+                .iter()
+                .map(|(symbol_id, rate)| (self.symbols[*symbol_id as usize].clone(), *rate))
+                .collect(),
+        )
+    }
+
+    /// Returns the sum of funding rates paid after `start` up to and including `end`, None if no
+    /// symbol pays funding in that range.
+    pub fn get_funding_rates_between(&self, start: &i64, end: &i64) -> Option<FundingRateByDate> {
+        if start >= end {
+            return None;
+        }
+        let mut summed = FundingRateByDate::new();
+        for rates in self
+            .funding
+            .range((Bound::Excluded(start), Bound::Included(end)))
+            .map(|(_, rates)| rates)
+        {
+            for (symbol_id, rate) in rates {
+                *summed
+                    .entry(self.symbols[*symbol_id as usize].clone())
+                    .or_default() += rate;
+            }
+        }
+        if summed.is_empty() {
+            None
+        } else {
+            Some(summed)
+        }
+    }
+
+    /// Returns all quotes with dates between `start` and `end`, inclusive, sorted by date.
+    pub fn quotes_between(&self, start: &i64, end: &i64) -> Vec<PenelopeQuote> {
+        let first = self.dates.partition_point(|date| date < start);
+        let last = self.dates.partition_point(|date| date <= end);
+
+        let mut quotes = Vec::new();
+        if first >= last {
+            return quotes;
+        }
+        quotes.reserve(self.offsets[last] - self.offsets[first]);
+        for pos in first..last {
+            for row in self.offsets[pos]..self.offsets[pos + 1] {
+                quotes.push(self.build_quote(row, self.dates[pos]));
+            }
+        }
+        quotes
+    }
+
+    /// Returns the position of `date` within the dataset, this can be passed to
+    /// [get_date](Penelope::get_date) and [has_next](Penelope::has_next).
+    pub fn index_of(&self, date: &i64) -> Option<usize> {
+        self.dates.binary_search(date).ok()
+    }
+
+    pub fn get_symbols(&self) -> &[String] {
+        &self.symbols
     }
 
     pub fn get_date(&self, pos: usize) -> Option<&i64> {
@@ -43,37 +444,398 @@ impl Penelope {
 
     pub fn new() -> Self {
         Self {
+            symbols: Vec::new(),
+            symbol_ids: HashMap::new(),
             dates: Vec::new(),
-            inner: HashMap::new(),
+            offsets: vec![0],
+            row_symbols: Vec::new(),
+            bids: Vec::new(),
+            asks: Vec::new(),
+            volumes: Vec::new(),
+            funding: BTreeMap::new(),
+        }
+    }
+
+    /// Loads 1m klines for each symbol from a local directory of Binance archives, see
+    /// [get_binance_1m_klines] for the expected layout. Each kline produces two quotes: the open
+    /// price at the open time and the close price at the close time.
+    pub fn from_binance(
+        dir: impl AsRef<Path>,
+        symbols: Vec<&str>,
+        start: Date,
+        end: Date,
+    ) -> Result<Self, PenelopeError> {
+        let mut records = Vec::new();
+        for symbol in symbols {
+            records.extend(get_binance_1m_klines(dir.as_ref(), symbol, start, end)?);
         }
+
+        let mut quotes = Vec::with_capacity(records.len() * 2);
+        for record in &records {
+            quotes.push((record.open_date, record.open, record.symbol.as_str()));
+            quotes.push((record.close_date, record.close, record.symbol.as_str()));
+        }
+        //Inserts have to be sorted by date, symbols are read one after the other
+        quotes.sort_by_key(|(date, _, _)| *date);
+
+        let mut penelope = Self::new();
+        for (date, price, symbol) in quotes {
+            penelope.add_quote(price, price, date, symbol);
+        }
+        Ok(penelope)
     }
 
-    pub fn from_binance() -> Self {
+    /// Loads quotes from a local CSV file with a header row. Rows must be sorted by date, this is
+    /// checked as rows are read and the first row out of order returns an error. Rows that cannot
+    /// be parsed return an error with the line number in the file rather than panicking.
+    pub fn from_csv(
+        path: impl AsRef<Path>,
+        columns: &PenelopeColumns,
+    ) -> Result<Self, PenelopeError> {
+        let mut rdr = csv::Reader::from_path(path)?;
+
+        let headers = rdr.headers()?.clone();
+        let date_pos = csv_column(&headers, &columns.date)?;
+        let symbol_pos = csv_column(&headers, &columns.symbol)?;
+        let bid_pos = csv_column(&headers, &columns.bid)?;
+        let ask_pos = csv_column(&headers, &columns.ask)?;
+        let volume_pos = match &columns.volume {
+            Some(volume) => Some((volume, csv_column(&headers, volume)?)),
+            None => None,
+        };
+
         let mut penelope = Self::new();
+        let mut last_date: Option<i64> = None;
+        for row in csv_rows(&mut rdr) {
+            let (line, row) = row?;
 
-        for record in get_binance_1m_klines() {
-            penelope.add_quote(record.open, record.open, record.open_date, "BTC");
-            penelope.add_quote(record.close, record.close, record.close_date, "BTC");
+            let date: i64 = parse_csv_field(&row, line, date_pos, &columns.date)?;
+            let bid: f64 = parse_csv_field(&row, line, bid_pos, &columns.bid)?;
+            let ask: f64 = parse_csv_field(&row, line, ask_pos, &columns.ask)?;
+            let volume = match volume_pos {
+                Some((name, pos)) => Some(parse_csv_field::<f64>(&row, line, pos, name)?),
+                None => None,
+            };
+            let symbol = csv_field(&row, line, symbol_pos, &columns.symbol)?;
+            if symbol.is_empty() {
+                return Err(PenelopeError::MalformedRow {
+                    line,
+                    reason: format!("empty value for {}", columns.symbol),
+                });
+            }
+
+            if let Some(previous) = last_date {
+                if date < previous {
+                    return Err(PenelopeError::UnsortedRow {
+                        line,
+                        date,
+                        previous,
+                    });
+                }
+            }
+            last_date = Some(date);
+
+            penelope.insert_quote(bid, ask, volume, date, symbol.to_string());
         }
-        penelope
+        Ok(penelope)
     }
 
-    pub fn add_quote(&mut self, bid: f64, ask: f64, date: i64, symbol: impl Into<String> + Clone) {
-        //Inserts should be in sorted order
-        let quote = PenelopeQuote {
-            bid,
-            ask,
-            date,
-            symbol: symbol.into(),
+    /// Adds funding rates from a local CSV file with `date`, `symbol` and `rate` columns, see
+    /// [add_funding_rate](Penelope::add_funding_rate). Rows can be in any order. Rows that cannot
+    /// be parsed return an error with the line number in the file.
+    pub fn add_funding_from_csv(&mut self, path: impl AsRef<Path>) -> Result<(), PenelopeError> {
+        let mut rdr = csv::Reader::from_path(path)?;
+
+        let headers = rdr.headers()?.clone();
+        let date_pos = csv_column(&headers, "date")?;
+        let symbol_pos = csv_column(&headers, "symbol")?;
+        let rate_pos = csv_column(&headers, "rate")?;
+
+        for row in csv_rows(&mut rdr) {
+            let (line, row) = row?;
+
+            let date: i64 = parse_csv_field(&row, line, date_pos, "date")?;
+            let rate: f64 = parse_csv_field(&row, line, rate_pos, "rate")?;
+            let symbol = csv_field(&row, line, symbol_pos, "symbol")?;
+            if symbol.is_empty() {
+                return Err(PenelopeError::MalformedRow {
+                    line,
+                    reason: "empty value for symbol".to_string(),
+                });
+            }
+            self.add_funding_rate(rate, date, symbol);
+        }
+        Ok(())
+    }
+
+    /// Writes the dataset to `path` in a versioned binary format. The header holds the symbol
+    /// list, date range and a checksum of the contents, see [PenelopeHeader].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PenelopeError> {
+        let mut body = Vec::with_capacity(
+            self.dates.len() * 16 + self.bids.len() * 28 + self.symbols.len() * 8 + 64,
+        );
+        body.extend((self.symbols.len() as u32).to_le_bytes());
+        for symbol in &self.symbols {
+            body.extend((symbol.len() as u32).to_le_bytes());
+            body.extend(symbol.as_bytes());
+        }
+        body.extend((self.dates.len() as u64).to_le_bytes());
+        body.extend(
+            self.dates
+                .first()
+                .copied()
+                .unwrap_or_default()
+                .to_le_bytes(),
+        );
+        body.extend(self.dates.last().copied().unwrap_or_default().to_le_bytes());
+        body.extend((self.bids.len() as u64).to_le_bytes());
+
+        for date in &self.dates {
+            body.extend(date.to_le_bytes());
+        }
+        for offset in &self.offsets {
+            body.extend((*offset as u64).to_le_bytes());
+        }
+        for symbol_id in &self.row_symbols {
+            body.extend(symbol_id.to_le_bytes());
+        }
+        for bid in &self.bids {
+            body.extend(bid.to_le_bytes());
+        }
+        for ask in &self.asks {
+            body.extend(ask.to_le_bytes());
+        }
+        for volume in &self.volumes {
+            body.extend(volume.unwrap_or(f64::NAN).to_le_bytes());
+        }
+        let funding_count: usize = self.funding.values().map(|rates| rates.len()).sum();
+        body.extend((funding_count as u64).to_le_bytes());
+        for (date, rates) in &self.funding {
+            for (symbol_id, rate) in rates {
+                body.extend(date.to_le_bytes());
+                body.extend(symbol_id.to_le_bytes());
+                body.extend(rate.to_le_bytes());
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(PENELOPE_PREAMBLE_LEN + body.len());
+        bytes.extend(PENELOPE_MAGIC);
+        bytes.extend(PENELOPE_VERSION.to_le_bytes());
+        bytes.extend(checksum(&body).to_le_bytes());
+        bytes.extend(body);
+        fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Reads the header of a dataset written by [save](Penelope::save) without loading quotes.
+    pub fn read_header(path: impl AsRef<Path>) -> Result<PenelopeHeader, PenelopeError> {
+        let file = std::io::BufReader::new(fs::File::open(path)?);
+        PenelopeReader { inner: file }.read_header()
+    }
+
+    /// Loads a dataset written by [save](Penelope::save). Returns an error if the file was
+    /// written with a newer version of the format or if the checksum does not match.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PenelopeError> {
+        let bytes = fs::read(path)?;
+        let mut reader = PenelopeReader {
+            inner: bytes.as_slice(),
         };
+        let header = reader.read_header()?;
 
-        if let Some(date_row) = self.inner.get_mut(&date) {
-            date_row.insert(quote.symbol.clone(), quote);
+        let found = checksum(&bytes[PENELOPE_PREAMBLE_LEN..]);
+        if found != header.checksum {
+            return Err(PenelopeError::ChecksumMismatch {
+                expected: header.checksum,
+                found,
+            });
+        }
+
+        let mut dates = Vec::new();
+        for _ in 0..header.dates {
+            dates.push(reader.read_i64()?);
+        }
+        let mut offsets = Vec::new();
+        for _ in 0..=header.dates {
+            offsets.push(reader.read_u64()? as usize);
+        }
+        let mut row_symbols = Vec::new();
+        for _ in 0..header.rows {
+            let symbol_id = reader.read_u32()?;
+            if symbol_id as usize >= header.symbols.len() {
+                return Err(PenelopeError::InvalidFormat(format!(
+                    "unknown symbol id {symbol_id}"
+                )));
+            }
+            row_symbols.push(symbol_id);
+        }
+        let mut bids = Vec::new();
+        for _ in 0..header.rows {
+            bids.push(reader.read_f64()?);
+        }
+        let mut asks = Vec::new();
+        for _ in 0..header.rows {
+            asks.push(reader.read_f64()?);
+        }
+        let mut volumes = Vec::new();
+        for _ in 0..header.rows {
+            if header.version < 2 {
+                volumes.push(None);
+                continue;
+            }
+            let volume = reader.read_f64()?;
+            volumes.push(if volume.is_nan() { None } else { Some(volume) });
+        }
+        let mut funding: BTreeMap<i64, Vec<(u32, f64)>> = BTreeMap::new();
+        if header.version >= 3 {
+            for _ in 0..reader.read_u64()? {
+                let date = reader.read_i64()?;
+                let symbol_id = reader.read_u32()?;
+                let rate = reader.read_f64()?;
+                if symbol_id as usize >= header.symbols.len() {
+                    return Err(PenelopeError::InvalidFormat(format!(
+                        "unknown symbol id {symbol_id}"
+                    )));
+                }
+                funding.entry(date).or_default().push((symbol_id, rate));
+            }
+        }
+        for rates in funding.values_mut() {
+            rates.sort_by_key(|(symbol_id, _)| *symbol_id);
+        }
+
+        if !reader.inner.is_empty() {
+            return Err(PenelopeError::InvalidFormat(
+                "unexpected bytes after end of dataset".to_string(),
+            ));
+        }
+        if offsets.first() != Some(&0)
+            || offsets.last() != Some(&(header.rows as usize))
+            || offsets.windows(2).any(|pair| pair[0] > pair[1])
+            || dates.windows(2).any(|pair| pair[0] >= pair[1])
+        {
+            return Err(PenelopeError::InvalidFormat(
+                "date index is not sorted".to_string(),
+            ));
+        }
+
+        let symbol_ids = header
+            .symbols
+            .iter()
+            .enumerate()
+            .map(|(id, symbol)| (symbol.clone(), id as u32))
+            .collect();
+        let mut source = Self {
+            symbols: header.symbols,
+            symbol_ids,
+            dates,
+            offsets,
+            row_symbols,
+            bids,
+            asks,
+            volumes,
+            funding,
+        };
+        source.sort_rows();
+        Ok(source)
+    }
+
+    /// Loads every dataset with a `.penelope` extension in `dir`, keyed by file name without the
+    /// extension. This is used by servers to mount a directory of pre-built datasets.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<HashMap<String, Self>, PenelopeError> {
+        let mut datasets = HashMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(PENELOPE_EXTENSION) {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                datasets.insert(name.to_string(), Self::load(&path)?);
+            }
+        }
+        Ok(datasets)
+    }
+
+    fn intern(&mut self, symbol: String) -> u32 {
+        if let Some(id) = self.symbol_ids.get(&symbol) {
+            return *id;
+        }
+        let id = self.symbols.len() as u32;
+        self.symbols.push(symbol.clone());
+        self.symbol_ids.insert(symbol, id);
+        id
+    }
+
+    fn insert_row(&mut self, row: usize, symbol_id: u32, bid: f64, ask: f64, volume: Option<f64>) {
+        if row == self.bids.len() {
+            self.row_symbols.push(symbol_id);
+            self.bids.push(bid);
+            self.asks.push(ask);
+            self.volumes.push(volume);
         } else {
-            let mut date_row = HashMap::new();
-            date_row.insert(quote.symbol.clone(), quote);
-            self.inner.insert(date, date_row);
-            self.dates.push(date);
+            self.row_symbols.insert(row, symbol_id);
+            self.bids.insert(row, bid);
+            self.asks.insert(row, ask);
+            self.volumes.insert(row, volume);
+        }
+    }
+
+    pub fn add_quote(&mut self, bid: f64, ask: f64, date: i64, symbol: impl Into<String> + Clone) {
+        self.insert_quote(bid, ask, None, date, symbol.into());
+    }
+
+    pub fn add_quote_with_volume(
+        &mut self,
+        bid: f64,
+        ask: f64,
+        volume: f64,
+        date: i64,
+        symbol: impl Into<String> + Clone,
+    ) {
+        self.insert_quote(bid, ask, Some(volume), date, symbol.into());
+    }
+
+    /// Adds a funding rate for `symbol`, paid on `date`. Rates are a fraction of position value,
+    /// positive rates are paid by longs to shorts.
+    pub fn add_funding_rate(&mut self, rate: f64, date: i64, symbol: impl Into<String>) {
+        let symbol_id = self.intern(symbol.into());
+        //Rates within a date are sorted by symbol id, as with quotes
+        let rates = self.funding.entry(date).or_default();
+        match rates.binary_search_by_key(&symbol_id, |(id, _)| *id) {
+            Ok(pos) => rates[pos].1 = rate,
+            Err(pos) => rates.insert(pos, (symbol_id, rate)),
+        }
+    }
+
+    fn insert_quote(&mut self, bid: f64, ask: f64, volume: Option<f64>, date: i64, symbol: String) {
+        //Inserts should be in sorted order, out of order inserts are supported but each one moves
+        //every row after it
+        let symbol_id = self.intern(symbol);
+
+        match self.dates.binary_search(&date) {
+            Ok(pos) => {
+                let rows = self.offsets[pos]..self.offsets[pos + 1];
+                match self.find_row(rows, symbol_id) {
+                    Ok(row) => {
+                        self.bids[row] = bid;
+                        self.asks[row] = ask;
+                        self.volumes[row] = volume;
+                        return;
+                    }
+                    Err(row) => self.insert_row(row, symbol_id, bid, ask, volume),
+                }
+                for offset in self.offsets.iter_mut().skip(pos + 1) {
+                    *offset += 1;
+                }
+            }
+            Err(pos) => {
+                let row = self.offsets[pos];
+                self.insert_row(row, symbol_id, bid, ask, volume);
+                self.dates.insert(pos, date);
+                self.offsets.insert(pos + 1, row);
+                for offset in self.offsets.iter_mut().skip(pos + 1) {
+                    *offset += 1;
+                }
+            }
         }
     }
 
@@ -97,8 +859,458 @@ impl Penelope {
     }
 }
 
+/// Quotes on a single date, borrowed from [Penelope]. See [Penelope::get_quotes].
+#[derive(Clone, Debug)]
+pub struct PenelopeQuotes<'a> {
+    source: &'a Penelope,
+    date: i64,
+    rows: Range<usize>,
+}
+
+impl<'a> PenelopeQuotes<'a> {
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<PenelopeQuote> {
+        let symbol_id = *self.source.symbol_ids.get(symbol)?;
+        let row = self.source.find_row(self.rows.clone(), symbol_id).ok()?;
+        Some(self.source.build_quote(row, self.date))
+    }
+
+    /// Returns each quote with the symbol it is for.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, PenelopeQuote)> + 'a {
+        let (source, date) = (self.source, self.date);
+        self.rows.clone().map(move |row| {
+            let symbol = &source.symbols[source.row_symbols[row] as usize];
+            (symbol.as_str(), source.build_quote(row, date))
+        })
+    }
+
+    pub fn to_map(&self) -> PenelopeQuoteByDate {
+        self.iter()
+            .map(|(symbol, quote)| (symbol.to_string(), quote))
+            .collect()
+    }
+}
+
+impl Input for Penelope {
+    type Quote = PenelopeQuote;
+
+    fn get_quotes(&self, date: &i64) -> Option<impl Iterator<Item = (&str, PenelopeQuote)>> {
+        Some(Penelope::get_quotes(self, date)?.iter())
+    }
+
+    fn get_symbols(&self) -> &[String] {
+        Penelope::get_symbols(self)
+    }
+
+    fn get_date(&self, pos: usize) -> Option<&i64> {
+        Penelope::get_date(self, pos)
+    }
+
+    fn has_next(&self, pos: usize) -> bool {
+        Penelope::has_next(self, pos)
+    }
+}
+
+impl Funding for Penelope {
+    fn get_funding_rates(&self, date: &i64) -> Option<FundingRateByDate> {
+        Penelope::get_funding_rates(self, date)
+    }
+
+    fn get_funding_rates_between(&self, start: &i64, end: &i64) -> Option<FundingRateByDate> {
+        Penelope::get_funding_rates_between(self, start, end)
+    }
+}
+
 impl Default for Penelope {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use time::macros::date;
+
+    use super::{Penelope, PenelopeColumns, PenelopeError};
+    use crate::source::tests::{binance_dir, write_binance_archive};
+
+    fn dataset_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("penelope_{}_{name}.penelope", std::process::id()))
+    }
+
+    fn write_csv(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("penelope_{}_{name}.csv", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    fn setup() -> Penelope {
+        let mut source = Penelope::new();
+        source.add_quote(101.00, 102.00, 100, "ABC");
+        source.add_quote(10.00, 11.00, 100, "BCD");
+        source.add_quote(102.00, 103.00, 101, "ABC");
+        source.add_quote(11.00, 12.00, 101, "BCD");
+        source.add_quote(105.00, 106.00, 103, "ABC");
+        source
+    }
+
+    #[test]
+    fn test_that_quotes_are_returned_by_date() {
+        let source = setup();
+
+        let quotes = source.get_quotes(&101).unwrap();
+        assert_eq!(quotes.len(), 2);
+        assert_eq!(quotes.get("ABC").unwrap().ask, 103.00);
+        assert_eq!(quotes.get("BCD").unwrap().date, 101);
+        assert!(source.get_quotes(&102).is_none());
+    }
+
+    #[test]
+    fn test_that_index_of_finds_date_position() {
+        let source = setup();
+
+        assert_eq!(source.index_of(&100), Some(0));
+        assert_eq!(source.index_of(&103), Some(2));
+        assert_eq!(source.index_of(&102), None);
+        assert_eq!(source.get_date(source.index_of(&101).unwrap()), Some(&101));
+    }
+
+    #[test]
+    fn test_that_quotes_between_is_inclusive_and_sorted() {
+        let source = setup();
+
+        let quotes = source.quotes_between(&101, &103);
+        assert_eq!(quotes.len(), 3);
+        assert_eq!(quotes.first().unwrap().date, 101);
+        assert_eq!(quotes.last().unwrap().date, 103);
+
+        assert_eq!(source.quotes_between(&90, &99).len(), 0);
+        assert_eq!(source.quotes_between(&0, &1000).len(), 5);
+    }
+
+    #[test]
+    fn test_that_out_of_order_and_duplicate_inserts_are_sorted() {
+        let mut source = setup();
+        source.add_quote(103.00, 104.00, 102, "ABC");
+        source.add_quote(1.00, 2.00, 99, "BCD");
+        source.add_quote(200.00, 201.00, 101, "ABC");
+
+        assert_eq!(source.get_date(0), Some(&99));
+        assert_eq!(source.get_date(3), Some(&102));
+        assert_eq!(
+            source.get_quotes_unchecked(&102).get("ABC").unwrap().bid,
+            103.00
+        );
+        assert_eq!(
+            source.get_quotes_unchecked(&101).get("ABC").unwrap().bid,
+            200.00
+        );
+        assert_eq!(source.get_quotes_unchecked(&101).len(), 2);
+
+        let dates: Vec<i64> = source
+            .quotes_between(&0, &1000)
+            .iter()
+            .map(|q| q.date)
+            .collect();
+        let mut sorted = dates.clone();
+        sorted.sort();
+        assert_eq!(dates, sorted);
+    }
+
+    #[test]
+    fn test_that_symbols_inserted_in_different_order_are_found_on_each_date() {
+        let mut source = Penelope::new();
+        source.add_quote(100.00, 101.00, 100, "ABC");
+        source.add_quote(200.00, 201.00, 100, "BCD");
+        source.add_quote(300.00, 301.00, 100, "CDE");
+        source.add_quote(301.00, 302.00, 101, "CDE");
+        source.add_quote(101.00, 102.00, 101, "ABC");
+        source.add_quote(201.00, 202.00, 101, "BCD");
+        source.add_quote(202.00, 203.00, 101, "BCD");
+
+        let quotes = source.get_quotes(&101).unwrap();
+        assert_eq!(quotes.len(), 3);
+        assert_eq!(quotes.get("ABC").unwrap().bid, 101.00);
+        assert_eq!(quotes.get("BCD").unwrap().bid, 202.00);
+        assert_eq!(quotes.get("CDE").unwrap().bid, 301.00);
+        assert!(quotes.get("XYZ").is_none());
+    }
+
+    #[test]
+    fn test_that_csv_loads_with_default_columns() {
+        let path = write_csv(
+            "default",
+            "date,symbol,bid,ask\n100,ABC,101.0,102.0\n100,BCD,10.0,11.0\n101,ABC,102.0,103.0\n",
+        );
+        let source = Penelope::from_csv(&path, &PenelopeColumns::default()).unwrap();
+
+        assert_eq!(source.get_date(0), Some(&100));
+        assert_eq!(source.get_date(1), Some(&101));
+        assert!(!source.has_next(2));
+        let quotes = source.get_quotes_unchecked(&100);
+        assert_eq!(quotes.len(), 2);
+        assert_eq!(quotes.get("BCD").unwrap().ask, 11.0);
+    }
+
+    #[test]
+    fn test_that_csv_loads_with_column_mapping() {
+        let path = write_csv(
+            "mapping",
+            "ticker,ts,best_ask,best_bid,size\nABC,100,102.0,101.0,500.0\n",
+        );
+        let columns = PenelopeColumns {
+            date: "ts".to_string(),
+            symbol: "ticker".to_string(),
+            bid: "best_bid".to_string(),
+            ask: "best_ask".to_string(),
+            volume: Some("size".to_string()),
+        };
+        let source = Penelope::from_csv(&path, &columns).unwrap();
+
+        let quote = source
+            .get_quotes_unchecked(&100)
+            .get("ABC")
+            .unwrap()
+            .clone();
+        assert_eq!(quote.bid, 101.0);
+        assert_eq!(quote.ask, 102.0);
+        assert_eq!(quote.volume, Some(500.0));
+    }
+
+    #[test]
+    fn test_that_csv_with_missing_column_errors() {
+        let path = write_csv("missing", "date,symbol,bid\n100,ABC,101.0\n");
+        let res = Penelope::from_csv(&path, &PenelopeColumns::default());
+
+        assert!(matches!(res, Err(PenelopeError::MissingColumn(col)) if col == "ask"));
+    }
+
+    #[test]
+    fn test_that_csv_with_malformed_row_reports_line() {
+        let path = write_csv(
+            "malformed",
+            "date,symbol,bid,ask\n100,ABC,101.0,102.0\n101,ABC,abc,103.0\n",
+        );
+        let res = Penelope::from_csv(&path, &PenelopeColumns::default());
+
+        assert!(matches!(
+            res,
+            Err(PenelopeError::MalformedRow { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_that_csv_with_unsorted_rows_errors() {
+        let path = write_csv(
+            "unsorted",
+            "date,symbol,bid,ask\n101,ABC,101.0,102.0\n100,ABC,102.0,103.0\n",
+        );
+        let res = Penelope::from_csv(&path, &PenelopeColumns::default());
+
+        assert!(matches!(
+            res,
+            Err(PenelopeError::UnsortedRow {
+                line: 3,
+                date: 100,
+                previous: 101
+            })
+        ));
+    }
+
+    #[test]
+    fn test_that_binance_loads_multiple_symbols_in_date_order() {
+        let dir = binance_dir("penelope");
+        write_binance_archive(
+            &dir,
+            "BTCUSDT-1m-2022-08-03",
+            "1659484800000,100.0,101.0,99.0,100.5,1.0,1659484859999,100.0,10,0.5,50.0\n1659484860000,100.5,101.0,99.0,101.5,1.0,1659484919999,100.0,10,0.5,50.0\n",
+        );
+        write_binance_archive(
+            &dir,
+            "ETHUSDT-1m-2022-08-03",
+            "1659484800000,10.0,11.0,9.0,10.5,1.0,1659484859999,10.0,10,0.5,5.0\n",
+        );
+
+        let source = Penelope::from_binance(
+            &dir,
+            vec!["BTCUSDT", "ETHUSDT"],
+            date!(2022 - 08 - 03),
+            date!(2022 - 08 - 03),
+        )
+        .unwrap();
+
+        assert_eq!(source.get_date(0), Some(&1659484800));
+        assert_eq!(source.get_date(1), Some(&1659484859));
+        assert_eq!(source.get_date(2), Some(&1659484860));
+        let first = source.get_quotes_unchecked(&1659484800);
+        assert_eq!(first.get("BTCUSDT").unwrap().bid, 100.0);
+        assert_eq!(first.get("ETHUSDT").unwrap().ask, 10.0);
+    }
+
+    #[test]
+    fn test_that_saved_dataset_loads_with_same_quotes() {
+        let mut source = setup();
+        source.add_quote_with_volume(12.00, 13.00, 1000.0, 103, "BCD");
+        let path = dataset_path("roundtrip");
+        source.save(&path).unwrap();
+
+        let loaded = Penelope::load(&path).unwrap();
+        assert_eq!(loaded.get_date(0), Some(&100));
+        assert_eq!(loaded.index_of(&103), Some(2));
+        assert!(!loaded.has_next(3));
+        let quotes = loaded.get_quotes(&101).unwrap();
+        assert_eq!(quotes.len(), 2);
+        assert_eq!(quotes.get("BCD").unwrap().ask, 12.00);
+        assert_eq!(quotes.get("BCD").unwrap().volume, None);
+        assert_eq!(loaded.quotes_between(&100, &103).len(), 6);
+        let quotes = loaded.get_quotes(&103).unwrap();
+        assert_eq!(quotes.get("BCD").unwrap().volume, Some(1000.0));
+    }
+
+    #[test]
+    fn test_that_version_one_dataset_loads_without_volumes() {
+        let path = dataset_path("version_one");
+        setup().save(&path).unwrap();
+
+        //Version one is identical to version three without the volume column and the funding
+        //count at the end
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 5 * 8 - 8);
+        bytes[4..6].copy_from_slice(&1_u16.to_le_bytes());
+        let checksum = super::checksum(&bytes[14..]);
+        bytes[6..14].copy_from_slice(&checksum.to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+
+        let loaded = Penelope::load(&path).unwrap();
+        let quotes = loaded.get_quotes(&100).unwrap();
+        assert_eq!(quotes.get("ABC").unwrap().bid, 101.00);
+        assert_eq!(quotes.get("ABC").unwrap().volume, None);
+    }
+
+    #[test]
+    fn test_that_funding_rates_are_returned_by_date_and_saved() {
+        let path = dataset_path("funding");
+        let mut source = setup();
+        source.add_funding_rate(0.0001, 101, "ABC");
+        source.add_funding_rate(-0.0002, 101, "BCD");
+        source.add_funding_rate(0.0003, 103, "ABC");
+        source.save(&path).unwrap();
+
+        let loaded = Penelope::load(&path).unwrap();
+        assert!(loaded.get_funding_rates(&100).is_none());
+        let rates = loaded.get_funding_rates(&101).unwrap();
+        assert_eq!(rates.get("ABC"), Some(&0.0001));
+        assert_eq!(rates.get("BCD"), Some(&-0.0002));
+        assert_eq!(loaded.get_funding_rates(&103).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_that_funding_rates_are_loaded_from_csv_and_summed_between_dates() {
+        let path = write_csv(
+            "funding",
+            "date,symbol,rate\n103,ABC,0.0003\n101,BCD,-0.0002\n101,ABC,0.0001\n",
+        );
+        let mut source = setup();
+        source.add_funding_from_csv(&path).unwrap();
+
+        let rates = source.get_funding_rates(&101).unwrap();
+        assert_eq!(rates.get("ABC"), Some(&0.0001));
+        assert_eq!(rates.get("BCD"), Some(&-0.0002));
+
+        //Start is excluded and end is included
+        let summed = source.get_funding_rates_between(&100, &103).unwrap();
+        assert!((summed.get("ABC").unwrap() - 0.0004).abs() < 1e-12);
+        assert_eq!(summed.get("BCD"), Some(&-0.0002));
+        assert!(source.get_funding_rates_between(&101, &102).is_none());
+    }
+
+    #[test]
+    fn test_that_funding_csv_with_malformed_rate_errors() {
+        let path = write_csv("funding_malformed", "date,symbol,rate\n101,ABC,abc\n");
+
+        let res = setup().add_funding_from_csv(&path);
+        assert!(matches!(
+            res,
+            Err(PenelopeError::MalformedRow { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_that_header_carries_symbols_and_date_range() {
+        let path = dataset_path("header");
+        setup().save(&path).unwrap();
+
+        let header = Penelope::read_header(&path).unwrap();
+        assert_eq!(header.symbols, vec!["ABC".to_string(), "BCD".to_string()]);
+        assert_eq!(header.start, Some(100));
+        assert_eq!(header.end, Some(103));
+        assert_eq!(header.dates, 3);
+        assert_eq!(header.rows, 5);
+    }
+
+    #[test]
+    fn test_that_corrupted_dataset_fails_checksum() {
+        let path = dataset_path("corrupted");
+        setup().save(&path).unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+
+        let res = Penelope::load(&path);
+        assert!(matches!(res, Err(PenelopeError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_that_dataset_with_unknown_version_errors() {
+        let path = dataset_path("version");
+        setup().save(&path).unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[4] = 99;
+        std::fs::write(&path, bytes).unwrap();
+
+        let res = Penelope::load(&path);
+        assert!(matches!(res, Err(PenelopeError::UnsupportedVersion(99))));
+    }
+
+    #[test]
+    fn test_that_truncated_dataset_errors() {
+        let path = dataset_path("truncated");
+        setup().save(&path).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..10]).unwrap();
+
+        let res = Penelope::load(&path);
+        assert!(matches!(res, Err(PenelopeError::InvalidFormat(..))));
+    }
+
+    #[test]
+    fn test_that_dataset_dir_is_keyed_by_file_name() {
+        let dir = std::env::temp_dir().join(format!("penelope_{}_dir", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        setup().save(dir.join("first.penelope")).unwrap();
+        Penelope::random(10, vec!["ABC"])
+            .save(dir.join("second.penelope"))
+            .unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a dataset").unwrap();
+
+        let datasets = Penelope::load_dir(&dir).unwrap();
+        assert_eq!(datasets.len(), 2);
+        assert!(datasets.get("first").unwrap().get_quotes(&103).is_some());
+        assert!(datasets.get("second").unwrap().get_quotes(&109).is_some());
+    }
+}
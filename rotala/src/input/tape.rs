@@ -0,0 +1,247 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::penelope::{Penelope, PenelopeQuote};
+use super::{DatedQuotes, Input, Side};
+use crate::source::{get_trade_prints, SourceError, TradePrint};
+
+/// Single trade on the tape. The aggressor is the side that took liquidity, so a buy aggressor
+/// lifted the offer and a sell aggressor hit the bid.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TapePrint {
+    pub price: f64,
+    pub size: f64,
+    pub aggressor: Side,
+}
+
+/// Latest quote for a symbol at `date` with the trades printed on that date, in the order they
+/// printed. Quotes that are carried forward to a date with trades have no volume.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TapeQuote {
+    pub bid: f64,
+    pub ask: f64,
+    pub symbol: String,
+    pub date: i64,
+    #[serde(default)]
+    pub volume: Option<f64>,
+    #[serde(default)]
+    pub trades: Vec<TapePrint>,
+}
+
+impl TapeQuote {
+    fn new(quote: &PenelopeQuote, date: i64, volume: Option<f64>) -> Self {
+        Self {
+            bid: quote.bid,
+            ask: quote.ask,
+            symbol: quote.symbol.clone(),
+            date,
+            volume,
+            trades: Vec::new(),
+        }
+    }
+}
+
+pub type TapeQuoteByDate = HashMap<String, TapeQuote>;
+
+#[derive(Debug)]
+pub enum TapeError {
+    Source(SourceError),
+    TradeBeforeQuote { symbol: String, date: i64 },
+}
+
+impl Error for TapeError {}
+
+impl fmt::Display for TapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TapeError::Source(err) => write!(f, "Source: {err}"),
+            TapeError::TradeBeforeQuote { symbol, date } => {
+                write!(
+                    f,
+                    "TradeBeforeQuote: {symbol} traded on {date} with no quote"
+                )
+            }
+        }
+    }
+}
+
+impl From<SourceError> for TapeError {
+    fn from(value: SourceError) -> Self {
+        TapeError::Source(value)
+    }
+}
+
+/// Tape replays individual trades interleaved with quotes. Every date with either a quote or a
+/// trade is a date in the dataset. On dates with trades but no new quote, the last quote for the
+/// symbol is carried forward so that exchanges can see trades against the prevailing spread.
+/// Trades before the first quote for a symbol have no spread to execute against, so they return an
+/// error rather than being dropped.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Tape {
+    quotes: DatedQuotes<TapeQuote>,
+}
+
+impl Tape {
+    pub fn new(quotes: &Penelope, trades: Vec<TradePrint>) -> Result<Self, TapeError> {
+        let mut prints: BTreeMap<i64, HashMap<String, Vec<TapePrint>>> = BTreeMap::new();
+        for trade in trades {
+            prints
+                .entry(trade.date)
+                .or_default()
+                .entry(trade.symbol)
+                .or_default()
+                .push(TapePrint {
+                    price: trade.price,
+                    size: trade.size,
+                    aggressor: trade.aggressor,
+                });
+        }
+
+        let mut dates: BTreeSet<i64> = prints.keys().copied().collect();
+        let mut pos = 0;
+        while let Some(date) = quotes.get_date(pos) {
+            dates.insert(*date);
+            pos += 1;
+        }
+
+        let mut tape = Self::default();
+        for symbol in quotes.get_symbols() {
+            tape.quotes.add_symbol(symbol);
+        }
+        let mut last: HashMap<String, PenelopeQuote> = HashMap::new();
+        for date in dates {
+            let mut quotes_on_date = HashMap::new();
+            if let Some(view) = quotes.get_quotes(&date) {
+                for (symbol, quote) in view.iter() {
+                    quotes_on_date.insert(
+                        symbol.to_string(),
+                        TapeQuote::new(&quote, date, quote.volume),
+                    );
+                    last.insert(symbol.to_string(), quote);
+                }
+            }
+            for (symbol, trades) in prints.remove(&date).unwrap_or_default() {
+                let quote = match quotes_on_date.get_mut(&symbol) {
+                    Some(quote) => quote,
+                    None => match last.get(&symbol) {
+                        Some(quote) => quotes_on_date
+                            .entry(symbol)
+                            .or_insert(TapeQuote::new(quote, date, None)),
+                        None => return Err(TapeError::TradeBeforeQuote { symbol, date }),
+                    },
+                };
+                quote.trades = trades;
+            }
+            for (symbol, quote) in quotes_on_date {
+                tape.quotes.insert(&symbol, date, quote);
+            }
+        }
+        Ok(tape)
+    }
+
+    /// Loads trades for each symbol from a local CSV file, see [get_trade_prints] for the
+    /// expected format, and interleaves them with `quotes`.
+    pub fn from_csv(quotes: &Penelope, trade_files: Vec<(&str, &Path)>) -> Result<Self, TapeError> {
+        let mut trades = Vec::new();
+        for (symbol, path) in trade_files {
+            trades.extend(get_trade_prints(path, symbol)?);
+        }
+        Self::new(quotes, trades)
+    }
+
+    pub fn get_quotes(&self, date: &i64) -> Option<&TapeQuoteByDate> {
+        self.quotes.get(date)
+    }
+}
+
+impl Input for Tape {
+    type Quote = TapeQuote;
+
+    fn get_quotes(&self, date: &i64) -> Option<impl Iterator<Item = (&str, TapeQuote)>> {
+        Input::get_quotes(&self.quotes, date)
+    }
+
+    fn get_symbols(&self) -> &[String] {
+        self.quotes.get_symbols()
+    }
+
+    fn get_date(&self, pos: usize) -> Option<&i64> {
+        self.quotes.get_date(pos)
+    }
+
+    fn has_next(&self, pos: usize) -> bool {
+        self.quotes.has_next(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Side, Tape, TapeError};
+    use crate::input::penelope::Penelope;
+    use crate::input::Input;
+    use crate::source::TradePrint;
+
+    fn print(date: i64, symbol: &str, price: f64, aggressor: Side) -> TradePrint {
+        TradePrint {
+            symbol: symbol.to_string(),
+            date,
+            price,
+            size: 10.0,
+            aggressor,
+        }
+    }
+
+    #[test]
+    fn test_that_trades_are_interleaved_with_quotes() {
+        let mut quotes = Penelope::new();
+        quotes.add_quote_with_volume(100.0, 101.0, 50.0, 100, "ABC");
+        quotes.add_quote(102.0, 103.0, 103, "ABC");
+
+        let tape = Tape::new(
+            &quotes,
+            vec![
+                print(100, "ABC", 101.0, Side::Buy),
+                print(101, "ABC", 100.5, Side::Sell),
+                print(101, "ABC", 100.0, Side::Sell),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(tape.get_date(0), Some(&100));
+        assert_eq!(tape.get_date(1), Some(&101));
+        assert_eq!(tape.get_date(2), Some(&103));
+        assert!(!tape.has_next(3));
+
+        let first = &tape.get_quotes(&100).unwrap()["ABC"];
+        assert_eq!(first.volume, Some(50.0));
+        assert_eq!(first.trades.len(), 1);
+
+        //Quote is carried forward to a date with only trades
+        let carried = tape.get_quotes(&101).unwrap();
+        assert_eq!(carried.len(), 1);
+        assert_eq!(carried["ABC"].bid, 100.0);
+        assert_eq!(carried["ABC"].date, 101);
+        assert_eq!(carried["ABC"].volume, None);
+        assert_eq!(carried["ABC"].trades[1].price, 100.0);
+        assert_eq!(carried["ABC"].trades[1].aggressor, Side::Sell);
+
+        assert!(tape.get_quotes(&103).unwrap()["ABC"].trades.is_empty());
+    }
+
+    #[test]
+    fn test_that_trades_before_first_quote_are_rejected() {
+        let mut quotes = Penelope::new();
+        quotes.add_quote(100.0, 101.0, 100, "ABC");
+
+        let err = Tape::new(&quotes, vec![print(99, "ABC", 100.0, Side::Buy)]).unwrap_err();
+        assert!(matches!(err, TapeError::TradeBeforeQuote { date: 99, .. }));
+
+        //Symbol that never quotes has no spread at any date
+        let err = Tape::new(&quotes, vec![print(101, "BCD", 10.0, Side::Sell)]).unwrap_err();
+        assert!(matches!(err, TapeError::TradeBeforeQuote { date: 101, .. }));
+    }
+}
@@ -14,21 +14,22 @@
 //!
 //! A single exchange implementation is composed of:
 //! - An input, [Penelope](crate::input::penelope::Penelope) is an example. The input produces
-//! quotes and will define the format of quotes that exchanges wishing to use the source must use.
+//!   quotes in its own format, exchanges can use any [Input](crate::input::Input) with quotes that
+//!   convert into the quote type of the exchange.
 //! - An orderbook implementation, [Diana](crate::orderbook::diana::Diana) is an example. The
-//! orderbook contains the core execution logic and defines the format of orders and trades. This
-//! is distinct from an exchange as the an orderbook could be LOB, could use candles, etc. And this
-//! varies in a distinct way from the interface presented to clients.
+//!   orderbook contains the core execution logic and defines the format of orders and trades. This
+//!   is distinct from an exchange as the an orderbook could be LOB, could use candles, etc. And this
+//!   varies in a distinct way from the interface presented to clients.
 //! - An exchange implementation, [Uist](crate::exchange::uist::UistV1) is an example. In terms of
-//! code, this ends up being a fairly thin wrapper depending more on the kind of clients than
-//! the actual execution logic used by the orderbook. To explain more from above, the
-//! exchange is the external interface that provides a set of possible operations to users and does
-//! not concern itself too closely with how things are implemented (but it does have to bind to s
-//! single orderbook implementation). Uist, for example, has a lot of additional methods concerning
-//! orchestration and how clients can match state with exchange.
+//!   code, this ends up being a fairly thin wrapper depending more on the kind of clients than
+//!   the actual execution logic used by the orderbook. To explain more from above, the
+//!   exchange is the external interface that provides a set of possible operations to users and does
+//!   not concern itself too closely with how things are implemented (but it does have to bind to s
+//!   single orderbook implementation). Uist, for example, has a lot of additional methods concerning
+//!   orchestration and how clients can match state with exchange.
 //! - The server implementation of the exchange returning JSON responses over the exchange impl.
 //! - The client implementation of the exchange which provides a Rust API for the server, as much
-//! for documenting how clients can call the server.
+//!   for documenting how clients can call the server.
 //!
 //! In addition to all this, we have data sources which call some external source and are bound into
 //! the exchange: for example, the Uist exchange can be created using a Binance input.
@@ -45,24 +46,40 @@
 //! - loop
 //!     - fetch_quotes, returns price information for current date
 //!     - insert_order/delete_order
-//!     - tick, returning whether there is another tick and trades executed on the last tick
+//!     - tick, returning whether there is another tick, trades executed on the last tick and
+//!       changes to the state of orders
 //!
 //! Uist has no client identifiers. It is possible to run more than one strategy against Uist but
 //! it is not advisable as it will be difficult to work out which trades are allocated to each
 //! strategy. The server does support concurrent execution but without client identifiers it isn't
 //! possible to return a properly synchronized result.
 //!
-//! Order_id is only set once order is passed into the orderbook. Exchange only passes trades to
-//! orderbook once tick is called and these pending orders will be re-ordered so that sells are
-//! executed before buys. Order_id does not always reflect execution priority.
+//! Order_id is returned when the order is inserted. Exchange only passes orders to the orderbook
+//! once tick is called and these pending orders will be re-ordered so that sells are executed
+//! before buys. Order_id reflects the order of insertion and does not always reflect execution
+//! priority.
 //!
-//! Once an order is inserted, it cannot be deleted on the same tick as an order only gets an id
-//! once it is passed into the orderbook.
+//! An order can be deleted before the next tick, in which case it never reaches the orderbook.
+//!
+//! Orders carry a time-in-force (GTC, IOC, FOK, DAY, GTD) which controls how long they rest in
+//! the orderbook. Orders that expire are reported in the tick response, see
+//! [TimeInForce](crate::exchange::uist_v1::TimeInForce).
 //!
 //! ``
-//! cargo run --bin uist_server_v1 [ipv4_address] [port]
+//! cargo run --bin uist_server_v1 [ipv4_address] [port] [dataset_dir]
+//! cargo run --bin uist_server_v1 --config [path]
 //! ``
 //!
+//! The config file lists named datasets to serve, see [config](crate::http::config) for the
+//! format. Without any datasets the server serves a single random dataset named `RANDOM`.
+//!
+//! # Islay
+//!
+//! [IslayV1](crate::exchange::islay_v1::IslayV1) executes orders against L2 depth replayed by
+//! [Lobster](crate::input::lobster::Lobster). Marketable orders walk the book and resting limit
+//! orders track their position in the queue, filling only once enough volume has traded at their
+//! price. Islay implements [Exchange](crate::exchange::Exchange) but has no server yet.
+//!
 //! # Development priorities
 //!
 //! Short-term:
@@ -72,7 +89,7 @@
 //! - Add another exchange so separation of concerns is clearer
 //!
 //! Long-term:
-//! - Add orderbook with L2 data, this is going to require L2 sources
+//! - Add a server for Islay and more L2 sources
 pub mod exchange;
 pub mod http;
 pub mod input;
@@ -1,9 +1,64 @@
 //! Sources are external data sources that are used to create Inputs and then Exchanges. Source
 //! creation should be hidden from users and embedded within the creation of Inputs. Each Source
 //! should have its own internal format that is converted into an Input format within the Input.
-use std::io::{Cursor, Write};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use time::Date;
+
+use crate::input::Side;
+
+#[derive(Debug)]
+pub enum SourceError {
+    MissingFile(PathBuf),
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    Csv(csv::Error),
+    MalformedRow {
+        file: String,
+        line: u64,
+        reason: String,
+    },
+}
+
+impl Error for SourceError {}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::MissingFile(path) => write!(f, "MissingFile: {}", path.display()),
+            SourceError::Io(err) => write!(f, "Io: {err}"),
+            SourceError::Zip(err) => write!(f, "Zip: {err}"),
+            SourceError::Csv(err) => write!(f, "Csv: {err}"),
+            SourceError::MalformedRow { file, line, reason } => {
+                write!(f, "MalformedRow: {file} line {line}, {reason}")
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for SourceError {
+    fn from(value: std::io::Error) -> Self {
+        SourceError::Io(value)
+    }
+}
+
+impl From<zip::result::ZipError> for SourceError {
+    fn from(value: zip::result::ZipError) -> Self {
+        SourceError::Zip(value)
+    }
+}
+
+impl From<csv::Error> for SourceError {
+    fn from(value: csv::Error) -> Self {
+        SourceError::Csv(value)
+    }
+}
 
 pub struct BinanceKlinesQuote {
+    pub symbol: String,
     pub open_date: i64,
     pub open: f64,
     pub high: f64,
@@ -17,52 +72,294 @@ pub struct BinanceKlinesQuote {
     pub taker_buy_asset_volume: f64,
 }
 
-// Building this way isn't performant but this is easier to reason about atm
-pub fn get_binance_1m_klines() -> Vec<BinanceKlinesQuote> {
-    let url =
-        "https://data.binance.vision/data/spot/daily/klines/BTCUSDT/1m/BTCUSDT-1m-2022-08-03.zip";
+// Binance switched spot timestamps from milliseconds to microseconds at the start of 2025, anything
+// larger than this is too far in the future to be milliseconds.
+const BINANCE_MICROS_THRESHOLD: i64 = 100_000_000_000_000;
+
+fn binance_timestamp_to_secs(val: i64) -> i64 {
+    if val >= BINANCE_MICROS_THRESHOLD {
+        val / 1_000_000
+    } else {
+        val / 1000
+    }
+}
 
+/// Reads daily 1m kline archives in the format distributed by data.binance.vision (i.e.
+/// `BTCUSDT-1m-2022-08-03.zip`) from a local directory. Every day in the range from `start` to
+/// `end`, inclusive, must have an archive in the directory.
+///
+/// Rows are returned in the order that they appear in the archives, which is sorted by date within
+/// each day.
+pub fn get_binance_1m_klines(
+    dir: impl AsRef<Path>,
+    symbol: &str,
+    start: Date,
+    end: Date,
+) -> Result<Vec<BinanceKlinesQuote>, SourceError> {
     let mut result = Vec::new();
 
-    if let Ok(resp) = reqwest::blocking::get(url) {
-        if let Ok(contents) = resp.bytes() {
-            let mut c = Cursor::new(Vec::new());
-            let _res = c.write(&contents);
-
-            if let Ok(mut zip) = zip::ZipArchive::new(c) {
-                for i in 0..zip.len() {
-                    if let Ok(mut zip_file) = zip.by_index(i) {
-                        let mut rdr = csv::Reader::from_reader(&mut zip_file);
-                        for row in rdr.records().flatten() {
-                            let open_date = (row[0].parse::<i64>().unwrap()) / 1000;
-                            let open = row[1].parse::<f64>().unwrap();
-                            let high = row[2].parse::<f64>().unwrap();
-                            let low = row[3].parse::<f64>().unwrap();
-                            let close = row[4].parse::<f64>().unwrap();
-                            let volume = row[5].parse::<f64>().unwrap();
-                            let close_date = (row[6].parse::<i64>().unwrap()) / 1000;
-                            let quote_volume = row[7].parse::<f64>().unwrap();
-                            let trades_number = row[8].parse::<i64>().unwrap();
-                            let taker_buy_volume = row[9].parse::<f64>().unwrap();
-                            let taker_buy_asset_volume = row[10].parse::<f64>().unwrap();
-                            result.push(BinanceKlinesQuote {
-                                open_date,
-                                open,
-                                high,
-                                low,
-                                close,
-                                volume,
-                                close_date,
-                                quote_volume,
-                                trades_number,
-                                taker_buy_volume,
-                                taker_buy_asset_volume,
-                            });
-                        }
-                    }
+    let mut date = start;
+    while date <= end {
+        let file_name = format!("{symbol}-1m-{date}.zip");
+        let path = dir.as_ref().join(&file_name);
+        if !path.exists() {
+            return Err(SourceError::MissingFile(path));
+        }
+
+        let mut zip = zip::ZipArchive::new(File::open(&path)?)?;
+        for i in 0..zip.len() {
+            let zip_file = zip.by_index(i)?;
+            let mut rdr = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(zip_file);
+            for row in rdr.records() {
+                let row = row?;
+                let line = row.position().map(|pos| pos.line()).unwrap_or_default();
+
+                // Some archives are distributed with a header row
+                if line == 1 && row.get(0).is_some_and(|val| val.parse::<i64>().is_err()) {
+                    continue;
+                }
+
+                if row.len() < 11 {
+                    return Err(SourceError::MalformedRow {
+                        file: file_name.clone(),
+                        line,
+                        reason: format!("expected 11 columns, found {}", row.len()),
+                    });
                 }
+
+                let parse_i64 = |pos: usize| -> Result<i64, SourceError> {
+                    row[pos]
+                        .trim()
+                        .parse::<i64>()
+                        .map_err(|_| SourceError::MalformedRow {
+                            file: file_name.clone(),
+                            line,
+                            reason: format!("cannot parse column {pos} from {:?}", &row[pos]),
+                        })
+                };
+                let parse_f64 = |pos: usize| -> Result<f64, SourceError> {
+                    row[pos]
+                        .trim()
+                        .parse::<f64>()
+                        .map_err(|_| SourceError::MalformedRow {
+                            file: file_name.clone(),
+                            line,
+                            reason: format!("cannot parse column {pos} from {:?}", &row[pos]),
+                        })
+                };
+
+                result.push(BinanceKlinesQuote {
+                    symbol: symbol.to_string(),
+                    open_date: binance_timestamp_to_secs(parse_i64(0)?),
+                    open: parse_f64(1)?,
+                    high: parse_f64(2)?,
+                    low: parse_f64(3)?,
+                    close: parse_f64(4)?,
+                    volume: parse_f64(5)?,
+                    close_date: binance_timestamp_to_secs(parse_i64(6)?),
+                    quote_volume: parse_f64(7)?,
+                    trades_number: parse_i64(8)?,
+                    taker_buy_volume: parse_f64(9)?,
+                    taker_buy_asset_volume: parse_f64(10)?,
+                });
             }
         }
+
+        match date.next_day() {
+            Some(next) => date = next,
+            None => break,
+        }
+    }
+    Ok(result)
+}
+
+pub struct TradePrint {
+    pub symbol: String,
+    pub date: i64,
+    pub price: f64,
+    pub size: f64,
+    pub aggressor: Side,
+}
+
+/// Reads individual trades for one symbol from a local CSV file with the columns time, price,
+/// size and aggressor side. Time is in seconds and can be fractional, fractions are dropped. Side
+/// is `buy`/`b` or `sell`/`s` in any case. An optional header row is skipped.
+///
+/// Rows are returned in the order that they appear in the file.
+pub fn get_trade_prints(
+    path: impl AsRef<Path>,
+    symbol: &str,
+) -> Result<Vec<TradePrint>, SourceError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(SourceError::MissingFile(path.to_path_buf()));
+    }
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut result = Vec::new();
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(path)?;
+    for row in rdr.records() {
+        let row = row?;
+        let line = row.position().map(|pos| pos.line()).unwrap_or_default();
+
+        if line == 1
+            && row
+                .get(0)
+                .is_some_and(|val| val.trim().parse::<f64>().is_err())
+        {
+            continue;
+        }
+
+        let malformed = |reason: String| SourceError::MalformedRow {
+            file: file_name.clone(),
+            line,
+            reason,
+        };
+        if row.len() < 4 {
+            return Err(malformed(format!(
+                "expected 4 columns, found {}",
+                row.len()
+            )));
+        }
+        let parse_f64 = |pos: usize| -> Result<f64, SourceError> {
+            let val = row[pos].trim();
+            val.parse::<f64>()
+                .ok()
+                .filter(|val| val.is_finite())
+                .ok_or_else(|| malformed(format!("cannot parse column {pos} from {val:?}")))
+        };
+
+        let aggressor = match row[3].trim().to_ascii_lowercase().as_str() {
+            "buy" | "b" => Side::Buy,
+            "sell" | "s" => Side::Sell,
+            val => return Err(malformed(format!("unknown aggressor side {val:?}"))),
+        };
+        result.push(TradePrint {
+            symbol: symbol.to_string(),
+            date: parse_f64(0)?.floor() as i64,
+            price: parse_f64(1)?,
+            size: parse_f64(2)?,
+            aggressor,
+        });
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use time::macros::date;
+
+    use super::{get_binance_1m_klines, get_trade_prints, Side, SourceError};
+
+    pub(crate) fn write_binance_archive(dir: &Path, file_name: &str, rows: &str) {
+        let file = std::fs::File::create(dir.join(format!("{file_name}.zip"))).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        zip.start_file(
+            format!("{file_name}.csv"),
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+        zip.write_all(rows.as_bytes()).unwrap();
+        zip.finish().unwrap();
+    }
+
+    pub(crate) fn binance_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("binance_{}_{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_that_binance_archives_load_over_date_range() {
+        let dir = binance_dir("range");
+        write_binance_archive(
+            &dir,
+            "BTCUSDT-1m-2022-08-03",
+            "1659484800000,100.0,101.0,99.0,100.5,1.0,1659484859999,100.0,10,0.5,50.0\n",
+        );
+        write_binance_archive(
+            &dir,
+            "BTCUSDT-1m-2022-08-04",
+            "1659571200000,200.0,201.0,199.0,200.5,1.0,1659571259999,200.0,10,0.5,100.0\n",
+        );
+
+        let res = get_binance_1m_klines(
+            &dir,
+            "BTCUSDT",
+            date!(2022 - 08 - 03),
+            date!(2022 - 08 - 04),
+        )
+        .unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].open_date, 1659484800);
+        assert_eq!(res[0].close_date, 1659484859);
+        assert_eq!(res[1].open, 200.0);
+    }
+
+    #[test]
+    fn test_that_missing_binance_archive_errors() {
+        let dir = binance_dir("missing");
+        let res = get_binance_1m_klines(
+            &dir,
+            "ETHUSDT",
+            date!(2022 - 08 - 03),
+            date!(2022 - 08 - 03),
+        );
+        assert!(matches!(res, Err(SourceError::MissingFile(..))));
+    }
+
+    #[test]
+    fn test_that_malformed_binance_row_errors_with_line() {
+        let dir = binance_dir("malformed");
+        write_binance_archive(
+            &dir,
+            "BTCUSDT-1m-2022-08-03",
+            "1659484800000,100.0,101.0,99.0,100.5,1.0,1659484859999,100.0,10,0.5,50.0\n1659484860000,abc,101.0,99.0,100.5,1.0,1659484919999,100.0,10,0.5,50.0\n",
+        );
+
+        let res = get_binance_1m_klines(
+            &dir,
+            "BTCUSDT",
+            date!(2022 - 08 - 03),
+            date!(2022 - 08 - 03),
+        );
+        assert!(matches!(
+            res,
+            Err(SourceError::MalformedRow { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_that_trade_prints_load_with_header() {
+        let dir = binance_dir("prints");
+        let path = dir.join("ABC-trades.csv");
+        std::fs::write(
+            &path,
+            "time,price,size,side\n100.25,10.5,3,buy\n101,10.4,2,S\n",
+        )
+        .unwrap();
+
+        let res = get_trade_prints(&path, "ABC").unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].date, 100);
+        assert_eq!(res[0].aggressor, Side::Buy);
+        assert_eq!(res[1].price, 10.4);
+        assert_eq!(res[1].aggressor, Side::Sell);
+
+        std::fs::write(&path, "100,10.5,3,cross\n").unwrap();
+        assert!(matches!(
+            get_trade_prints(&path, "ABC"),
+            Err(SourceError::MalformedRow { line: 1, .. })
+        ));
     }
-    result
 }
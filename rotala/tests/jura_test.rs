@@ -9,5 +9,5 @@ fn test_that_uist_works() {
     let order = Order::market_buy(0, "100.0", "97.00");
     exchange.insert_order(order);
 
-    exchange.tick(source.get_quotes_unchecked(&100));
+    exchange.tick(&source.get_quotes_unchecked(&100));
 }
@@ -11,5 +11,5 @@ fn test_that_uist_works() {
     let order = Order::market_buy("ABC", 100.0);
     exchange.insert_order(order);
 
-    exchange.tick(source.get_quotes_unchecked(&100));
+    exchange.tick(&source.get_quotes_unchecked(&100));
 }